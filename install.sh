@@ -34,8 +34,28 @@ echo "Adding environment variables to $CONFIG_FILE"
  echo 'export CQL_LSP_DB_URL="127.0.0.1"'
  echo 'export CQL_LSP_DB_PASSWD="cassandra"'
  echo 'export CQL_LSP_DB_USER="cassandra"'
+ echo 'export CQL_LSP_DB_TIMEOUT="3"'
+ echo 'export CQL_LSP_DB_RETRIES="2"'
+ echo 'export CQL_LSP_OFFLINE="false"'
+ echo 'export CQL_LSP_SCHEMA_SNAPSHOT="false"'
  echo 'export CQL_LSP_ENABLE_LOGGING="false"'
+ echo 'export CQL_LSP_LOG_LEVEL="info"'
  echo 'export CQL_LSP_TYPE_ALIGNMENT_OFFSET="7"'
+ echo 'export CQL_LSP_FORMAT_SELECTORS="false"'
+ echo 'export CQL_LSP_CONTEXT_BASED_SELECT="false"'
+ echo 'export CQL_LSP_QUALIFIED_DROP="false"'
+ echo 'export CQL_LSP_COMMAND_SNIPPETS="false"'
+ echo 'export CQL_LSP_MAX_COMPLETION_ITEMS="5000"'
+ echo 'export CQL_LSP_QUERY_TIMEOUT_MS="2000"'
+ echo 'export CQL_LSP_DIALECT="cassandra"'
+ echo 'export CQL_LSP_KEYWORD_CASE="preserve"'
+ echo 'export CQL_LSP_FORMAT_ON_SAVE="false"'
+ echo 'export CQL_LSP_VALIDATE_ON_SAVE="false"'
+ echo 'export CQL_LSP_LIVE_VALIDATION="false"'
+ echo 'export CQL_LSP_DEBOUNCE_MS="300"'
+ echo 'export CQL_LSP_LISTEN=""'
+ echo 'export CQL_LSP_ENABLE_EXEC="false"'
+ echo 'export CQL_LSP_ALLOW_MUTATIONS="false"'
 } >> "$CONFIG_FILE"
 
 echo "Installation complete for user $TARGET_USER."
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+
+use crate::consts::*;
+use crate::lsp::Backend;
+
+/*
+    Quick fixes for DDL statements
+
+    CREATE TABLE ks.t ...  -> CREATE TABLE IF NOT EXISTS ks.t ...
+    DROP TABLE ks.t;       -> DROP TABLE IF EXISTS ks.t;
+*/
+impl Backend {
+    pub async fn get_code_actions(&self, uri: &Url, range: &Range) -> Vec<CodeActionOrCommand> {
+        let documents = self.documents.read().await;
+        let Some(document) = documents.get(uri) else {
+            return vec![];
+        };
+
+        let mut actions = Vec::<CodeActionOrCommand>::new();
+
+        for (index, line) in document.split('\n').enumerate() {
+            if index < range.start.line as usize || index > range.end.line as usize {
+                continue;
+            }
+
+            let Some((offset, clause)) = self.get_missing_exists_clause(line) else {
+                continue;
+            };
+
+            let position = Position {
+                line: index as u32,
+                character: line[..offset].encode_utf16().count() as u32,
+            };
+
+            let edit = TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text: format!(" {}", clause),
+            };
+
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Add {}", clause.to_uppercase()),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+
+        actions
+    }
+
+    /*
+        Byte offset right after the object type keyword
+        and the clause missing there
+
+        CREATE [MATERIALIZED VIEW | CUSTOM INDEX | TABLE | ...] -> IF NOT EXISTS
+        DROP [MATERIALIZED VIEW | TABLE | ...] -> IF EXISTS
+
+        Clause follows the case of the object keyword,
+        CREATE OR REPLACE can't be combined with IF NOT EXISTS
+    */
+    pub fn get_missing_exists_clause(&self, line: &str) -> Option<(usize, String)> {
+        let mut words = Vec::<(usize, &str)>::new();
+        let mut start = None;
+
+        for (i, c) in line.char_indices() {
+            match (c.is_whitespace() || c == '(' || c == ';', start) {
+                (true, Some(s)) => {
+                    words.push((s, &line[s..i]));
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            words.push((s, &line[s..]));
+        }
+
+        let lw: Vec<String> = words.iter().map(|(_, w)| w.to_lowercase()).collect();
+
+        if lw.len() < 2 || (lw[0] != "create" && lw[0] != "drop") {
+            return None;
+        }
+
+        let mut object_index = 1;
+        if lw[1] == "materialized" || lw[1] == "custom" {
+            object_index = 2;
+        }
+
+        if !DDL_OBJECTS_LWC.contains(lw.get(object_index)?) {
+            return None;
+        }
+
+        // Clause is already present or object name wasn't typed yet
+        if lw.get(object_index + 1).is_none_or(|w| w == "if") {
+            return None;
+        }
+
+        let (object_offset, object) = words[object_index];
+        let clause = match lw[0] == "create" {
+            true => "if not exists",
+            false => "if exists",
+        };
+
+        let clause = match object.chars().all(|c| c.is_uppercase()) {
+            true => clause.to_uppercase(),
+            false => clause.to_string(),
+        };
+
+        Some((object_offset + object.len(), clause))
+    }
+}
@@ -0,0 +1,337 @@
+use tower_lsp::jsonrpc;
+use tower_lsp::lsp_types::*;
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use crate::cqlsh::{self, CqlError, CqlSettings};
+use crate::lsp::Backend;
+use crate::setup::{load_config, load_settings};
+
+pub const RUN_STATEMENT_COMMAND: &str = "cql.runStatement";
+pub const DESCRIBE_TABLE_COMMAND: &str = "cql.describeTable";
+pub const RELOAD_CONFIG_COMMAND: &str = "cql.reloadConfig";
+
+/*
+    workspace/executeCommand handlers
+
+    cql.runStatement [uri, { line, character }]
+
+    Runs statement under the cursor against the configured cluster,
+    disabled unless CQL_LSP_ENABLE_EXEC is set. Only SELECT statements
+    are allowed unless CQL_LSP_ALLOW_MUTATIONS is also set
+
+    cql.describeTable ["keyspace.table"] | [uri, { line, character }]
+
+    Returns CREATE TABLE statement of the table, table name is resolved
+    from the cursor when uri is passed. Tables without keyspace use the
+    latest USE keyspace
+
+    cql.reloadConfig []
+
+    Re-reads env variables && config.lsp, cached schema is dropped
+    && connection is checked again. Registered commands aren't
+    updated, CQL_LSP_ENABLE_EXEC still requires a restart
+*/
+impl Backend {
+    pub async fn run_statement(&self, arguments: &[LSPAny]) -> jsonrpc::Result<Option<LSPAny>> {
+        if !self.config().enable_exec {
+            return Err(jsonrpc::Error::invalid_request());
+        }
+
+        let uri = arguments
+            .first()
+            .and_then(|uri| uri.as_str())
+            .and_then(|uri| Url::parse(uri).ok());
+        let position = arguments.get(1).and_then(|position| {
+            Some(Position {
+                line: position.get("line")?.as_u64()? as u32,
+                character: position.get("character")?.as_u64()? as u32,
+            })
+        });
+
+        let (Some(uri), Some(position)) = (uri, position) else {
+            return Err(jsonrpc::Error::invalid_params(
+                "Expected arguments: [uri, { line, character }]",
+            ));
+        };
+
+        let Some(text) = self.documents.read().await.get(&uri).cloned() else {
+            return Err(jsonrpc::Error::invalid_params(format!(
+                "Document {} isn't opened",
+                uri
+            )));
+        };
+
+        let offset = Self::position_to_offset(&text, &position);
+        let Some(statement) = Self::statement_at(&text, offset) else {
+            return Err(jsonrpc::Error::invalid_params(
+                "No statement found at the given position",
+            ));
+        };
+
+        let is_select = statement
+            .split_whitespace()
+            .next()
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("select"));
+
+        if !is_select && !self.config().allow_mutations {
+            let message = "Only SELECT statements can be executed, set CQL_LSP_ALLOW_MUTATIONS to run other statements";
+            self.client
+                .show_message(MessageType::WARNING, message)
+                .await;
+            return Err(jsonrpc::Error::invalid_params(message));
+        }
+
+        let result = cqlsh::execute_statement(&self.config(), &statement).await;
+
+        match result {
+            Ok(output) => {
+                self.client
+                    .show_message(MessageType::INFO, output.clone())
+                    .await;
+                Ok(Some(LSPAny::String(output)))
+            }
+            Err(e) => {
+                let message = format!("Statement failed: {}", e);
+                self.client
+                    .show_message(MessageType::ERROR, message.clone())
+                    .await;
+                Err(jsonrpc::Error::invalid_params(message))
+            }
+        }
+    }
+
+    pub async fn describe_table(&self, arguments: &[LSPAny]) -> jsonrpc::Result<Option<LSPAny>> {
+        let Some(first) = arguments.first().and_then(|argument| argument.as_str()) else {
+            return Err(jsonrpc::Error::invalid_params(
+                "Expected arguments: [\"keyspace.table\"] or [uri, { line, character }]",
+            ));
+        };
+
+        let position = arguments.get(1).and_then(|position| {
+            Some(Position {
+                line: position.get("line")?.as_u64()? as u32,
+                character: position.get("character")?.as_u64()? as u32,
+            })
+        });
+
+        let name = match (Url::parse(first), position) {
+            (Ok(uri), Some(position)) => {
+                let Some(text) = self.documents.read().await.get(&uri).cloned() else {
+                    return Err(jsonrpc::Error::invalid_params(format!(
+                        "Document {} isn't opened",
+                        uri
+                    )));
+                };
+
+                let offset = Self::position_to_offset(&text, &position);
+                let name = self.tree_for(&uri).await.and_then(|tree| {
+                    let node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+                    Self::referenced_name(node, &text)
+                });
+
+                match name {
+                    Some(name) => name,
+                    None => {
+                        return Err(jsonrpc::Error::invalid_params(
+                            "No table name found at the given position",
+                        ));
+                    }
+                }
+            }
+            _ => first.to_string(),
+        };
+
+        // Unquoted names are lowercased, quoted ones keep their case
+        let (keyspace, table) = match Self::parse_qualified_name(&name) {
+            (Some(keyspace), table) => (keyspace, table),
+            (None, table) => {
+                let Some(keyspace) = self.latest_keyspace(&position.unwrap_or_default()).await
+                else {
+                    return Err(jsonrpc::Error::invalid_params(format!(
+                        "Keyspace of table {} is unknown, use keyspace.table",
+                        table
+                    )));
+                };
+                (keyspace, table)
+            }
+        };
+
+        let result = cqlsh::describe_table(&self.config(), &keyspace, &table).await;
+
+        match result {
+            Ok(statement) => Ok(Some(LSPAny::String(statement))),
+            Err(e) => Err(jsonrpc::Error::invalid_params(format!(
+                "Failed to describe {}.{}: {}",
+                keyspace, table, e
+            ))),
+        }
+    }
+
+    pub async fn reload_config(&self) -> jsonrpc::Result<Option<LSPAny>> {
+        let url = self.replace_settings(load_settings(&load_config())).await;
+
+        self.client
+            .log_message(MessageType::INFO, format!("Config reloaded, url {}", url))
+            .await;
+
+        self.check_cluster_connection().await;
+
+        Ok(Some(LSPAny::String(url)))
+    }
+
+    /*
+        Swaps settings used by every later query, returns the new url.
+        Queries already running keep the settings they started with
+    */
+    async fn replace_settings(&self, settings: CqlSettings) -> String {
+        let url = settings.url();
+
+        *self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(settings);
+
+        // Schema of the previous cluster must not be served
+        *self.schema_snapshot.write().await = None;
+        *self.datacenters.write().await = None;
+        self.db_unreachable.store(false, Ordering::Relaxed);
+
+        url
+    }
+
+    /*
+        Reports whether the configured cluster is reachable,
+        schema snapshot is refreshed once it is
+    */
+    pub async fn check_cluster_connection(&self) {
+        let config = self.config();
+
+        if config.offline {
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    "Offline mode, schema-aware completions && validation are disabled",
+                )
+                .await;
+            return;
+        }
+
+        match cqlsh::check_connection(&config).await {
+            Ok(_) => {
+                self.client
+                    .show_message(MessageType::INFO, format!("Connected to {}", config.url()))
+                    .await;
+
+                if config.schema_snapshot {
+                    self.spawn_schema_snapshot_refresh().await;
+                }
+            }
+            Err(e) => {
+                let hint = match e {
+                    CqlError::Auth(_) => "check CQL_LSP_DB_USER && CQL_LSP_DB_PASSWD",
+                    _ => "check CQL_LSP_DB_URL",
+                };
+
+                // Already reported, completions won't repeat the warning
+                self.db_unreachable.store(true, Ordering::Relaxed);
+                self.client
+                    .show_message(
+                        MessageType::WARNING,
+                        format!(
+                            "Failed to connect to {}: {}. Schema-aware completions will be unavailable, {}",
+                            config.url(),
+                            e,
+                            hint
+                        ),
+                    )
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Offline backend fails the query, the error names the resolved table
+    async fn describe_error(name: &str) -> String {
+        let service = Backend::test_service();
+        let arguments = [LSPAny::String(name.to_string())];
+
+        match service.inner().describe_table(&arguments).await {
+            Ok(result) => panic!("expected an error, got {:?}", result),
+            Err(e) => e.message.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn described_table_names_follow_identifier_rules() {
+        for (name, resolved) in [
+            ("ks.users", "ks.users"),
+            ("Ks.Users", "ks.users"),
+            (" KS . users ", "ks.users"),
+            (r#""Ks"."Users""#, "Ks.Users"),
+            (r#"ks."My.Table""#, "ks.My.Table"),
+            (r#""ks"".x".t"#, r#"ks".x.t"#),
+        ] {
+            let message = describe_error(name).await;
+            assert!(
+                message.starts_with(&format!("Failed to describe {}: ", resolved)),
+                "{} -> {}",
+                name,
+                message
+            );
+        }
+
+        assert_eq!(
+            describe_error("Users").await,
+            "Keyspace of table users is unknown, use keyspace.table"
+        );
+    }
+
+    // Nothing listens on these ports, the refused connection names the url
+    fn unreachable(port: u16) -> CqlSettings {
+        let mut settings = CqlSettings::new();
+        settings.urls = vec![format!("127.0.0.1:{}", port)];
+        settings.db_retries = 0;
+        settings.connection_timeout_secs = 1;
+        settings
+    }
+
+    #[tokio::test]
+    async fn replaced_settings_are_used_by_later_queries() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        let arguments = [LSPAny::String("ks.users".to_string())];
+
+        let describe = || async {
+            match backend.describe_table(&arguments).await {
+                Ok(result) => panic!("expected an error, got {:?}", result),
+                Err(e) => e.message.to_string(),
+            }
+        };
+
+        assert_eq!(
+            backend.replace_settings(unreachable(1)).await,
+            "127.0.0.1:1"
+        );
+        let previous = backend.config();
+        let message = describe().await;
+        assert!(message.contains("127.0.0.1:1"), "{}", message);
+
+        *backend.schema_snapshot.write().await = Some(Default::default());
+        assert_eq!(
+            backend.replace_settings(unreachable(2)).await,
+            "127.0.0.1:2"
+        );
+        assert!(backend.schema_snapshot.read().await.is_none());
+        assert_eq!(previous.url(), "127.0.0.1:1");
+
+        let message = describe().await;
+        assert!(message.contains("127.0.0.1:2"), "{}", message);
+        assert!(!message.contains("127.0.0.1:1"), "{}", message);
+    }
+}
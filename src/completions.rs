@@ -1,48 +1,61 @@
 use log::{info, warn};
+use std::collections::HashMap;
 
 use crate::consts::*;
-use crate::cqlsh::{self, Column};
+use crate::cqlsh::{self, Column, TableOptions};
+use crate::lexer;
 use crate::lsp::Backend;
+use crate::tree_sitter::parse_cql;
 use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
 
 impl Backend {
     pub fn is_use_keyspace_line(&self, s: &str) -> bool {
-        // use "x";
-        if s.len() < 8 {
-            return false;
-        }
+        self.get_use_keyspace(s).is_some()
+    }
 
-        let input_str: Vec<char> = s.trim().chars().collect();
+    /*
+        use "x";      -> x
+        USE   'x' ;   -> x
+        USE x;        -> x
 
-        let use_statement = String::from_iter(&input_str[0..=2]);
+        Name is taken between the opening && closing quote,
+        unquoted names are case insensitive, so they are lowercased
+    */
+    pub fn get_use_keyspace(&self, s: &str) -> Option<String> {
+        let trimmed = s.trim();
 
-        if use_statement.to_lowercase() != "use" {
-            return false;
+        let keyword = trimmed.get(..3)?;
+        if !keyword.eq_ignore_ascii_case("use") {
+            return None;
         }
 
-        if (input_str[3] != '\"'
-            && input_str[input_str.len() - 2] != '\"'
-            && input_str[input_str.len() - 1] != ';')
-            || (input_str[3] != '\"'
-                && input_str[input_str.len() - 2] != '\"'
-                && input_str[input_str.len() - 1] != ';')
-        {
-            return false;
+        let rest = &trimmed[3..];
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
         }
 
-        true
+        let name = rest.trim().strip_suffix(';')?.trim_end();
+
+        let keyspace = match name.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let quoted = name[1..].strip_suffix(quote)?;
+                quoted.replace(&format!("{}{}", quote, quote), &quote.to_string())
+            }
+            _ => {
+                if name.contains(char::is_whitespace) || name.contains(['"', '\'']) {
+                    return None;
+                }
+                name.to_lowercase()
+            }
+        };
+
+        (!keyspace.is_empty()).then_some(keyspace)
     }
 
     // Works
     pub async fn get_keyspaces(&self) -> Vec<String> {
-        let items = cqlsh::query_keyspaces(&self.config).await;
-
-        match items {
-            Ok(r) => r.into_iter().collect(),
-            Err(_) => {
-                vec![]
-            }
-        }
+        self.schema_keyspaces().await
     }
 
     // Works
@@ -129,6 +142,28 @@ impl Backend {
         false
     }
 
+    /*
+        ALTER KEYSPACE |
+        ALTER KEYSPACE IF EXISTS my|
+
+        Unlike USE the name isn't wrapped in quotes
+    */
+    pub fn should_suggest_alter_keyspaces(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let tokens = lexer::code_tokens(prefix);
+        let words = lexer::words(lexer::completed_tokens(&tokens, prefix));
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+        matches!(
+            words.as_slice(),
+            ["alter", "keyspace"] | ["alter", "keyspace", "if", "exists"]
+        )
+    }
+
     pub fn should_suggest_drop_aggregate(&self, line: &str, position: &Position) -> bool {
         let lw = line.to_lowercase();
 
@@ -318,6 +353,77 @@ impl Backend {
         vec!["Core".to_string(), "Classic".to_string()]
     }
 
+    /*
+        Datacenter names are cached after the first successful query,
+        topology of the cluster rarely changes during a session
+
+        Failed queries aren't cached, next completion retries
+    */
+    pub async fn get_datacenters(&self) -> Vec<String> {
+        if let Some(datacenters) = self.datacenters.read().await.as_ref() {
+            return datacenters.clone();
+        }
+
+        let datacenters = self
+            .unwrap_query(cqlsh::query_datacenters(&self.config()))
+            .await;
+
+        if !datacenters.is_empty() {
+            *self.datacenters.write().await = Some(datacenters.clone());
+        }
+
+        datacenters
+    }
+
+    /*
+        CREATE KEYSPACE x WITH replication = { |
+        ALTER KEYSPACE x WITH replication = {'class': |
+        CREATE KEYSPACE x WITH replication = {'class': 'NetworkTopologyStrategy', '|
+    */
+    pub fn should_suggest_replication_options(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let lw = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw.split_whitespace().collect();
+
+        if splitted.len() < 2
+            || (splitted[0] != "create" && splitted[0] != "alter")
+            || splitted[1] != "keyspace"
+        {
+            return false;
+        }
+
+        let Some(replication_pos) = lw.find("replication") else {
+            return false;
+        };
+
+        let map = &lw[replication_pos..];
+        if map.matches('{').count() <= map.matches('}').count() {
+            return false;
+        }
+
+        // Opening quote of the key may be typed already
+        let trimmed = map.trim_end();
+        let key = trimmed.strip_suffix('\'').unwrap_or(trimmed).trim_end();
+
+        key.ends_with('{') || key.ends_with(',') || self.is_replication_class_value(prefix)
+    }
+
+    /*
+        Cursor is placed after 'class':
+    */
+    pub fn is_replication_class_value(&self, prefix: &str) -> bool {
+        prefix
+            .to_lowercase()
+            .trim_end()
+            .trim_end_matches('\'')
+            .trim_end()
+            .ends_with("'class':")
+    }
+
     // Works
     pub fn should_suggest_graph_engine_types(&self, line: &str, position: &Position) -> bool {
         let prefix = match line.get(..position.character as usize) {
@@ -424,7 +530,7 @@ impl Backend {
 
             # TRUNCATE
 
-            TRUNCATE TBALE $0<TK_NAME> ;
+            TRUNCATE [TABLE] $0<TK_NAME> ;
 
             -------------[#UPDATE SKIPPED]-------------
 
@@ -434,40 +540,174 @@ impl Backend {
             USE '$0<TK_NAME>';
         */
 
-        let items = vec![
-            CompletionItem {
-                label: "ALTER".to_string(),
-                kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("ALTER KEYSPACE cql command".to_string()),
-                documentation: Some(Documentation::String(
-                    "ALTER KEYSPACE cql command".to_string(),
-                )),
-                insert_text: Some(r#"ALTER KEYSPACE $0";"#.to_string()),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            },
-            CompletionItem {
-                label: "ALTER".to_string(),
-                kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("ALTER MATERIALIZED VIEW cql command".to_string()),
-                documentation: Some(Documentation::String(
-                    "ALTER MATERIALIZED VIEW cql command".to_string(),
-                )),
-                insert_text: Some(r#"ALTER MATERIALIZED VIEW $0";"#.to_string()),
+        let templates = [
+            (
+                "ALTER KEYSPACE",
+                "ALTER KEYSPACE ${1:keyspace_name} WITH ${2};$0",
+            ),
+            (
+                "ALTER MATERIALIZED VIEW",
+                "ALTER MATERIALIZED VIEW ${1:view_name} WITH ${2};$0",
+            ),
+            ("ALTER ROLE", "ALTER ROLE ${1:role_name} WITH ${2};$0"),
+            ("ALTER TABLE", "ALTER TABLE ${1:table_name} ${2};$0"),
+            ("ALTER TYPE", "ALTER TYPE ${1:type_name} ${2};$0"),
+            (
+                "ALTER USER",
+                "ALTER USER ${1:user_name} WITH PASSWORD '${2}';$0",
+            ),
+            (
+                "COMMIT SEARCH INDEX",
+                "COMMIT SEARCH INDEX ON ${1:table_name};$0",
+            ),
+            (
+                "CREATE AGGREGATE",
+                "CREATE AGGREGATE IF NOT EXISTS ${1:aggregate_name} (${2:arg_type})\nSFUNC ${3:state_function}\nSTYPE ${4:state_type}\nINITCOND ${5:initial_value};$0",
+            ),
+            (
+                "CREATE FUNCTION",
+                "CREATE FUNCTION IF NOT EXISTS ${1:function_name} (${2:args})\nRETURNS NULL ON NULL INPUT\nRETURNS ${3:return_type}\nLANGUAGE ${4:java}\nAS '${5}';$0",
+            ),
+            (
+                "CREATE INDEX",
+                "CREATE INDEX IF NOT EXISTS ${1:index_name} ON ${2:table_name} (${3:column_name});$0",
+            ),
+            (
+                "CREATE KEYSPACE",
+                "CREATE KEYSPACE IF NOT EXISTS ${1:keyspace_name} WITH replication = {'class': '${2:SimpleStrategy}', 'replication_factor': ${3:1}};$0",
+            ),
+            (
+                "CREATE MATERIALIZED VIEW",
+                "CREATE MATERIALIZED VIEW IF NOT EXISTS ${1:view_name} AS\nSELECT ${2:*} FROM ${3:table_name}\nWHERE ${4:column_name} IS NOT NULL\nPRIMARY KEY (${5});$0",
+            ),
+            (
+                "CREATE ROLE",
+                "CREATE ROLE IF NOT EXISTS ${1:role_name} WITH PASSWORD = '${2}' AND LOGIN = ${3:true};$0",
+            ),
+            (
+                "CREATE SEARCH INDEX",
+                "CREATE SEARCH INDEX IF NOT EXISTS ON ${1:table_name};$0",
+            ),
+            (
+                "CREATE TABLE",
+                "CREATE TABLE IF NOT EXISTS ${1:table_name} (\n    ${2:id} ${3:uuid} PRIMARY KEY,\n    ${4}\n);$0",
+            ),
+            (
+                "CREATE TYPE",
+                "CREATE TYPE IF NOT EXISTS ${1:type_name} (\n    ${2}\n);$0",
+            ),
+            (
+                "CREATE USER",
+                "CREATE USER IF NOT EXISTS ${1:user_name} WITH PASSWORD '${2}';$0",
+            ),
+            (
+                "DROP AGGREGATE",
+                "DROP AGGREGATE IF EXISTS ${1:aggregate_name};$0",
+            ),
+            (
+                "DROP FUNCTION",
+                "DROP FUNCTION IF EXISTS ${1:function_name};$0",
+            ),
+            ("DROP INDEX", "DROP INDEX IF EXISTS ${1:index_name};$0"),
+            (
+                "DROP KEYSPACE",
+                "DROP KEYSPACE IF EXISTS ${1:keyspace_name};$0",
+            ),
+            (
+                "DROP MATERIALIZED VIEW",
+                "DROP MATERIALIZED VIEW IF EXISTS ${1:view_name};$0",
+            ),
+            ("DROP ROLE", "DROP ROLE IF EXISTS ${1:role_name};$0"),
+            (
+                "DROP SEARCH INDEX",
+                "DROP SEARCH INDEX ON ${1:table_name};$0",
+            ),
+            ("DROP TABLE", "DROP TABLE IF EXISTS ${1:table_name};$0"),
+            ("DROP TYPE", "DROP TYPE IF EXISTS ${1:type_name};$0"),
+            ("DROP USER", "DROP USER IF EXISTS ${1:user_name};$0"),
+            (
+                "LIST ALL PERMISSIONS",
+                "LIST ALL PERMISSIONS OF ${1:role_name};$0",
+            ),
+            ("LIST ROLES", "LIST ROLES${1};$0"),
+            ("LIST USERS", "LIST USERS;$0"),
+            (
+                "REVOKE PERMISSION",
+                "REVOKE ${1:permission} ON ${2:resource} FROM ${3:role_name};$0",
+            ),
+            (
+                "REVOKE ROLE",
+                "REVOKE ${1:role_name} FROM ${2:role_name};$0",
+            ),
+            ("SELECT", "SELECT ${1:*} FROM ${2:table_name}${3};$0"),
+            ("TRUNCATE TABLE", "TRUNCATE TABLE ${1:table_name};$0"),
+            ("USE", "USE ${1:keyspace_name};$0"),
+        ];
+
+        let mut items: Vec<CompletionItem> = templates
+            .iter()
+            .map(|(label, snippet)| CompletionItem {
+                label: label.to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(format!("{} cql command", label)),
+                insert_text: Some(snippet.to_string()),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
-            },
-        ];
+            })
+            .collect();
+
+        // Plain keywords stay available next to the templates
+        items.extend(KEYWORDS.iter().cloned());
 
         Ok(Some(CompletionResponse::Array(items)))
     }
 
-    pub fn should_suggest_command_sequence(&self, line: &str, position: &Position) -> bool {
-        false
+    /*
+        [CQL_LSP_COMMAND_SNIPPETS=true]
+
+        Empty line where a statement could begin,
+        previous statement has to be terminated
+
+        SELECT * FROM ks.t;
+        |                      -> statement templates
+    */
+    pub fn should_suggest_command_sequence(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+    ) -> bool {
+        if !self.config().command_snippets || !line.trim().is_empty() {
+            return false;
+        }
+
+        let offset = Self::position_to_offset(text, position);
+        let Some(before) = text.get(..offset) else {
+            return false;
+        };
+
+        let Some(&(start, end)) = Self::statement_ranges(before).last() else {
+            return true;
+        };
+
+        let statement = before[start..end].to_lowercase();
+        let is_open_batch = statement.starts_with("begin")
+            && !statement
+                .trim_end_matches(';')
+                .trim_end()
+                .ends_with("apply batch");
+
+        statement.ends_with(';') && !is_open_batch
     }
 
     // Works
-    pub async fn should_suggest_keywords(&self, line: &str, position: &Position) -> bool {
+    pub async fn should_suggest_keywords(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+        document_url: &Url,
+    ) -> bool {
         let prefix = match line.get(..position.character as usize) {
             Some(p) => p,
             None => return false,
@@ -501,16 +741,17 @@ impl Backend {
             }
         }
 
-        let trimmed_prefix = prefix.trim_end().to_lowercase();
-        let split: Vec<&str> = trimmed_prefix.split(' ').collect();
+        let tokens = lexer::code_tokens(prefix);
+        let words = lexer::words(&tokens);
+
+        // One of the last two words e.g CREATE TABLE | && CREATE TABLE na|
+        let near_end = |word: &str| words.iter().rev().take(2).any(|w| w == word);
 
-        if split.len() > 0 && split[split.len() - 1].contains(";") {
+        if tokens.last().is_some_and(|t| t.is_punct(";")) {
             return false;
         }
 
-        if split.len() >= 2
-            && (split[split.len() - 1].contains("from") || split[split.len() - 2].contains("from"))
-        {
+        if near_end("from") {
             return false;
         }
 
@@ -537,50 +778,52 @@ impl Backend {
                 || lw.contains("user")
                 || lw.contains("function")
                 || lw.contains("aggregate"))
-            && split.len() >= 3
+            && words.len() >= 3
         {
             return false;
         }
 
-        let current = self.current_document.read().await;
-
-        if let Some(ref document_lock) = *current {
-            let document = document_lock.read().await;
-            let splitx: Vec<&str> = document.text.split('\n').collect();
-
-            if self.is_line_in_multiline_comment_ref(line, position.line as usize, &splitx) {
-                return false;
-            }
+        let splitx: Vec<&str> = text.split('\n').collect();
 
-            let mut index_up = position.line as usize;
+        // Document of the request, not the last opened one
+        let in_comment = match self
+            .is_position_in_comment(document_url, text, position)
+            .await
+        {
+            Some(in_comment) => in_comment,
+            None => self.is_line_in_multiline_comment_ref(line, position.line as usize, &splitx),
+        };
 
-            while index_up > 0 && index_up < splitx.len() {
-                if (!splitx[index_up].contains("(")
-                    && KEYWORDS_STRINGS_LWC.contains(&splitx[index_up].to_string()))
-                    || splitx[index_up].contains(";")
-                {
-                    break;
-                }
+        if in_comment {
+            return false;
+        }
 
-                if splitx[index_up].contains("(") {
-                    return false;
-                }
+        let mut index_up = position.line as usize;
 
-                index_up -= 1;
+        while index_up > 0 && index_up < splitx.len() {
+            if (!splitx[index_up].contains("(")
+                && KEYWORDS_STRINGS_LWC.contains(&splitx[index_up].to_string()))
+                || splitx[index_up].contains(";")
+            {
+                break;
             }
 
-            if index_up < splitx.len() && splitx[index_up].contains("(") {
+            if splitx[index_up].contains("(") {
                 return false;
             }
+
+            index_up -= 1;
+        }
+
+        if index_up < splitx.len() && splitx[index_up].contains("(") {
+            return false;
         }
 
         if lw.contains("create") && lw.contains("if not exists") {
             let mut index = lw.rfind(&"exists").unwrap();
             index += 6;
 
-            if position.character > index as u32
-                && (split[split.len() - 1] == "exists" || split[split.len() - 2] == "exists")
-            {
+            if position.character > index as u32 && near_end("exists") {
                 return false;
             }
         }
@@ -589,9 +832,7 @@ impl Backend {
             let mut index = lw.rfind(&"table").unwrap();
             index += 5;
 
-            if position.character > index as u32
-                && (split[split.len() - 1] == "table" || split[split.len() - 2] == "table")
-            {
+            if position.character > index as u32 && near_end("table") {
                 return false;
             }
         }
@@ -600,9 +841,7 @@ impl Backend {
             let mut index = lw.rfind(&"aggregate").unwrap();
             index += 9;
 
-            if position.character > index as u32
-                && (split[split.len() - 1] == "aggregate" || split[split.len() - 2] == "aggregate")
-            {
+            if position.character > index as u32 && near_end("aggregate") {
                 return false;
             }
         }
@@ -611,9 +850,7 @@ impl Backend {
             let mut index = lw.rfind(&"function").unwrap();
             index += 8;
 
-            if position.character > index as u32
-                && (split[split.len() - 1] == "function" || split[split.len() - 2] == "function")
-            {
+            if position.character > index as u32 && near_end("function") {
                 return false;
             }
         }
@@ -622,9 +859,7 @@ impl Backend {
             let mut index = lw.rfind(&"index").unwrap();
             index += 5;
 
-            if position.character > index as u32
-                && (split[split.len() - 1] == "index" || split[split.len() - 2] == "index")
-            {
+            if position.character > index as u32 && near_end("index") {
                 return false;
             }
         }
@@ -633,9 +868,7 @@ impl Backend {
             let mut keyspace = lw.rfind(&"keyspace").unwrap();
             keyspace += 8;
 
-            if position.character > keyspace as u32
-                && (split[split.len() - 1] == "keyspace" || split[split.len() - 2] == "keyspace")
-            {
+            if position.character > keyspace as u32 && near_end("keyspace") {
                 return false;
             }
         }
@@ -644,9 +877,7 @@ impl Backend {
             let mut keyspace = lw.rfind(&"view").unwrap();
             keyspace += 4;
 
-            if position.character > keyspace as u32
-                && (split[split.len() - 1] == "view" || split[split.len() - 2] == "view")
-            {
+            if position.character > keyspace as u32 && near_end("view") {
                 return false;
             }
         }
@@ -655,9 +886,7 @@ impl Backend {
             let mut keyspace = lw.rfind(&"role").unwrap();
             keyspace += 4;
 
-            if position.character > keyspace as u32
-                && (split[split.len() - 1] == "role" || split[split.len() - 2] == "role")
-            {
+            if position.character > keyspace as u32 && near_end("role") {
                 return false;
             }
         }
@@ -666,9 +895,7 @@ impl Backend {
             let mut keyspace = lw.rfind(&"type").unwrap();
             keyspace += 4;
 
-            if position.character > keyspace as u32
-                && (split[split.len() - 1] == "type" || split[split.len() - 2] == "type")
-            {
+            if position.character > keyspace as u32 && near_end("type") {
                 return false;
             }
         }
@@ -677,9 +904,7 @@ impl Backend {
             let mut keyspace = lw.rfind(&"user").unwrap();
             keyspace += 4;
 
-            if position.character > keyspace as u32
-                && (split[split.len() - 1] == "user" || split[split.len() - 2] == "user")
-            {
+            if position.character > keyspace as u32 && near_end("user") {
                 return false;
             }
         }
@@ -695,9 +920,7 @@ impl Backend {
 
             etc.
         */
-        if split.len() >= 2
-            && (split[split.len() - 1].contains("and") || split[split.len() - 2].contains("and"))
-        {
+        if near_end("and") {
             return false;
         }
 
@@ -712,10 +935,7 @@ impl Backend {
 
             etc.
         */
-        if split.len() >= 2
-            && (split[split.len() - 1].contains("where")
-                || split[split.len() - 2].contains("where"))
-        {
+        if near_end("where") {
             return false;
         }
 
@@ -744,11 +964,8 @@ impl Backend {
                 }
                 pos += 1;
 
-                if self.is_use_keyspace_line(str) {
-                    let istr: Vec<char> = str.trim().chars().collect();
-
-                    let extracted_ksp = String::from_iter(&istr[5..istr.len() - 2]);
-                    keyspace_latest = extracted_ksp.clone();
+                if let Some(keyspace) = self.get_use_keyspace(str) {
+                    keyspace_latest = keyspace;
                 }
             }
 
@@ -783,45 +1000,279 @@ impl Backend {
         true
     }
 
+    /*
+        [context_based_select=false] -> column name only
+        [context_based_select=true]  -> column name + FROM keyspace.table;
+    */
+    pub fn should_insert_select_statement(&self, line: &str) -> bool {
+        self.config().context_based_select && self.should_field_be_edit(line)
+    }
+
+    /*
+        Index of the first character of the word before the cursor
+
+        |name           -> 0
+        SELECT na|      -> 7
+        SELECT a,na|    -> 9
+        SELECT |        -> 7
+    */
     pub fn get_start_offset(&self, line: &str, position: &Position) -> u32 {
-        let mut index = position.character as usize;
+        // Cursor column && the result are in UTF-16 code units
+        let offset = Self::position_to_offset(line, &Position::new(0, position.character));
+        let prefix = &line[..offset];
+
+        let word_len: usize = prefix
+            .chars()
+            .rev()
+            .take_while(|c| !c.is_whitespace() && *c != ',')
+            .map(char::len_utf16)
+            .sum();
+
+        (prefix.encode_utf16().count() - word_len) as u32
+    }
 
-        while index > 0 {
-            if let Some(char) = line.chars().nth(index) {
-                if char == ' ' {
-                    return index as u32;
-                }
-            }
+    pub fn column_to_text_edit(&self, column: &Column, lates_keyspace: Option<&str>) -> String {
+        let column_name = Self::quote_identifier(&column.column_name);
+
+        if lates_keyspace == Some(column.keyspace_name.as_str()) {
+            return format!(
+                "{}, FROM {};",
+                column_name,
+                Self::quote_identifier(&column.table_name)
+            );
+        }
+
+        format!(
+            "{}, FROM {};",
+            column_name,
+            Self::quote_qualified_name(&column.keyspace_name, &column.table_name)
+        )
+    }
 
-            index -= 1;
+    /*
+        Human readable system_schema.columns kind
+    */
+    pub fn column_kind_detail(&self, column: &Column) -> String {
+        match column.kind.as_str() {
+            "partition_key" => "partition key".to_string(),
+            "clustering" => "clustering".to_string(),
+            "static" => "static".to_string(),
+            _ => "regular".to_string(),
         }
+    }
+
+    /*
+        completionItem/resolve payload, documentation is built
+        only once the item is highlighted
 
-        0
+        {keyspace, table}                   -> CREATE TABLE of the table
+        {keyspace, table, column, ...}      -> type && kind of the column
+    */
+    pub fn resolve_data(keyspace: &str, table: &str, column: Option<&Column>) -> serde_json::Value {
+        match column {
+            Some(column) => serde_json::json!({
+                "keyspace": keyspace,
+                "table": table,
+                "column": column.column_name,
+                "type": column.column_type,
+                "kind": column.kind,
+            }),
+            None => serde_json::json!({ "keyspace": keyspace, "table": table }),
+        }
     }
 
-    pub fn column_to_text_edit(&self, column: &Column, lates_keyspace: Option<&str>) -> String {
-        let mut result_str: String;
+    /*
+        Keys first, then regular columns, functions last
 
-        if let Some(keyspace) = lates_keyspace {
-            if keyspace == column.keyspace_name {
-                result_str = format!("{}, FROM {};", column.column_name, column.table_name);
-            } else {
-                result_str = format!(
-                    "{}, FROM {}.{};",
-                    column.column_name, column.keyspace_name, column.table_name
-                );
+        0_id  1_created_at  2_name  3_count(*)
+    */
+    pub fn field_sort_text(column: &Column) -> String {
+        let sort_prefix = match column.kind.as_str() {
+            "partition_key" => 0,
+            "clustering" => 1,
+            _ => 2,
+        };
+
+        format!("{}_{}", sort_prefix, column.column_name.to_lowercase())
+    }
+
+    pub fn sorted_native_functions() -> Vec<CompletionItem> {
+        CQL_NATIVE_FUNCTIONS
+            .iter()
+            .map(|item| CompletionItem {
+                sort_text: Some(format!("3_{}", Self::case_sort_text(&item.label))),
+                ..item.clone()
+            })
+            .collect()
+    }
+
+    /*
+        Aggregates && functions applied to the column
+
+        SELECT sum(price), writetime(name) FROM ...
+
+        sum && avg are offered for numeric columns only,
+        writetime && ttl aren't allowed on primary key columns
+    */
+    pub fn get_wrapped_fields(&self, columns: &[Column], line: &str) -> Vec<CompletionItem> {
+        let lw_line = line.to_lowercase();
+
+        let mut items = vec![CompletionItem {
+            label: "count(*)".to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: Some("Number of rows".to_string()),
+            sort_text: Some("3_count(*)".to_string()),
+            insert_text: Some("count(*)".to_string()),
+            ..Default::default()
+        }];
+
+        for column in columns {
+            if lw_line.contains(&column.column_name.to_lowercase()) {
+                continue;
+            }
+
+            let mut functions = vec!["min", "max"];
+
+            if NUMERIC_TYPES_LWC.contains(&column.column_type.to_lowercase()) {
+                functions.extend(["sum", "avg"]);
+            }
+
+            if column.kind == "regular" || column.kind == "static" {
+                functions.extend(["writetime", "ttl"]);
+            }
+
+            for function in functions {
+                items.push(CompletionItem {
+                    label: format!(
+                        "{}({}) | {}.{}",
+                        function, column.column_name, column.keyspace_name, column.table_name
+                    ),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(column.column_type.clone()),
+                    sort_text: Some(format!(
+                        "3_{}({})",
+                        function,
+                        column.column_name.to_lowercase()
+                    )),
+                    insert_text: Some(format!(
+                        "{}({})",
+                        function,
+                        Self::quote_identifier(&column.column_name)
+                    )),
+                    ..Default::default()
+                });
             }
-            return result_str;
         }
-        result_str = format!(
-            "{}, FROM {}.{};",
-            column.column_name, column.keyspace_name, column.table_name
-        );
-        result_str
+
+        items
+    }
+
+    /*
+        SELECT field completion of the column
+
+        With a text edit the typed word && the rest of the line are replaced,
+        see select_from_edit. Quoted column name is inserted otherwise
+    */
+    fn column_item(&self, column: &Column, text_edit: Option<TextEdit>) -> CompletionItem {
+        let kind = match text_edit {
+            Some(_) => CompletionItemKind::SNIPPET,
+            None => CompletionItemKind::FIELD,
+        };
+
+        CompletionItem {
+            label: format!(
+                "{} | {}.{}",
+                column.column_name, column.keyspace_name, column.table_name,
+            ),
+            detail: Some(self.column_kind_detail(column)),
+            data: Some(Self::resolve_data(
+                &column.keyspace_name,
+                &column.table_name,
+                Some(column),
+            )),
+            sort_text: Some(Self::field_sort_text(column)),
+            kind: Some(kind),
+            insert_text: match text_edit {
+                Some(_) => None,
+                None => Some(Self::quote_identifier(&column.column_name)),
+            },
+            text_edit: text_edit.map(CompletionTextEdit::Edit),
+            ..Default::default()
+        }
+    }
+
+    /*
+        SELECT na|me ;  -> SELECT name, FROM users;
+
+        FROM clause replaces the rest of the line,
+        both ends of the range are in UTF-16 code units
+    */
+    fn select_from_edit(
+        &self,
+        column: &Column,
+        latest_keyspace: Option<&str>,
+        line: &str,
+        position: &Position,
+    ) -> TextEdit {
+        TextEdit {
+            range: Range::new(
+                Position::new(position.line, self.get_start_offset(line, position)),
+                Position::new(position.line, line.encode_utf16().count() as u32),
+            ),
+            new_text: self.column_to_text_edit(column, latest_keyspace),
+        }
+    }
+
+    /*
+        Column items of the columns not yet typed in the line,
+        followed by wrapped columns && native functions
+
+        FROM clause is inserted along with the column when
+        context based select is enabled && from_keyspace is Some.
+        Tables outside of the inner keyspace are qualified, Some(None)
+        qualifies every table
+    */
+    fn field_items(
+        &self,
+        columns: &[Column],
+        from_keyspace: Option<Option<&str>>,
+        line: &str,
+        position: &Position,
+    ) -> Vec<CompletionItem> {
+        let lw_line = line.to_lowercase();
+        let mut wrapped = self.get_wrapped_fields(columns, line);
+
+        let from_keyspace = from_keyspace.filter(|_| self.should_insert_select_statement(line));
+
+        let mut result: Vec<CompletionItem> = columns
+            .iter()
+            .filter(|column| !lw_line.contains(&column.column_name.to_lowercase()))
+            .map(|column| {
+                let text_edit = from_keyspace
+                    .map(|keyspace| self.select_from_edit(column, keyspace, line, position));
+                self.column_item(column, text_edit)
+            })
+            .collect();
+
+        result.append(&mut wrapped);
+        result.extend(Self::sorted_native_functions());
+        result
     }
 
+    /*
+        Text Edit
+
+        line.len() == position.character;
+        SELECT id FROM ;
+        SELECT name ;
+
+        Insert Text
+
+        ... FROM keyspace_name.table_name;
+    */
     pub async fn get_fields(
         &self,
+        text: &str,
         line: &str,
         position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
@@ -829,594 +1280,2984 @@ impl Backend {
 
         let lw_line = line.to_lowercase();
 
+        // FROM on one of the next lines, nothing is inserted after the column
+        if !lw_line.contains("from")
+            && let Some((keyspace, table)) = self.get_select_from_target(text, position)
+        {
+            let keyspace = match keyspace {
+                Some(keyspace) => Some(keyspace),
+                None => self.latest_keyspace(position).await,
+            };
+
+            let Some(keyspace) = keyspace else {
+                return Ok(Some(CompletionResponse::Array(vec![])));
+            };
+
+            let items = self.schema_hard_scoped_fields(&keyspace, &table).await;
+            return Ok(Some(CompletionResponse::Array(
+                self.field_items(&items, None, line, position),
+            )));
+        }
+
         if lw_line.contains("from") {
-            let trimmed = lw_line.trim_end();
+            // Original line, quoted names are case sensitive
+            let trimmed = line.trim_end();
             let split: Vec<&str> = trimmed.split(' ').collect();
-            if !split[split.len() - 1].contains("from") && split[split.len() - 1].len() > 1 {
-                let ksp_tbl = split[split.len() - 1].replace(";", "");
+            let last = split[split.len() - 1];
+            if !last.to_lowercase().contains("from") && last.len() > 1 {
+                let ksp_tbl = last.replace(";", "");
 
                 if ksp_tbl.contains(".") {
-                    let keyspace_table: Vec<&str> = ksp_tbl.split('.').collect();
-                    if keyspace_table.len() == 2 {
-                        let ksp = keyspace_table[0];
-                        let tbl = keyspace_table[1];
-
-                        let mut items: Vec<Column> = Vec::new();
-
-                        let result =
-                            cqlsh::query_hard_scoped_fields(&self.config, &ksp, &tbl).await;
-                        match result {
-                            Ok(mut r) => {
-                                items.append(&mut r);
-                            }
-                            Err(_) => {}
-                        }
-
-                        let mut result: Vec<CompletionItem> = Vec::new();
-
-                        if self.should_field_be_edit(line) {
-                            for item in items {
-                                if lw_line.contains(&item.column_name.to_lowercase()) {
-                                    continue;
-                                }
-
-                                let text_edit_str = self.column_to_text_edit(&item, Some(&ksp));
-
-                                let text_edit = TextEdit {
-                                    range: Range {
-                                        start: Position {
-                                            line: position.line,
-                                            character: self.get_start_offset(line, position) + 1,
-                                        },
-                                        end: Position {
-                                            line: position.line,
-                                            // Insane wierd shit :D
-                                            character: line.len() as u32,
-                                        },
-                                    },
-                                    new_text: text_edit_str,
-                                };
-
-                                result.push(CompletionItem {
-                                    label: format!(
-                                        "{} | {}.{}",
-                                        item.column_name, item.keyspace_name, item.table_name,
-                                    ),
-                                    kind: Some(CompletionItemKind::SNIPPET),
-                                    text_edit: Some(CompletionTextEdit::Edit(text_edit)),
-                                    ..Default::default()
-                                });
-                            }
-                        } else {
-                            for item in items {
-                                if lw_line.contains(&item.column_name.to_lowercase()) {
-                                    continue;
-                                }
-
-                                result.push(CompletionItem {
-                                    label: format!(
-                                        "{} | {}.{}",
-                                        item.column_name, item.keyspace_name, item.table_name,
-                                    ),
-                                    kind: Some(CompletionItemKind::FIELD),
-                                    insert_text: Some(format!("{}", item.column_name)),
-                                    ..Default::default()
-                                });
-                            }
-                        }
-
-                        let mut x: Vec<CompletionItem> =
-                            CQL_NATIVE_FUNCTIONS.iter().cloned().collect();
-
-                        result.append(&mut x);
-
-                        return Ok(Some(CompletionResponse::Array(result)));
+                    let (keyspace, tbl) = Self::parse_qualified_name(&ksp_tbl);
+                    if let Some(ksp) = keyspace {
+                        let items = self.schema_hard_scoped_fields(&ksp, &tbl).await;
+                        return Ok(Some(CompletionResponse::Array(self.field_items(
+                            &items,
+                            Some(Some(&ksp)),
+                            line,
+                            position,
+                        ))));
                     }
                 } else {
-                    tbl_name = ksp_tbl;
+                    tbl_name = Self::parse_identifier(&ksp_tbl);
                 }
             }
         }
 
         if let Some(keyspace) = self.latest_keyspace(position).await {
-            let mut items: Vec<Column> = Vec::new();
-
-            if tbl_name != "" {
-                let result =
-                    cqlsh::query_hard_scoped_fields(&self.config, &keyspace, &tbl_name).await;
-                match result {
-                    Ok(mut r) => {
-                        items.append(&mut r);
-                    }
-                    Err(_) => {}
-                }
-            } else {
-                items = cqlsh::query_keyspace_scoped_fields(&self.config, &keyspace)
-                    .await
-                    .unwrap_or_else(|_| vec![]);
-            }
-
-            let mut result: Vec<CompletionItem> = Vec::new();
+            let items = match tbl_name.as_str() {
+                "" => self.schema_keyspace_scoped_fields(&keyspace).await,
+                _ => self.schema_hard_scoped_fields(&keyspace, &tbl_name).await,
+            };
+
+            return Ok(Some(CompletionResponse::Array(self.field_items(
+                &items,
+                Some(Some(&keyspace)),
+                line,
+                position,
+            ))));
+        }
 
-            if self.should_field_be_edit(line) {
-                for item in items {
-                    if lw_line.contains(&item.column_name.to_lowercase()) {
-                        continue;
-                    }
-                    let text_edit_str = self.column_to_text_edit(&item, Some(&keyspace));
-
-                    let text_edit = TextEdit {
-                        range: Range {
-                            start: Position {
-                                line: position.line,
-                                character: self.get_start_offset(line, position) + 1,
-                            },
-                            end: Position {
-                                line: position.line,
-                                // Insane wierd shit :D
-                                character: line.len() as u32,
-                            },
-                        },
-                        new_text: text_edit_str,
-                    };
+        let items = self.schema_g_fields().await;
+        Ok(Some(CompletionResponse::Array(self.field_items(
+            &items,
+            Some(None),
+            line,
+            position,
+        ))))
+    }
 
-                    result.push(CompletionItem {
-                        label: format!(
-                            "{} | {}.{}",
-                            item.column_name, item.keyspace_name, item.table_name,
-                        ),
-                        kind: Some(CompletionItemKind::SNIPPET),
-                        text_edit: Some(CompletionTextEdit::Edit(text_edit)),
-                        ..Default::default()
-                    });
-                }
-            } else {
-                for item in items {
-                    if lw_line.contains(&item.column_name.to_lowercase()) {
-                        continue;
-                    }
+    /*
+        User defined functions && aggregates callable in selectors
 
+        Keyspace of the FROM target, the latest USE keyspace otherwise.
+        Ones outside of the USE keyspace are qualified, ks.fn()
+    */
+    pub async fn get_udf_completions(
+        &self,
+        text: &str,
+        position: &Position,
+    ) -> Vec<CompletionItem> {
+        let use_keyspace = self.latest_keyspace(position).await;
+
+        let keyspace = match self.get_select_from_target(text, position) {
+            Some((Some(keyspace), _)) => keyspace,
+            _ => match &use_keyspace {
+                Some(keyspace) => keyspace.clone(),
+                None => return vec![],
+            },
+        };
+
+        let functions = self
+            .unwrap_query(cqlsh::query_keyspace_scoped_functions(
+                &self.config(),
+                &keyspace,
+            ))
+            .await
+            .into_iter()
+            .map(|f| (f.function_name, "User defined function"));
+
+        let aggregates = self
+            .unwrap_query(cqlsh::query_keyspace_scoped_aggregates(
+                &self.config(),
+                &keyspace,
+            ))
+            .await
+            .into_iter()
+            .map(|a| (a.aggregate_name, "User defined aggregate"));
+
+        let qualified = use_keyspace.as_deref() != Some(keyspace.as_str());
+
+        functions
+            .chain(aggregates)
+            .map(|(name, detail)| {
+                let call = match qualified {
+                    true => Self::quote_qualified_name(&keyspace, &name),
+                    false => Self::quote_identifier(&name),
+                };
+
+                CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(format!("{} | {}", detail, keyspace)),
+                    sort_text: Some(format!("3_{}", name.to_lowercase())),
+                    insert_text: Some(format!("{}($0)", call)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /*
+        FROM target of the SELECT statement at the cursor
+
+        SELECT
+            id,
+            na|
+        FROM ks.users;      -> (Some(ks), users)
+    */
+    pub fn get_select_from_target(
+        &self,
+        text: &str,
+        position: &Position,
+    ) -> Option<(Option<String>, String)> {
+        let offset = Self::position_to_offset(text, position);
+
+        let (start, end) = Self::statement_ranges(text)
+            .into_iter()
+            .find(|(start, end)| *start <= offset && offset <= *end)?;
+
+        let tokens = lexer::code_tokens(&text[start..end]);
+        let (_, keyspace, table) = Self::selected_columns(&tokens)?;
+
+        Some((keyspace, table))
+    }
+
+    /*
+        SELECT |
+        SELECT id, na|
+
+        Selector list may span several lines, the statement
+        is checked from its start in that case
+
+        SELECT
+            id,
+            |
+        FROM ks.users;
+    */
+    pub fn should_suggest_fields(&self, text: &str, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if Self::is_selector_slot(prefix) {
+            return true;
+        }
+
+        let offset = Self::position_to_offset(text, position);
+
+        Self::statement_ranges(text)
+            .into_iter()
+            .find(|(start, end)| *start <= offset && offset <= *end)
+            .is_some_and(|(start, _)| Self::is_selector_slot(&text[start..offset]))
+    }
+
+    fn is_selector_slot(prefix: &str) -> bool {
+        let tokens = lexer::code_tokens(prefix);
+
+        if !tokens.iter().any(|t| t.is_keyword("select"))
+            || tokens
+                .iter()
+                .any(|t| t.is_punct("*") || t.is_keyword("from"))
+        {
+            return false;
+        }
+
+        // Selector starts right after SELECT or a comma
+        match lexer::completed_tokens(&tokens, prefix).last() {
+            Some(last) => last.is_keyword("select") || last.is_punct(","),
+            None => false,
+        }
+    }
+
+    pub fn should_suggest_where_columns(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if !splitted.contains(&"from") || !splitted.contains(&"where") {
+            return false;
+        }
+
+        if let Some(semi_colon_pos) = prefix.find(';')
+            && position.character > semi_colon_pos as u32
+        {
+            return false;
+        }
+
+        // WHERE | or WHERE partial_name
+        let last_kw = if lw_prefix.ends_with(char::is_whitespace) {
+            splitted.last()
+        } else if splitted.len() > 1 {
+            splitted.get(splitted.len() - 2)
+        } else {
+            None
+        };
+
+        matches!(last_kw, Some(&"where") | Some(&"and"))
+    }
+
+    /*
+        GRANT |
+        REVOKE partial_permission|
+    */
+    pub fn should_suggest_permissions(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let lw_prefix = prefix.trim_start().to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if splitted.first() != Some(&"grant") && splitted.first() != Some(&"revoke") {
+            return false;
+        }
+
+        match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len() == 1,
+            false => splitted.len() == 2,
+        }
+    }
+
+    /*
+        GRANT ... TO |
+        REVOKE ... FROM |
+        ALTER | DROP ROLE | USER |
+        LIST ROLES OF |
+    */
+    pub fn should_suggest_roles(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        // Keyword right before the role that is being typed
+        let last_kw = if lw_prefix.ends_with(char::is_whitespace) {
+            splitted.len().checked_sub(1)
+        } else {
+            splitted.len().checked_sub(2)
+        };
+
+        let Some(last_kw) = last_kw else {
+            return false;
+        };
+
+        match (splitted[0], splitted[last_kw]) {
+            ("grant", "to") | ("revoke", "from") | ("list", "of") => true,
+            ("alter" | "drop", "role" | "user") => last_kw == 1,
+            _ => false,
+        }
+    }
+
+    /*
+        INSERT INTO ks.t (id, addr) VALUES (1, { |
+        UPDATE ks.t SET addr = { street: 'a', |
+    */
+    pub fn should_suggest_udt_fields(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        self.get_udt_literal_column(prefix).is_some()
+    }
+
+    /*
+        Innermost unclosed { literal before the cursor
+
+        Returns offset of the { and field names typed so far,
+        None if the cursor isn't at a field name position
+    */
+    pub fn get_udt_literal(&self, prefix: &str) -> Option<(usize, Vec<String>)> {
+        // (bracket, offset, typed fields, current slot start)
+        let mut stack = Vec::<(char, usize, Vec<String>, usize)>::new();
+        let mut in_single_quotes = false;
+        let mut in_double_quotes = false;
+
+        for (i, c) in prefix.char_indices() {
+            match c {
+                '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+                '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+                _ if in_single_quotes || in_double_quotes => {}
+                '{' | '(' | '[' => stack.push((c, i, vec![], i + 1)),
+                '}' | ')' | ']' => {
+                    stack.pop();
+                }
+                ',' => {
+                    if let Some(top) = stack.last_mut() {
+                        top.3 = i + 1;
+                    }
+                }
+                ':' => {
+                    if let Some(top) = stack.last_mut()
+                        && top.0 == '{'
+                    {
+                        let field = prefix[top.3..i].trim().replace('"', "");
+                        top.2.push(field.to_lowercase());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if in_single_quotes || in_double_quotes {
+            return None;
+        }
+
+        let (bracket, offset, fields, slot_start) = stack.pop()?;
+        let slot = prefix[slot_start..].trim_start();
+
+        if bracket != '{' || !slot.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        Some((offset, fields))
+    }
+
+    /*
+        Table && column the { literal is assigned to
+
+        Literals nested inside of other literals aren't resolved
+    */
+    pub fn get_udt_literal_column(&self, prefix: &str) -> Option<(Option<String>, String, String)> {
+        let (brace_offset, _) = self.get_udt_literal(prefix)?;
+        let before = &prefix[..brace_offset];
+        let lw_before = before.to_lowercase();
+
+        let words: Vec<&str> = lw_before.split_whitespace().collect();
+
+        // UPDATE ks.t SET a = 1, addr = {
+        if words.first() == Some(&"update") {
+            let set_offset = Self::find_word_offset(&lw_before, "set")?;
+            let assignment = before[set_offset..].trim_end().strip_suffix('=')?;
+            let column: String = assignment
+                .trim_end()
+                .chars()
+                .rev()
+                .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '"')
+                .collect::<Vec<char>>()
+                .into_iter()
+                .rev()
+                .collect();
+
+            let (keyspace, table) = Self::parse_qualified_name(before.split_whitespace().nth(1)?);
+            return Some((keyspace, table, Self::parse_identifier(&column)));
+        }
+
+        // INSERT INTO ks.t (id, addr) VALUES (1, {
+        if words.first() == Some(&"insert") && words.get(1) == Some(&"into") {
+            let values_offset = Self::find_word_offset(&lw_before, "values")?;
+            let into_offset = Self::find_word_offset(&lw_before, "into")?;
+
+            let target = &before[into_offset + "into".len()..values_offset];
+            let (table, columns) = target.split_once('(')?;
+            let columns: Vec<String> = columns
+                .split(')')
+                .next()?
+                .split(',')
+                .map(Self::parse_identifier)
+                .collect();
+
+            let values = before[values_offset + "values".len()..].trim_start();
+            let values = values.strip_prefix('(')?;
+
+            // Literal has to be a direct value of the VALUES list
+            let mut depth = 0;
+            let mut index = 0;
+            for c in values.chars() {
+                match c {
+                    '(' | '{' | '[' => depth += 1,
+                    ')' | '}' | ']' => depth -= 1,
+                    ',' if depth == 0 => index += 1,
+                    _ => {}
+                }
+            }
+
+            if depth != 0 {
+                return None;
+            }
+
+            let (keyspace, table) = Self::parse_qualified_name(table.trim());
+            return Some((keyspace, table, columns.get(index)?.clone()));
+        }
+
+        None
+    }
+
+    /*
+        INSERT INTO ks.t (|
+        INSERT INTO ks.t (id, partial_column|
+        INSERT INTO ks.t (id, name) |
+    */
+    pub fn should_suggest_insert_columns(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        self.get_insert_columns_clause(prefix).is_some()
+    }
+
+    /*
+        Table and columns of INSERT column list,
+        column that is still being typed isn't listed
+
+        INSERT INTO ks.t (id, na -> (Some(ks), t, [id], false)
+        INSERT INTO t (id, name) -> (None, t, [id, name], true)
+    */
+    pub fn get_insert_columns_clause(
+        &self,
+        prefix: &str,
+    ) -> Option<(Option<String>, String, Vec<String>, bool)> {
+        let lw_prefix = prefix.trim_start().to_lowercase();
+        let words: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if words.first() != Some(&"insert") || words.get(1) != Some(&"into") {
+            return None;
+        }
+
+        let into_offset = Self::find_word_offset(&prefix.to_lowercase(), "into")?;
+        let (table, columns) = prefix[into_offset + "into".len()..].split_once('(')?;
+
+        let table = table.trim();
+        if table.is_empty() || table.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let (keyspace, table) = Self::parse_qualified_name(table);
+
+        let (columns, closed) = match columns.split_once(')') {
+            Some((columns, rest)) => {
+                // VALUES keyword is allowed to be partially typed
+                let rest = rest.trim().to_lowercase();
+                if rest.contains(char::is_whitespace) || !"values".starts_with(&rest) {
+                    return None;
+                }
+                (columns, true)
+            }
+            None => (columns, false),
+        };
+
+        let mut columns: Vec<String> = columns.split(',').map(Self::parse_identifier).collect();
+
+        if !closed {
+            columns.pop();
+        }
+
+        columns.retain(|c| !c.is_empty());
+
+        Some((keyspace, table, columns, closed))
+    }
+
+    pub async fn get_insert_columns(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table, listed, closed)) = self.get_insert_columns_clause(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        // VALUES (?, ?) placeholders follow the listed columns
+        if closed {
+            if listed.is_empty() {
+                return Ok(Some(CompletionResponse::Array(vec![])));
+            }
+
+            let placeholders: Vec<String> = listed
+                .iter()
+                .enumerate()
+                .map(|(i, _)| format!("${{{}:?}}", i + 1))
+                .collect();
+
+            let result = ["VALUES", "values"]
+                .iter()
+                .map(|keyword| CompletionItem {
+                    label: format!("{} ({})", keyword, vec!["?"; listed.len()].join(", ")),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some(format!("Values of {}", listed.join(", "))),
+                    insert_text: Some(format!("{} ({});$0", keyword, placeholders.join(", "))),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                })
+                .collect();
+
+            return Ok(Some(CompletionResponse::Array(result)));
+        }
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter() {
+            if listed
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&column.column_name))
+            {
+                continue;
+            }
+
+            // Partition key columns can't be omitted
+            let (kind_detail, sort_prefix) = match column.kind.as_str() {
+                "partition_key" => (format!("{} (required)", self.column_kind_detail(column)), 0),
+                "clustering" => (self.column_kind_detail(column), 1),
+                _ => (self.column_kind_detail(column), 2),
+            };
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!(
+                    "{} | {} | {}.{}",
+                    kind_detail, column.column_type, column.keyspace_name, column.table_name
+                )),
+                sort_text: Some(format!("{}_{}", sort_prefix, column.column_name)),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        INSERT INTO ks.t (id, name) VALUES (1, |
+        SELECT * FROM ks.t WHERE id = |
+        DELETE FROM t WHERE id = partial_value|
+
+        Resolved column is returned, the handler picks templates by its type
+    */
+    pub async fn should_suggest_value_template(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> Option<Column> {
+        if self.config().offline {
+            return None;
+        }
+
+        self.get_value_column(line, position).await
+    }
+
+    /*
+        Column whose value is being typed, None when
+        the column isn't found in the schema
+    */
+    pub async fn get_value_column(&self, line: &str, position: &Position) -> Option<Column> {
+        let offset = Self::position_to_offset(line, &Position::new(0, position.character));
+        let (keyspace, table, column) = self.get_value_target(&line[..offset])?;
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => self.latest_keyspace(position).await?,
+        };
+
+        self.schema_hard_scoped_fields(&keyspace, &table)
+            .await
+            .into_iter()
+            .find(|c| c.column_name == column)
+    }
+
+    /*
+        Table && column of the value slot before the cursor,
+        only a single word is allowed to be typed in the slot
+
+        INSERT INTO ks.t (id, name) VALUES (1, |    -> (Some(ks), t, name)
+        UPDATE t SET a = 1 WHERE id = |             -> (None, t, id)
+    */
+    pub fn get_value_target(&self, prefix: &str) -> Option<(Option<String>, String, String)> {
+        let tokens = lexer::code_tokens(prefix);
+        let completed = lexer::completed_tokens(&tokens, prefix);
+        let last = completed.last()?;
+
+        let table_at = |index: usize| -> Option<(Option<String>, String)> {
+            match completed.get(index..index + 3) {
+                Some([keyspace, dot, table]) if dot.is_punct(".") && keyspace.end == dot.start => {
+                    Some((
+                        Some(Self::parse_identifier(keyspace.text)),
+                        Self::parse_identifier(table.text),
+                    ))
+                }
+                _ => {
+                    let table = completed.get(index).filter(|t| t.is_word())?;
+                    Some((None, Self::parse_identifier(table.text)))
+                }
+            }
+        };
+
+        let first = completed.first()?;
+
+        // INSERT INTO ks.t (id, name) VALUES (
+        if first.is_keyword("insert") {
+            let into = completed.iter().position(|t| t.is_keyword("into"))?;
+            let values = completed.iter().position(|t| t.is_keyword("values"))?;
+            let (keyspace, table) = table_at(into + 1)?;
+
+            let open = completed[into + 1..values]
+                .iter()
+                .position(|t| t.is_punct("("))?;
+            let columns: Vec<String> = completed[into + 1 + open + 1..values]
+                .split(|t| t.is_punct(","))
+                .filter_map(|column| match column {
+                    [column, ..] if column.is_word() => Some(Self::parse_identifier(column.text)),
+                    _ => None,
+                })
+                .collect();
+
+            if !completed.get(values + 1)?.is_punct("(")
+                || !(last.is_punct("(") || last.is_punct(","))
+            {
+                return None;
+            }
+
+            // Slot has to be a direct value of the VALUES list
+            let mut depth = 0;
+            let mut index = 0;
+            for token in &completed[values + 2..] {
+                match token.text {
+                    "(" | "[" | "{" => depth += 1,
+                    ")" | "]" | "}" => depth -= 1,
+                    "," if depth == 0 => index += 1,
+                    _ => {}
+                }
+            }
+
+            if depth != 0 {
+                return None;
+            }
+
+            return Some((keyspace, table, columns.get(index)?.clone()));
+        }
+
+        // WHERE id = , WHERE id = 1 AND name =
+        if !last.is_punct("=") || !completed.iter().any(|t| t.is_keyword("where")) {
+            return None;
+        }
+
+        let column = completed.get(completed.len().checked_sub(2)?)?;
+        if !column.is_word() {
+            return None;
+        }
+
+        let (keyspace, table) = if first.is_keyword("update") {
+            table_at(1)?
+        } else if first.is_keyword("select") || first.is_keyword("delete") {
+            let from = completed.iter().position(|t| t.is_keyword("from"))?;
+            table_at(from + 1)?
+        } else {
+            return None;
+        };
+
+        Some((keyspace, table, Self::parse_identifier(column.text)))
+    }
+
+    /*
+        Bind markers && a literal template matching the column type
+
+        uuid        -> uuid()
+        timestamp   -> currentTimestamp()
+        text        -> '...'
+    */
+    pub fn get_value_templates(&self, column: &Column) -> Vec<CompletionItem> {
+        let name = Self::quote_identifier(&column.column_name);
+
+        let mut items = vec![
+            CompletionItem {
+                label: "?".to_string(),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: Some(format!("Bind marker | {}", column.column_type)),
+                insert_text: Some("?".to_string()),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: format!(":{}", name),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: Some(format!("Named bind marker | {}", column.column_type)),
+                insert_text: Some(format!(":{}", name)),
+                ..Default::default()
+            },
+        ];
+
+        let column_type = column.column_type.to_lowercase();
+        let column_type = column_type
+            .strip_prefix("frozen<")
+            .and_then(|t| t.strip_suffix('>'))
+            .unwrap_or(&column_type);
+        let base_type = column_type.split('<').next().unwrap_or(column_type).trim();
+
+        let templates: &[(&str, &str)] = match base_type {
+            "uuid" => &[("uuid()", "uuid()$0")],
+            "timeuuid" => &[("now()", "now()$0")],
+            "timestamp" => &[
+                ("currentTimestamp()", "currentTimestamp()$0"),
+                ("toTimestamp(now())", "toTimestamp(now())$0"),
+            ],
+            "date" => &[("currentDate()", "currentDate()$0")],
+            "time" => &[("currentTime()", "currentTime()$0")],
+            "text" | "varchar" | "ascii" | "inet" => &[("'text'", "'${1}'$0")],
+            "boolean" => &[("true", "true$0"), ("false", "false$0")],
+            "blob" => &[("0x", "0x${1}$0")],
+            "list" => &[("[]", "[${1}]$0")],
+            "set" => &[("{}", "{${1}}$0")],
+            "map" => &[("{key: value}", "{${1:key}: ${2:value}}$0")],
+            "tuple" => &[("()", "(${1})$0")],
+            _ => &[],
+        };
+
+        for (label, snippet) in templates {
+            items.push(CompletionItem {
+                label: label.to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(format!("{} literal", column.column_type)),
+                insert_text: Some(snippet.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+        }
+
+        items
+    }
+
+    /*
+        UPDATE ks.t SET |
+        UPDATE ks.t USING TTL 60 SET a = 1, partial_column|
+    */
+    pub fn should_suggest_update_set_columns(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        self.get_update_set_clause(prefix).is_some()
+    }
+
+    /*
+        Table and columns already assigned in UPDATE SET clause
+
+        UPDATE ks.t SET a = 1, m['k'] = 2, na -> (Some(ks), t, [a, m])
+    */
+    pub fn get_update_set_clause(
+        &self,
+        prefix: &str,
+    ) -> Option<(Option<String>, String, Vec<String>)> {
+        let lw_prefix = prefix.to_lowercase();
+        let words: Vec<&str> = prefix.split_whitespace().collect();
+
+        if !words.first()?.eq_ignore_ascii_case("update") {
+            return None;
+        }
+
+        let set_offset = Self::find_word_offset(&lw_prefix, "set")?;
+        let assignments = &prefix[set_offset + "set".len()..];
+
+        if !assignments.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        // Assignments are split by commas outside of literals
+        let mut segments = vec![String::new()];
+        let mut depth = 0;
+        let mut in_quotes = false;
+
+        for c in assignments.chars() {
+            match c {
+                '\'' => in_quotes = !in_quotes,
+                '(' | '{' | '[' if !in_quotes => depth += 1,
+                ')' | '}' | ']' if !in_quotes => depth -= 1,
+                ',' if !in_quotes && depth == 0 => {
+                    segments.push(String::new());
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some(segment) = segments.last_mut() {
+                segment.push(c);
+            }
+        }
+
+        let typed = segments.pop()?;
+        if !typed
+            .trim_start()
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '"')
+        {
+            return None;
+        }
+
+        let assigned: Vec<String> = segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .trim_start()
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '"')
+                    .collect::<String>()
+            })
+            .map(|column| Self::parse_identifier(&column))
+            .collect();
+
+        let (keyspace, table) = Self::parse_qualified_name(words.get(1)?);
+
+        Some((keyspace, table, assigned))
+    }
+
+    /*
+        Primary key columns can't be assigned in SET,
+        counter columns get views = views + $0 instead of views = $0
+    */
+    pub async fn get_update_set_columns(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table, assigned)) = self.get_update_set_clause(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter() {
+            if column.kind == "partition_key" || column.kind == "clustering" {
+                continue;
+            }
+
+            if assigned
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&column.column_name))
+            {
+                continue;
+            }
+
+            let detail = format!(
+                "{} | {} | {}.{}",
+                self.column_kind_detail(column),
+                column.column_type,
+                column.keyspace_name,
+                column.table_name
+            );
+            let name = Self::quote_identifier(&column.column_name);
+
+            // Counters are only incremented or decremented, views = views + 1
+            if column.column_type.eq_ignore_ascii_case("counter") {
+                for operator in ["+", "-"] {
+                    result.push(CompletionItem {
+                        label: format!(
+                            "{} = {} {}",
+                            column.column_name, column.column_name, operator
+                        ),
+                        kind: Some(CompletionItemKind::FIELD),
+                        detail: Some(detail.clone()),
+                        filter_text: Some(column.column_name.clone()),
+                        insert_text: Some(format!("{} = {} {} $0", name, name, operator)),
+                        insert_text_format: Some(InsertTextFormat::SNIPPET),
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(detail),
+                insert_text: Some(format!("{} = $0", name)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        CREATE INDEX name ON ks.t (|
+        CREATE CUSTOM INDEX IF NOT EXISTS name ON t (partial_column|
+    */
+    pub fn should_suggest_index_columns(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        self.get_index_target_table(prefix).is_some()
+    }
+
+    /*
+        Table of CREATE INDEX while its column is being typed
+
+        CREATE INDEX i ON ks.t ( -> (Some(ks), t)
+        CREATE INDEX i ON t (a   -> (None, t)
+    */
+    pub fn get_index_target_table(&self, prefix: &str) -> Option<(Option<String>, String)> {
+        let lw_prefix = prefix.to_lowercase();
+        let words: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if !matches!(
+            words.as_slice(),
+            ["create", "index", ..] | ["create", "custom", "index", ..]
+        ) {
+            return None;
+        }
+
+        let on_offset = Self::find_word_offset(&lw_prefix, "on")?;
+
+        let (table, column) = prefix[on_offset + "on".len()..].split_once('(')?;
+        let table = table.trim();
+
+        // Only the first slot, KEYS(| and closed targets are skipped
+        if table.is_empty()
+            || table.contains(char::is_whitespace)
+            || column.contains(['(', ')', ','])
+            || column.trim().contains(char::is_whitespace)
+        {
+            return None;
+        }
+
+        Some(Self::parse_qualified_name(table))
+    }
+
+    pub fn should_suggest_index_class(&self, line: &str, position: &Position) -> bool {
+        self.get_index_class_clause(line, position).is_some()
+    }
+
+    /*
+        CREATE CUSTOM INDEX i ON t (c) USING |           -> class
+        CREATE CUSTOM INDEX i ON t (c) USING 'org.ap|    -> class
+        CREATE CUSTOM INDEX i ON t (c) USING 'sai' |     -> options
+
+        Regular CREATE INDEX doesn't take a class
+    */
+    pub fn get_index_class_clause(&self, line: &str, position: &Position) -> Option<&'static str> {
+        let prefix = line.get(..position.character as usize)?;
+
+        let tokens = lexer::code_tokens(prefix);
+        let tokens = lexer::completed_tokens(&tokens, prefix);
+
+        if !matches!(
+            tokens,
+            [create, custom, index, ..]
+                if create.is_keyword("create")
+                    && custom.is_keyword("custom")
+                    && index.is_keyword("index")
+        ) {
+            return None;
+        }
+
+        match tokens {
+            [.., using] if using.is_keyword("using") => Some("class"),
+            [.., using, class]
+                if using.is_keyword("using") && class.kind == lexer::TokenKind::String =>
+            {
+                match class.text.len() > 1 && class.text.ends_with('\'') {
+                    true => Some("options"),
+                    false => Some("class"),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /*
+        Collection columns are indexed through index functions
+
+        map<k, v>       -> KEYS(c), VALUES(c), ENTRIES(c)
+        set<v>, list<v> -> VALUES(c)
+        frozen<...>     -> FULL(c)
+    */
+    pub async fn get_index_columns(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table)) = self.get_index_target_table(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter() {
+            let detail = format!(
+                "{} | {} | {}.{}",
+                self.column_kind_detail(column),
+                column.column_type,
+                column.keyspace_name,
+                column.table_name
+            );
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(detail.clone()),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
+
+            let column_type = column.column_type.to_lowercase();
+            let functions: &[&str] = if column_type.starts_with("frozen<") {
+                &["FULL"]
+            } else if column_type.starts_with("map<") {
+                &["KEYS", "VALUES", "ENTRIES"]
+            } else if column_type.starts_with("set<") || column_type.starts_with("list<") {
+                &["VALUES"]
+            } else {
+                &[]
+            };
+
+            for function in functions {
+                result.push(CompletionItem {
+                    label: format!("{}({})", function, column.column_name),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(detail.clone()),
+                    insert_text: Some(format!(
+                        "{}({})",
+                        function,
+                        Self::quote_identifier(&column.column_name)
+                    )),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        ALTER TABLE ks.t |
+        ALTER TABLE ks.t ADD | DROP | RENAME |
+        ALTER TABLE ks.t ADD column_name |
+        ALTER TABLE ks.t RENAME column_name |
+    */
+    pub fn should_suggest_alter_table_ops(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        self.get_alter_table_clause(prefix).is_some()
+    }
+
+    /*
+        Keyspace, table and the part of ALTER TABLE that is being typed
+
+        ALTER TABLE ks.t      -> (Some(ks), t, operation)
+        ALTER TABLE t DROP    -> (None, t, drop)
+        ALTER TABLE t ADD c   -> (None, t, type)
+        ALTER TABLE t RENAME c -> (None, t, to)
+    */
+    pub fn get_alter_table_clause(&self, prefix: &str) -> Option<(Option<String>, String, String)> {
+        let words: Vec<&str> = prefix.split_whitespace().collect();
+        let lw_words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+
+        if lw_words.len() < 3 || lw_words[0] != "alter" || lw_words[1] != "table" {
+            return None;
+        }
+
+        // Word that is still being typed isn't a part of the clause
+        let typed = match prefix.ends_with(char::is_whitespace) {
+            true => words.len(),
+            false => words.len() - 1,
+        };
+
+        if typed < 3 {
+            return None;
+        }
+
+        let rest: Vec<&str> = lw_words[3..typed].iter().map(|w| w.as_str()).collect();
+        let part = match rest.as_slice() {
+            [] => "operation",
+            [op @ ("add" | "drop" | "rename")] => op,
+            ["add", _] => "type",
+            ["rename", _] => "to",
+            _ => return None,
+        };
+
+        let (keyspace, table) = Self::parse_qualified_name(words[2]);
+
+        Some((keyspace, table, part.to_string()))
+    }
+
+    /*
+        Primary key columns can't be dropped,
+        only primary key columns can be renamed
+    */
+    pub async fn get_alter_table_ops(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table, part)) = self.get_alter_table_clause(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyword = |label: &str, insert_text: &str, detail: &str| CompletionItem {
+            label: label.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(detail.to_string()),
+            insert_text: Some(insert_text.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        };
+
+        match part.as_str() {
+            "operation" => {
+                let mut items = Vec::<CompletionItem>::new();
+                for (op, detail) in [
+                    ("ADD", "Adds a column"),
+                    ("DROP", "Drops a non primary key column"),
+                    ("RENAME", "Renames a primary key column"),
+                    ("WITH", "Changes table options"),
+                ] {
+                    items.push(keyword(op, &format!("{} $0", op), detail));
+                    items.push(keyword(
+                        &op.to_lowercase(),
+                        &format!("{} $0", op.to_lowercase()),
+                        detail,
+                    ));
+                }
+                return Ok(Some(CompletionResponse::Array(items)));
+            }
+            "add" => {
+                return Ok(Some(CompletionResponse::Array(vec![CompletionItem {
+                    label: "column_name type".to_string(),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some("New column".to_string()),
+                    insert_text: Some(r#"${1:column_name} ${2:text}$0"#.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                }])));
+            }
+            "type" => {
+                return Ok(Some(CompletionResponse::Array(
+                    TYPES.iter().cloned().collect(),
+                )));
+            }
+            "to" => {
+                return Ok(Some(CompletionResponse::Array(vec![
+                    keyword("TO", "TO $0", "New name of the column"),
+                    keyword("to", "to $0", "New name of the column"),
+                ])));
+            }
+            _ => {}
+        }
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter() {
+            let is_primary_key = column.kind == "partition_key" || column.kind == "clustering";
+
+            if (part == "drop") == is_primary_key {
+                continue;
+            }
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!(
+                    "{} | {} | {}.{}",
+                    self.column_kind_detail(column),
+                    column.column_type,
+                    column.keyspace_name,
+                    column.table_name
+                )),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        Space typed right after an operator e.g WHERE id = |
+
+        Nothing can be suggested there, returning early
+        saves schema queries of the predicates
+    */
+    pub fn is_idle_space_trigger(
+        &self,
+        line: &str,
+        position: &Position,
+        context: Option<&CompletionContext>,
+    ) -> bool {
+        let Some(context) = context else {
+            return false;
+        };
+
+        if context.trigger_kind != CompletionTriggerKind::TRIGGER_CHARACTER
+            || context.trigger_character.as_deref() != Some(" ")
+        {
+            return false;
+        }
+
+        // Cursor column is in UTF-16 code units
+        let offset = Self::position_to_offset(line, &Position::new(0, position.character));
+        let prefix = &line[..offset];
+
+        if Self::is_in_string_literal(prefix, offset as u32) {
+            return false;
+        }
+
+        let before = prefix.trim_end();
+
+        // map< | is a type position, x < | is a comparison
+        let is_comparison = |operator: char| {
+            before
+                .strip_suffix(operator)
+                .is_some_and(|rest| rest.ends_with(char::is_whitespace))
+        };
+
+        before.ends_with('=')
+            || before.ends_with('+')
+            || is_comparison('<')
+            || is_comparison('>')
+            || is_comparison('-')
+    }
+
+    /*
+        frozen<ks.address> -> (Some(ks), address)
+    */
+    pub fn get_udt_name(&self, column_type: &str) -> (Option<String>, String) {
+        let mut name = column_type.trim();
+
+        while let Some(inner) = name
+            .strip_prefix("frozen<")
+            .and_then(|inner| inner.strip_suffix('>'))
+        {
+            name = inner.trim();
+        }
+
+        Self::parse_qualified_name(name)
+    }
+
+    pub async fn get_udt_fields(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let (Some((_, typed_fields)), Some((keyspace, table, column))) = (
+            self.get_udt_literal(prefix),
+            self.get_udt_literal_column(prefix),
+        ) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+
+        let Some(column) = columns.iter().find(|c| c.column_name == column) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let (type_keyspace, type_name) = self.get_udt_name(&column.column_type);
+        let type_keyspace = type_keyspace.unwrap_or(keyspace);
+
+        let fields = self
+            .unwrap_query(cqlsh::query_udt_fields(
+                &self.config(),
+                &type_keyspace,
+                &type_name,
+            ))
+            .await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for (field_name, field_type) in fields.iter() {
+            if typed_fields.contains(&field_name.to_lowercase()) {
+                continue;
+            }
+
+            result.push(CompletionItem {
+                label: field_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!("{} | {}.{}", field_type, type_keyspace, type_name)),
+                insert_text: Some(format!("{}: $0", Self::quote_identifier(field_name))),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        CONSISTENCY |
+        ... USING CONSISTENCY partial_level|
+        CONSISTENCY 'partial_level|
+    */
+    pub fn should_suggest_consistency(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        // Word that is still being typed isn't a part of the clause
+        let typed = match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => splitted.len().saturating_sub(1),
+        };
+
+        typed >= 1 && splitted[typed - 1] == "consistency"
+    }
+
+    /*
+        INSERT ... USING |
+        UPDATE ks.t USING TTL 60 AND |
+    */
+    pub fn should_suggest_using_options(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        let typed = match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => splitted.len().saturating_sub(1),
+        };
+
+        matches!(
+            splitted[..typed],
+            [.., "using"]
+                | [.., "using", "ttl" | "timestamp", _, "and"]
+                | [.., "using", _, _, "and", "ttl" | "timestamp", _, "and"]
+        )
+    }
+
+    /*
+        INSERT ... USING TTL |
+        UPDATE ks.t USING TTL 60 AND TIMESTAMP 17|
+    */
+    pub fn should_suggest_using_values(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        self.get_using_option(prefix).is_some()
+    }
+
+    /*
+        Option of the USING clause whose value is being typed
+
+        UPDATE ks.t USING TTL |                 -> ttl
+        DELETE FROM t USING TIMESTAMP 17|       -> timestamp
+        SELECT ttl |                            -> None
+    */
+    pub fn get_using_option(&self, prefix: &str) -> Option<&'static str> {
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if !matches!(
+            splitted.first(),
+            Some(&"insert") | Some(&"update") | Some(&"delete")
+        ) {
+            return None;
+        }
+
+        // Value that is still being typed has to be a number
+        let typed = match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => {
+                if !splitted.last()?.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                splitted.len() - 1
+            }
+        };
+
+        let option = match splitted[..typed] {
+            [.., "using", option] | [.., "using", "ttl" | "timestamp", _, "and", option] => option,
+            _ => return None,
+        };
+
+        match option {
+            "ttl" => Some("ttl"),
+            "timestamp" => Some("timestamp"),
+            _ => None,
+        }
+    }
+
+    /*
+        beg|                        -> begin
+
+        BEGIN BATCH
+            INSERT ...;
+            |                       -> apply
+            ap|                     -> apply
+    */
+    pub fn should_suggest_batch(&self, text: &str, line: &str, position: &Position) -> bool {
+        self.get_batch_clause(text, line, position).is_some()
+    }
+
+    /*
+        Batch keyword that can be typed at the current line
+
+        Batch context is found by scanning upward for BEGIN ... BATCH
+        not closed by APPLY BATCH, APPLY BATCH is offered only once
+        the batch has at least one statement && batches can't be nested
+    */
+    pub fn get_batch_clause(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+    ) -> Option<&'static str> {
+        let prefix = line.get(..position.character as usize)?;
+        let typed = prefix.trim_start().to_lowercase();
+
+        // Single word at the start of the line
+        if typed.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let lines: Vec<&str> = text.split('\n').collect();
+        let current = (position.line as usize).min(lines.len());
+
+        let mut statements = 0;
+        let mut is_open_batch = false;
+
+        for previous in lines[..current].iter().rev() {
+            if self.is_batch_end(previous) {
+                break;
+            }
+
+            statements += previous.matches(';').count();
+
+            if self.is_batch_begin(previous) {
+                is_open_batch = true;
+                break;
+            }
+        }
+
+        match is_open_batch {
+            true if statements > 0 && "apply".starts_with(&typed) => Some("apply"),
+            false if !typed.is_empty() && "begin".starts_with(&typed) => Some("begin"),
+            _ => None,
+        }
+    }
+
+    /*
+        SELECT * FROM ks.t |
+        SELECT * FROM ks.t partial_keyword|
+    */
+    pub fn should_suggest_select_tail_keywords(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if splitted.first() != Some(&"select") {
+            return false;
+        }
+
+        // Word that is still being typed isn't a part of the clause
+        let typed = match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => splitted.len().saturating_sub(1),
+        };
+
+        // Table must be the last typed word, selector list is before FROM
+        typed >= 3 && splitted[typed - 2] == "from" && !splitted[typed - 1].contains(',')
+    }
+
+    /*
+        SELECT * FROM t LIMIT |
+        SELECT * FROM t PER PARTITION LIMIT |
+    */
+    pub fn should_suggest_limit_value(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') || !prefix.ends_with(char::is_whitespace) {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        splitted.first() == Some(&"select") && splitted.last() == Some(&"limit")
+    }
+
+    /*
+        SELECT * FROM t WHERE name = 'a' |
+        SELECT * FROM t WHERE id = 1 AND age > 20 |
+
+        Last WHERE condition has to be complete,
+        WHERE id = | and WHERE id IN (1, | are skipped
+    */
+    pub fn should_suggest_allow_filtering(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if prefix.contains(';') || !prefix.ends_with(char::is_whitespace) {
+            return false;
+        }
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if splitted.first() != Some(&"select") || !splitted.contains(&"from") {
+            return false;
+        }
+
+        let Some(where_pos) = splitted.iter().position(|w| *w == "where") else {
+            return false;
+        };
+        let clause = &splitted[where_pos + 1..];
+
+        // WHERE clause is already over
+        if clause
+            .iter()
+            .any(|w| matches!(*w, "group" | "order" | "per" | "limit" | "allow"))
+        {
+            return false;
+        }
+
+        let condition = match clause.iter().rposition(|w| *w == "and") {
+            Some(pos) => &clause[pos + 1..],
+            None => clause,
+        };
+
+        let Some(last) = condition.last() else {
+            return false;
+        };
+
+        let joined = condition.join(" ");
+        let has_operator = joined.contains(['=', '<', '>'])
+            || condition.iter().any(|w| matches!(*w, "in" | "contains"));
+        let is_open = last.ends_with(['=', '<', '>', '!'])
+            || matches!(*last, "in" | "contains" | "key")
+            || joined.matches('(').count() > joined.matches(')').count();
+
+        has_operator && !is_open
+    }
+
+    /*
+        ALLOW FILTERING is offered only when partition key
+        isn't fully restricted. Nothing is offered if table
+        columns couldn't be resolved
+    */
+    pub async fn get_allow_filtering(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table)) = self.get_from_table(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+        let constrained = self.get_constrained_columns(prefix);
+        let missing_partition_keys = self.get_missing_partition_keys(&columns, &constrained);
+
+        if missing_partition_keys.is_empty() {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        }
+
+        let documentation = Documentation::String(format!(
+            "Unconstrained partition key columns: {}\n\nQuery will scan every partition of {}.{}. Filtering is expensive on large tables, prefer restricting the partition key or using an index.",
+            missing_partition_keys.join(", "),
+            keyspace,
+            table
+        ));
+
+        let result = ["ALLOW FILTERING;", "allow filtering;"]
+            .iter()
+            .map(|keyword| CompletionItem {
+                label: keyword.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Query requires filtering".to_string()),
+                documentation: Some(documentation.clone()),
+                insert_text: Some(keyword.to_string()),
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    pub fn should_suggest_order_by_columns(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let lw_prefix = prefix.to_lowercase();
+        let splitted: Vec<&str> = lw_prefix.split_whitespace().collect();
+
+        if !splitted.contains(&"from") || prefix.contains(';') {
+            return false;
+        }
+
+        // ORDER BY | or ORDER BY partial_name
+        let typed = match lw_prefix.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => splitted.len().saturating_sub(1),
+        };
+
+        typed >= 2 && splitted[typed - 2] == "order" && splitted[typed - 1] == "by"
+    }
+
+    /*
+        Keyspace and table of FROM clause
+
+        SELECT * FROM ks.users WHERE -> (Some(ks), users)
+        SELECT * FROM users WHERE -> (None, users)
+    */
+    pub fn get_from_table(&self, line: &str) -> Option<(Option<String>, String)> {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        let from_pos = split.iter().position(|w| w.to_lowercase() == "from")?;
+        let ksp_tbl = split.get(from_pos + 1)?.replace(";", "");
+
+        if ksp_tbl.is_empty() {
+            return None;
+        }
+
+        Some(Self::parse_qualified_name(&ksp_tbl))
+    }
+
+    /*
+        Column names already constrained after WHERE
+
+        WHERE id = 1 AND name IN ('a', 'b') AND -> [id, name]
+    */
+    pub fn get_constrained_columns(&self, line: &str) -> Vec<String> {
+        let lw_line = line.to_lowercase();
+        let where_clause = match Self::find_word_offset(&lw_line, "where") {
+            Some(pos) => &lw_line[pos + "where".len()..],
+            None => return vec![],
+        };
+
+        let words: Vec<&str> = where_clause.split_whitespace().collect();
+
+        // Column that is still being typed isn't constrained yet
+        let typed = match where_clause.ends_with(char::is_whitespace) {
+            true => words.len(),
+            false => words.len().saturating_sub(1),
+        };
+
+        let mut columns = Vec::<String>::new();
+        let mut expect_column = true;
+
+        for w in words.iter().take(typed) {
+            if *w == "and" {
+                expect_column = true;
+                continue;
+            }
+
+            if expect_column {
+                let column: String = w
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '"')
+                    .collect();
+
+                columns.push(column.replace('"', ""));
+                expect_column = false;
+            }
+        }
+
+        columns
+    }
+
+    /*
+        Every partition key column has to be restricted (= or IN),
+        otherwise query requires ALLOW FILTERING
+    */
+    pub fn get_missing_partition_keys(
+        &self,
+        columns: &[Column],
+        constrained: &[String],
+    ) -> Vec<String> {
+        columns
+            .iter()
+            .filter(|c| {
+                c.kind == "partition_key" && !constrained.contains(&c.column_name.to_lowercase())
+            })
+            .map(|c| c.column_name.clone())
+            .collect()
+    }
+
+    pub async fn get_where_columns(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table)) = self.get_from_table(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+        let indexed = self
+            .unwrap_query(cqlsh::query_indexed_columns(
+                &self.config(),
+                &keyspace,
+                &table,
+            ))
+            .await;
+        let constrained = self.get_constrained_columns(prefix);
+
+        let is_constrained =
+            |column: &Column| constrained.contains(&column.column_name.to_lowercase());
+        let is_filterable = |column: &Column| {
+            column.kind == "partition_key"
+                || column.kind == "clustering"
+                || indexed.contains(&column.column_name)
+        };
+
+        let missing_partition_keys = self.get_missing_partition_keys(&columns, &constrained);
+
+        let documentation = match missing_partition_keys.is_empty() {
+            true => None,
+            false => Some(Documentation::String(format!(
+                "Unconstrained partition key columns: {}",
+                missing_partition_keys.join(", ")
+            ))),
+        };
+
+        // Columns aren't marked if keys/indexes couldn't be resolved
+        let has_filterable = columns.iter().any(is_filterable);
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        /*
+            Key && indexed columns come first, other columns
+            are listed last since they require ALLOW FILTERING
+        */
+        for column in columns.iter() {
+            if is_constrained(column) {
+                continue;
+            }
+
+            let (kind_detail, sort_prefix) = match column.kind.as_str() {
+                "partition_key" => (format!("{} (required)", self.column_kind_detail(column)), 0),
+                "clustering" => (self.column_kind_detail(column), 1),
+                _ if indexed.contains(&column.column_name) => ("indexed".to_string(), 2),
+                _ if has_filterable => (
+                    format!(
+                        "{}, requires ALLOW FILTERING",
+                        self.column_kind_detail(column)
+                    ),
+                    3,
+                ),
+                _ => (self.column_kind_detail(column), 3),
+            };
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!(
+                    "{} | {} | {}.{}",
+                    kind_detail, column.column_type, column.keyspace_name, column.table_name
+                )),
+                documentation: documentation.clone(),
+                sort_text: Some(format!("{}_{}", sort_prefix, column.column_name)),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(result)))
+    }
+
+    /*
+        Only clustering columns are valid inside ORDER BY,
+        tables without clustering columns yield nothing
+    */
+    pub async fn get_order_by_columns(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let Some((keyspace, table)) = self.get_from_table(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
+
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
+        let clustering_order = self
+            .unwrap_query(cqlsh::query_clustering_order(
+                &self.config(),
+                &keyspace,
+                &table,
+            ))
+            .await;
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter().filter(|c| c.kind == "clustering") {
+            let order = clustering_order
+                .iter()
+                .find(|(name, _)| name == &column.column_name)
+                .map(|(_, order)| order.to_uppercase());
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!(
+                    "{} | {} | {}.{}",
+                    self.column_kind_detail(column),
+                    column.column_type,
+                    column.keyspace_name,
+                    column.table_name
+                )),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
+
+            let Some(order) = order else {
+                continue;
+            };
+
+            for direction in ["ASC", "DESC"] {
+                let detail = match direction == order {
+                    true => "Clustering order of the table",
+                    false => "Reversed clustering order",
+                };
+
+                for dir in [direction.to_string(), direction.to_lowercase()] {
                     result.push(CompletionItem {
-                        label: format!(
-                            "{} | {}.{}",
-                            item.column_name, item.keyspace_name, item.table_name,
-                        ),
-                        kind: Some(CompletionItemKind::FIELD),
-                        insert_text: Some(format!("{}", item.column_name)),
+                        label: format!("{} {}", column.column_name, dir),
+                        kind: Some(CompletionItemKind::SNIPPET),
+                        detail: Some(detail.to_string()),
+                        insert_text: Some(format!(
+                            "{} {}$0",
+                            Self::quote_identifier(&column.column_name),
+                            dir
+                        )),
+                        insert_text_format: Some(InsertTextFormat::SNIPPET),
                         ..Default::default()
                     });
                 }
             }
+        }
 
-            let mut x: Vec<CompletionItem> = CQL_NATIVE_FUNCTIONS.iter().cloned().collect();
+        Ok(Some(CompletionResponse::Array(result)))
+    }
 
-            result.append(&mut x);
-            return Ok(Some(CompletionResponse::Array(result)));
+    // Works
+    pub fn should_suggest_from(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let tokens = lexer::code_tokens(prefix);
+
+        if !tokens.iter().any(|t| t.is_keyword("select"))
+            || lexer::code_tokens(line)
+                .iter()
+                .any(|t| t.is_keyword("from"))
+        {
+            return false;
         }
 
-        /*
-            Text Edit
+        let completed = lexer::completed_tokens(&tokens, prefix);
 
-            line.len() == position.character;
-            SELECT id FROM ;
-            SELECT name ;
+        // SELECT count(*)| isn't followed by a space yet
+        if completed.len() == tokens.len() && !prefix.ends_with(char::is_whitespace) {
+            return false;
+        }
 
-            Insert Text
+        // FROM follows a complete selector, not SELECT itself or a comma
+        match completed.last() {
+            Some(last) => !last.is_keyword("select") && !last.is_punct(","),
+            None => false,
+        }
+    }
 
-            ... FROM keyspace_name.table_name;
-        */
+    /*
+        SELECT * FROM ks.|
+        INSERT INTO ks.partial_table|
+        UPDATE "Ks".|
+    */
+    pub fn should_suggest_dot_scoped_tables(&self, line: &str, position: &Position) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
 
-        let items = cqlsh::query_g_fields(&self.config)
-            .await
-            .unwrap_or_else(|_| vec![]);
+        if prefix.contains(';') {
+            return false;
+        }
 
-        let mut result: Vec<CompletionItem> = Vec::new();
+        self.get_dot_scoped_keyspace(prefix).is_some()
+    }
 
-        if self.should_field_be_edit(line) {
-            for item in items {
-                if lw_line.contains(&item.column_name.to_lowercase()) {
-                    continue;
-                }
-                let text_edit_str = self.column_to_text_edit(&item, None);
-
-                let text_edit = TextEdit {
-                    range: Range {
-                        start: Position {
-                            line: position.line,
-                            character: self.get_start_offset(line, position) + 1,
-                        },
-                        end: Position {
-                            line: position.line,
-                            character: line.len() as u32,
-                        },
-                    },
-                    new_text: text_edit_str,
+    /*
+        Keyspace before the dot of the word being typed
+
+        FROM ks.us -> Some(ks)
+        FROM ks.users.x -> None
+    */
+    pub fn get_dot_scoped_keyspace(&self, prefix: &str) -> Option<String> {
+        let words: Vec<&str> = prefix.split_whitespace().collect();
+
+        if words.len() < 2 || prefix.ends_with(char::is_whitespace) {
+            return None;
+        }
+
+        let previous = words[words.len() - 2].to_lowercase();
+        if !matches!(
+            previous.as_str(),
+            "from" | "into" | "update" | "table" | "truncate" | "exists" | "on"
+        ) {
+            return None;
+        }
+
+        let (keyspace, table) = words[words.len() - 1].split_once('.')?;
+
+        let is_identifier = |name: &str| {
+            name.strip_prefix('"')
+                .unwrap_or(name)
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_')
+        };
+
+        let keyspace = Self::parse_identifier(keyspace);
+        if keyspace.is_empty() || !is_identifier(&keyspace) || !is_identifier(table) {
+            return None;
+        }
+
+        Some(keyspace)
+    }
+
+    // Only table name is inserted, keyspace is already typed
+    pub async fn get_dot_scoped_tables(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        // Cursor column is in UTF-16 code units
+        let offset = Self::position_to_offset(line, &Position::new(0, position.character));
+        let prefix = &line[..offset];
+
+        let Some(keyspace) = self.get_dot_scoped_keyspace(prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let tables = self.schema_keyspace_scoped_tables(&keyspace).await;
+
+        // Partially typed name is replaced, it might already start with a quote
+        let typed = prefix.rsplit('.').next().unwrap_or("");
+        let range = Range {
+            start: Position {
+                line: position.line,
+                character: position
+                    .character
+                    .saturating_sub(typed.encode_utf16().count() as u32),
+            },
+            end: *position,
+        };
+
+        let items = tables
+            .iter()
+            .map(|table| {
+                let filter_text = if typed.starts_with('"') {
+                    format!("\"{}", table.table_name)
+                } else {
+                    table.table_name.clone()
                 };
 
-                result.push(CompletionItem {
-                    label: format!(
-                        "{} | {}.{}",
-                        item.column_name, item.keyspace_name, item.table_name,
-                    ),
-                    kind: Some(CompletionItemKind::SNIPPET),
-                    text_edit: Some(CompletionTextEdit::Edit(text_edit)),
+                CompletionItem {
+                    label: table.table_name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some(table.united()),
+                    filter_text: Some(filter_text),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                        range,
+                        new_text: Self::quote_identifier(&table.table_name),
+                    })),
                     ..Default::default()
-                });
-            }
-        } else {
-            for item in items {
-                if lw_line.contains(&item.column_name.to_lowercase()) {
-                    continue;
                 }
-                result.push(CompletionItem {
-                    label: format!(
-                        "{} | {}.{}",
-                        item.column_name, item.keyspace_name, item.table_name,
-                    ),
-                    kind: Some(CompletionItemKind::VALUE),
-                    insert_text: Some(format!("{}", item.column_name)),
+            })
+            .collect();
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    /*
+        Tables of the latest USE keyspace are inserted without keyspace,
+        tables of other keyspaces stay qualified
+    */
+    pub async fn get_table_completions(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let keyspace = self.latest_keyspace(position).await;
+
+        let tables = self.schema_g_tables().await;
+
+        let mut items = Vec::<CompletionItem>::new();
+
+        for table in tables {
+            if keyspace.as_deref() == Some(table.keyspace_name.as_str()) {
+                items.push(CompletionItem {
+                    label: table.table_name.clone(),
+                    // Keyword to display scoped tables in different color
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some(format!("{}", table.united())),
+                    data: Some(Self::resolve_data(
+                        &table.keyspace_name,
+                        &table.table_name,
+                        None,
+                    )),
+                    insert_text: Some(Self::quote_identifier(&table.table_name)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
                     ..Default::default()
                 });
+                continue;
+            }
+
+            items.push(CompletionItem {
+                label: table.united(),
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some(format!("{}", table.united())),
+                data: Some(Self::resolve_data(
+                    &table.keyspace_name,
+                    &table.table_name,
+                    None,
+                )),
+                insert_text: Some(Self::quote_qualified_name(
+                    &table.keyspace_name,
+                    &table.table_name,
+                )),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            })
+        }
+
+        let typed = Self::typed_identifier(line, position);
+
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
+    }
+
+    pub fn is_inside_create_table_no_position(&self, line_index: usize, lines: &[String]) -> bool {
+        let current_line = line_index;
+        if current_line >= lines.len() {
+            return false;
+        }
+
+        let mut found_create_table = false;
+        let mut search_index = current_line;
+
+        loop {
+            let line_content = lines[search_index].to_lowercase();
+
+            if (line_content.contains("create table")
+                || line_content.contains("create table if not exists"))
+                && line_content.contains("(")
+                && !line_content.contains(")")
+            {
+                info!("Found CRT: {}", line_content);
+                found_create_table = true;
+                break;
+            }
+
+            if self.line_contains_cql_kw(&line_content) {
+                return false;
+            }
+
+            // Previous statement ended before reaching CREATE
+            if search_index != current_line && line_content.contains(";") {
+                return false;
+            }
+
+            if search_index == 0 {
+                break;
+            }
+            search_index -= 1;
+        }
+
+        if !found_create_table {
+            return false;
+        }
+
+        for line_content in lines.iter().skip(current_line + 1) {
+            if self.line_contains_cql_kw(line_content) {
+                return false;
+            }
+
+            if line_content.contains(")") {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn is_inside_create_type_no_position(&self, line_index: usize, lines: &[String]) -> bool {
+        let current_line = line_index;
+        if current_line >= lines.len() {
+            return false;
+        }
+
+        let mut found_create_table = false;
+        let mut search_index = current_line;
+
+        loop {
+            let line_content = lines[search_index].to_lowercase();
+
+            if (line_content.contains("create type")
+                || line_content.contains("create type if not exists"))
+                && line_content.contains("(")
+                && !line_content.contains(")")
+            {
+                info!("Found CRT: {}", line_content);
+                found_create_table = true;
+                break;
+            }
+
+            if self.line_contains_cql_kw(&line_content) {
+                return false;
+            }
+
+            // Previous statement ended before reaching CREATE
+            if search_index != current_line && line_content.contains(";") {
+                return false;
+            }
+
+            if search_index == 0 {
+                break;
+            }
+            search_index -= 1;
+        }
+
+        if !found_create_table {
+            return false;
+        }
+
+        for line_content in lines.iter().skip(current_line + 1) {
+            if self.line_contains_cql_kw(line_content) {
+                return false;
+            }
+
+            if line_content.contains(")") {
+                return true;
             }
         }
 
-        let mut x: Vec<CompletionItem> = CQL_NATIVE_FUNCTIONS.iter().cloned().collect();
+        false
+    }
+
+    pub fn is_inside_create_table(&self, line: &str, position: &Position, text: &str) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+        let lw = prefix.to_lowercase();
+        let split: Vec<&str> = lw.split(' ').collect();
+
+        if split.len() < 2 {
+            return false;
+        }
+
+        if split[0] == "create"
+            && split[1] == "table"
+            && line.contains("(")
+            && line.contains(")")
+            && (prefix.contains("(") && !prefix.contains(")"))
+        {
+            return true;
+        }
+
+        let lines: Vec<&str> = text.split('\n').collect();
 
-        result.append(&mut x);
-        Ok(Some(CompletionResponse::Array(result)))
-    }
+        let current_line = position.line as usize;
+        if current_line >= lines.len() {
+            return false;
+        }
 
-    // Works
-    pub fn should_suggest_fields(&self, line: &str, position: &Position) -> bool {
-        let prefix = match line.get(..position.character as usize) {
-            Some(p) => p,
-            None => return false,
-        };
+        let mut found_create_table = false;
+        let mut search_index = current_line;
 
-        let trimmed_prefix = prefix.trim_end().to_lowercase();
-        let splitted: Vec<&str> = trimmed_prefix.split(' ').collect();
+        loop {
+            let line_content = lines[search_index].to_lowercase();
 
-        if !splitted.contains(&"select") || splitted.contains(&"*") || splitted.contains(&"from") {
-            return false;
-        }
+            if (line_content.contains("create table")
+                || line_content.contains("create table if not exists"))
+                && line_content.contains("(")
+                && !line_content.contains(")")
+            {
+                info!("Found CRT: {}", line_content);
+                found_create_table = true;
+                break;
+            }
 
-        if splitted.contains(&"select") && splitted.len() == 1 {
-            return true;
+            if self.line_contains_cql_kw(&line_content) {
+                return false;
+            }
+
+            if search_index == 0 {
+                break;
+            }
+            search_index -= 1;
         }
 
-        if splitted.len() > 2 && !splitted[splitted.len() - 2].contains(",") {
+        if !found_create_table {
             return false;
         }
 
-        if splitted.len() > 0
-            && trimmed_prefix.len() != prefix.len()
-            && !splitted[splitted.len() - 1].contains(",")
-        {
-            return false;
+        for i in (current_line + 1)..lines.len() {
+            let line_content = lines[i];
+
+            if self.line_contains_cql_kw(line_content) {
+                return false;
+            }
+
+            if line_content.contains(")") {
+                return true;
+            }
         }
 
-        true
+        false
     }
 
-    // Works
-    pub fn should_suggest_from(&self, line: &str, position: &Position) -> bool {
-        let prefix = match line.get(..position.character as usize) {
-            Some(p) => p,
-            None => return false,
-        };
+    /*
+        Keyspace of the CREATE TABLE the cursor is in
 
-        let trimmed_prefix = prefix.trim_end().to_lowercase();
-        let splitted: Vec<&str> = trimmed_prefix.split(' ').collect();
+        CREATE TABLE ks.t (          -> Some(ks)
+        CREATE TABLE IF NOT EXISTS t ( -> latest USE keyspace
+    */
+    pub async fn create_table_keyspace(&self, position: &Position, text: &str) -> Option<String> {
+        let header = text
+            .lines()
+            .take(position.line as usize + 1)
+            .collect::<Vec<&str>>()
+            .into_iter()
+            .rev()
+            .find(|line| line.to_lowercase().contains("create table"));
+
+        let table_name = header.and_then(|header| {
+            let words: Vec<&str> = header.split_whitespace().collect();
+            let table_pos = words.iter().position(|w| w.eq_ignore_ascii_case("table"))?;
+
+            words[table_pos + 1..]
+                .iter()
+                .find(|w| !matches!(w.to_lowercase().as_str(), "if" | "not" | "exists"))
+                .map(|w| w.split('(').next().unwrap_or(w).to_string())
+        });
+
+        if let Some((Some(keyspace), _)) = table_name.as_deref().map(Self::parse_qualified_name) {
+            return Some(keyspace);
+        }
 
-        if !splitted.contains(&"select")
-            || splitted.contains(&"from")
-            || line.to_lowercase().contains("from")
-        {
-            return false;
+        self.latest_keyspace(position).await
+    }
+
+    /*
+        Statement before the cursor && the whole statement
+        if the cursor is inside of CREATE MATERIALIZED VIEW
+
+        View statements usually span multiple lines,
+        FROM is often typed before the SELECT list
+    */
+    pub fn get_view_statement(&self, position: &Position, text: &str) -> Option<(String, String)> {
+        let offset = Self::position_to_offset(text, position);
+
+        let (start, end) = Self::statement_ranges(text)
+            .into_iter()
+            .find(|(start, end)| *start <= offset && offset <= *end)?;
+
+        let statement = &text[start..end];
+        let words: Vec<String> = statement
+            .split_whitespace()
+            .take(3)
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        if words != ["create", "materialized", "view"] {
+            return None;
         }
 
-        if splitted.len() == 1
-            && splitted.contains(&"select")
-            && trimmed_prefix.len() != prefix.len()
-        {
+        Some((text[start..offset].to_string(), statement.to_string()))
+    }
+
+    /*
+        CREATE MATERIALIZED VIEW v AS SELECT a, | FROM ks.t ...
+        ... PRIMARY KEY ((a, |
+    */
+    pub async fn should_suggest_view_columns(&self, position: &Position, text: &str) -> bool {
+        let Some((prefix, statement)) = self.get_view_statement(position, text) else {
             return false;
-        }
+        };
 
-        if splitted.len() == 2
-            && splitted.contains(&"select")
-            && trimmed_prefix.len() == prefix.len()
-        {
+        if self.get_from_table(&statement).is_none() {
             return false;
         }
 
-        if splitted.len() >= 3
-            && splitted.contains(&"select")
-            && splitted[splitted.len() - 1].contains(",")
-        {
-            return false;
+        self.get_view_clause(&prefix).is_some()
+    }
+
+    /*
+        Part of the view being typed
+
+        AS SELECT a, |         -> select
+        PRIMARY KEY ((a), |    -> primary_key
+    */
+    pub fn get_view_clause(&self, prefix: &str) -> Option<&'static str> {
+        let lw_prefix = prefix.to_lowercase();
+
+        if let Some(select_pos) = Self::find_word_offset(&lw_prefix, "select") {
+            let selectors = &lw_prefix[select_pos + "select".len()..];
+
+            if Self::find_word_offset(selectors, "from").is_none() {
+                let typed = selectors.trim_end();
+                return (typed.is_empty() || typed.ends_with(',')).then_some("select");
+            }
         }
 
-        true
+        let primary_pos = Self::find_word_offset(&lw_prefix, "primary")?;
+        let key_clause = lw_prefix[primary_pos + "primary".len()..].trim_start();
+        let columns = key_clause.strip_prefix("key")?.trim_start();
+
+        let is_open =
+            columns.starts_with('(') && columns.matches('(').count() > columns.matches(')').count();
+        let typed = columns.trim_end();
+
+        (is_open && (typed.ends_with('(') || typed.ends_with(','))).then_some("primary_key")
     }
 
-    pub async fn get_table_completions(
+    /*
+        Columns of the base table, primary key columns
+        go first since they are required in the view key
+    */
+    pub async fn get_view_columns(
         &self,
         position: &Position,
+        text: &str,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        if let Some(keyspace) = self.latest_keyspace(&position).await {
-            let tables = cqlsh::query_keyspace_scoped_tables(&self.config, &keyspace)
-                .await
-                .unwrap_or_else(|_| vec![]);
+        let Some((prefix, statement)) = self.get_view_statement(position, text) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
 
-            let tables_unscoped = cqlsh::query_g_tables(&self.config)
-                .await
-                .unwrap_or_else(|_| vec![]);
+        let Some((keyspace, table)) = self.get_from_table(&statement) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
 
-            let mut items = Vec::<CompletionItem>::new();
+        let keyspace = match keyspace {
+            Some(keyspace) => keyspace,
+            None => match self.latest_keyspace(position).await {
+                Some(keyspace) => keyspace,
+                None => return Ok(Some(CompletionResponse::Array(vec![]))),
+            },
+        };
 
-            for table in tables {
-                items.push(CompletionItem {
-                    label: table.table_name.clone(),
-                    // Keyword to display scoped tables in different color
-                    kind: Some(CompletionItemKind::KEYWORD),
-                    detail: Some(format!("{}", table.united())),
-                    insert_text: Some(format!(r#"{}"#, table.table_name)),
-                    insert_text_format: Some(InsertTextFormat::SNIPPET),
-                    ..Default::default()
-                })
-            }
+        let columns = self.schema_hard_scoped_fields(&keyspace, &table).await;
 
-            for tablex in tables_unscoped {
-                items.push(CompletionItem {
-                    label: tablex.united(),
-                    kind: Some(CompletionItemKind::VARIABLE),
-                    detail: Some(format!("{}", tablex.united())),
-                    insert_text: Some(format!(r#"{}"#, tablex.united())),
-                    insert_text_format: Some(InsertTextFormat::SNIPPET),
-                    ..Default::default()
-                })
+        let is_primary_key_clause = self.get_view_clause(&prefix) == Some("primary_key");
+        let lw_prefix = prefix.to_lowercase();
+
+        let mut result: Vec<CompletionItem> = Vec::new();
+
+        for column in columns.iter() {
+            let is_primary_key = column.kind == "partition_key" || column.kind == "clustering";
+
+            // Columns already listed in the clause being typed
+            let clause = match is_primary_key_clause {
+                true => Self::find_word_offset(&lw_prefix, "primary").map(|p| &lw_prefix[p..]),
+                false => Self::find_word_offset(&lw_prefix, "select").map(|p| &lw_prefix[p..]),
+            };
+            if clause.is_some_and(|clause| {
+                clause
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .any(|w| w == column.column_name.to_lowercase())
+            }) {
+                continue;
             }
 
-            return Ok(Some(CompletionResponse::Array(items)));
+            let sort_prefix = match (is_primary_key_clause, is_primary_key) {
+                (true, true) => 0,
+                _ => 1,
+            };
+
+            result.push(CompletionItem {
+                label: column.column_name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(format!(
+                    "{} | {} | {}.{}",
+                    self.column_kind_detail(column),
+                    column.column_type,
+                    column.keyspace_name,
+                    column.table_name
+                )),
+                sort_text: Some(format!("{}_{}", sort_prefix, column.column_name)),
+                insert_text: Some(Self::quote_identifier(&column.column_name)),
+                ..Default::default()
+            });
         }
 
-        let tables = cqlsh::query_g_tables(&self.config)
-            .await
-            .unwrap_or_else(|_| vec![]);
+        Ok(Some(CompletionResponse::Array(result)))
+    }
 
-        let mut items = Vec::<CompletionItem>::new();
+    /*
+        Part of CREATE TABLE before the cursor
 
-        for table in tables {
-            items.push(CompletionItem {
-                label: table.united(),
-                kind: Some(CompletionItemKind::VARIABLE),
-                detail: Some(format!("{}", table.united())),
-                insert_text: Some(format!(r#"{}"#, table.united())),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            })
+        Table isn't in the cluster yet, columns are taken from the
+        statement itself. Unterminated statement runs until the cursor
+    */
+    pub fn get_create_table_prefix(&self, position: &Position, text: &str) -> Option<String> {
+        let offset = Self::position_to_offset(text, position);
+
+        let (start, end) = Self::statement_ranges(text)
+            .into_iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)?;
+
+        if offset > end && text[start..end].ends_with(';') {
+            return None;
         }
 
-        return Ok(Some(CompletionResponse::Array(items)));
+        let prefix = text.get(start..offset)?;
+        let words: Vec<String> = prefix
+            .split_whitespace()
+            .take(2)
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        (words == ["create", "table"]).then(|| prefix.to_string())
     }
 
-    pub async fn is_inside_create_table_no_position(
+    /*
+        CREATE TABLE t (
+            id int,
+            PRIMARY KEY (|
+            PRIMARY KEY ((id, |
+    */
+    pub async fn should_suggest_primary_key_columns(
         &self,
-        line_index: usize,
-        document_url: &Url,
+        position: &Position,
+        text: &str,
     ) -> bool {
-        let documents = self.documents.read().await;
+        self.get_create_table_prefix(position, text)
+            .is_some_and(|prefix| Self::primary_key_clause(&prefix).is_some())
+    }
 
-        if let Some(document) = documents.get(document_url) {
-            let lw_doc_text = document;
-            let lines: Vec<&str> = lw_doc_text.split('\n').collect();
+    /*
+        Byte offset of PRIMARY KEY whose column list is being typed
 
-            let current_line = line_index;
-            if current_line >= lines.len() {
-                return false;
-            }
+        Only the positions right after ( or , are reported,
+        inline id int PRIMARY KEY isn't a clause
+    */
+    fn primary_key_clause(prefix: &str) -> Option<usize> {
+        let tokens = lexer::code_tokens(prefix);
+        let tokens = lexer::completed_tokens(&tokens, prefix);
 
-            let mut found_create_table = false;
-            let mut search_index = current_line;
+        let primary = tokens.windows(3).rposition(|w| {
+            w[0].is_keyword("primary") && w[1].is_keyword("key") && w[2].is_punct("(")
+        })?;
 
-            loop {
-                let line_content = lines[search_index].to_lowercase();
+        let clause = &tokens[primary + 2..];
+        let opened = clause.iter().filter(|t| t.is_punct("(")).count();
+        let closed = clause.iter().filter(|t| t.is_punct(")")).count();
 
-                if (line_content.contains("create table")
-                    || line_content.contains("create table if not exists"))
-                    && line_content.contains("(")
-                    && !line_content.contains(")")
-                {
-                    info!("Found CRT: {}", line_content);
-                    found_create_table = true;
-                    break;
-                }
+        let last = clause.last()?;
+        (opened > closed && (last.is_punct("(") || last.is_punct(",")))
+            .then_some(tokens[primary].start)
+    }
 
-                if self.line_contains_cql_kw(&line_content) {
-                    return false;
-                }
+    /*
+        Columns declared in the CREATE TABLE the cursor is in, (name, type)
 
-                if search_index == 0 {
-                    break;
-                }
-                search_index -= 1;
-            }
+        Table isn't in system_schema until the statement is executed,
+        unlike query_hard_scoped_fields these come from the buffer
+    */
+    pub async fn columns_in_buffer_create_table(
+        &self,
+        text: &str,
+        position: &Position,
+    ) -> Vec<(String, String)> {
+        self.buffer_column_declarations(text, position)
+            .await
+            .into_iter()
+            .map(|(name, column_type, _)| (name, column_type))
+            .collect()
+    }
 
-            if !found_create_table {
-                return false;
-            }
+    /*
+        (name, type, inline PRIMARY KEY) of columns declared before the cursor
+    */
+    async fn buffer_column_declarations(
+        &self,
+        text: &str,
+        position: &Position,
+    ) -> Vec<(String, String, bool)> {
+        match self.get_create_table_prefix(position, text) {
+            Some(prefix) => Self::column_declarations(&prefix),
+            None => vec![],
+        }
+    }
 
-            for i in (current_line + 1)..lines.len() {
-                let line_content = lines[i];
+    /*
+        (name, type, inline PRIMARY KEY) of CREATE TABLE with unclosed column list
 
-                if self.line_contains_cql_kw(line_content) {
-                    return false;
-                }
+        CREATE TABLE t (id int PRIMARY KEY, views counter
 
-                if line_content.contains(")") {
-                    return true;
-                }
-            }
-        }
+        Column list is cut before PRIMARY KEY (...), closed && parsed with
+        tree-sitter. Quoted names end up in ERROR nodes, so the name is
+        the leaf right before the type
+    */
+    pub fn column_declarations(prefix: &str) -> Vec<(String, String, bool)> {
+        let tokens = lexer::code_tokens(prefix);
+        let end = tokens
+            .windows(3)
+            .rposition(|w| {
+                w[0].is_keyword("primary") && w[1].is_keyword("key") && w[2].is_punct("(")
+            })
+            .map_or(prefix.len(), |primary| tokens[primary].start);
 
-        false
-    }
+        let head = prefix[..end].trim_end().trim_end_matches(',');
+        let statement = format!("{}\n);", head);
 
-    pub async fn is_inside_create_type_no_position(
-        &self,
-        line_index: usize,
-        document_url: &Url,
-    ) -> bool {
-        let documents = self.documents.read().await;
+        let Some(tree) = parse_cql(&statement) else {
+            return vec![];
+        };
 
-        if let Some(document) = documents.get(document_url) {
-            let lw_doc_text = document;
-            let lines: Vec<&str> = lw_doc_text.split('\n').collect();
+        fn leaf(node: Node, forward: bool) -> Option<Node> {
+            let mut current = node;
+            let mut sibling = loop {
+                let sibling = match forward {
+                    true => current.next_sibling(),
+                    false => current.prev_sibling(),
+                };
+                if let Some(sibling) = sibling {
+                    break sibling;
+                }
+                current = current.parent()?;
+            };
 
-            let current_line = line_index;
-            if current_line >= lines.len() {
-                return false;
+            while sibling.child_count() > 0 {
+                sibling = match forward {
+                    true => sibling.child(0)?,
+                    false => sibling.child(sibling.child_count() - 1)?,
+                };
             }
 
-            let mut found_create_table = false;
-            let mut search_index = current_line;
-
-            loop {
-                let line_content = lines[search_index].to_lowercase();
+            Some(sibling)
+        }
 
-                if (line_content.contains("create type")
-                    || line_content.contains("create type if not exists"))
-                    && line_content.contains("(")
-                    && !line_content.contains(")")
+        fn collect(node: Node, statement: &str, columns: &mut Vec<(String, String, bool)>) {
+            if node.kind() == "cql_types_union" {
+                if let Some(name) = leaf(node, false)
+                    .filter(|name| matches!(name.kind(), "identifier" | "quoted_identifier"))
                 {
-                    info!("Found CRT: {}", line_content);
-                    found_create_table = true;
-                    break;
-                }
-
-                if self.line_contains_cql_kw(&line_content) {
-                    return false;
-                }
+                    let primary_key = leaf(node, true).is_some_and(|next| {
+                        statement[next.byte_range()].eq_ignore_ascii_case("primary")
+                    });
 
-                if search_index == 0 {
-                    break;
+                    columns.push((
+                        Backend::parse_identifier(&statement[name.byte_range()]),
+                        statement[node.byte_range()].to_string(),
+                        primary_key,
+                    ));
                 }
-                search_index -= 1;
+                return;
             }
 
-            if !found_create_table {
-                return false;
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                collect(child, statement, columns);
             }
+        }
 
-            for i in (current_line + 1)..lines.len() {
-                let line_content = lines[i];
+        let mut columns = Vec::<(String, String, bool)>::new();
+        collect(tree.root_node(), &statement, &mut columns);
 
-                if self.line_contains_cql_kw(line_content) {
-                    return false;
-                }
+        columns
+    }
 
-                if line_content.contains(")") {
-                    return true;
-                }
-            }
+    /*
+        Whether one of the declared columns is already PRIMARY KEY,
+        the key can't be declared inline twice
+    */
+    pub async fn has_inline_primary_key(&self, text: &str, position: &Position) -> bool {
+        self.buffer_column_declarations(text, position)
+            .await
+            .iter()
+            .any(|(_, _, primary_key)| *primary_key)
+    }
+
+    /*
+        Declared columns that aren't in the key yet,
+        (pk1, pk2), ck template right after PRIMARY KEY (
+    */
+    pub async fn get_primary_key_columns(
+        &self,
+        position: &Position,
+        text: &str,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let Some(prefix) = self.get_create_table_prefix(position, text) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let Some(primary) = Self::primary_key_clause(&prefix) else {
+            return Ok(Some(CompletionResponse::Array(vec![])));
+        };
+
+        let clause = &prefix[primary..];
+        let listed: Vec<String> = lexer::code_tokens(clause)
+            .iter()
+            .filter(|t| t.is_word())
+            .map(|t| Self::parse_identifier(t.text))
+            .collect();
+
+        let mut result: Vec<CompletionItem> = self
+            .columns_in_buffer_create_table(text, position)
+            .await
+            .into_iter()
+            .filter(|(column, _)| !listed.contains(column))
+            .map(|(column, column_type)| CompletionItem {
+                label: column.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(column_type),
+                insert_text: Some(Self::quote_identifier(&column)),
+                ..Default::default()
+            })
+            .collect();
+
+        // Only the opening ( of the clause is typed
+        if clause.trim_end().ends_with('(') && clause.matches('(').count() == 1 {
+            result.push(CompletionItem {
+                label: "((pk1, pk2), ck)".to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some("Composite partition key with a clustering column".to_string()),
+                insert_text: Some("(${1:pk1}, ${2:pk2}), ${3:ck}$0".to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
         }
 
-        false
+        Ok(Some(CompletionResponse::Array(result)))
     }
 
-    pub async fn is_inside_create_table(
+    /*
+        CREATE TABLE ks.t (...) WITH |
+        AND |
+
+        Fires only after the column definitions are closed,
+        so it doesn't collide with is_inside_create_table
+    */
+    pub async fn should_suggest_table_with_options(
         &self,
         line: &str,
         position: &Position,
-        document_url: &Url,
+        text: &str,
     ) -> bool {
         let prefix = match line.get(..position.character as usize) {
             Some(p) => p,
             None => return false,
         };
-        let lw = prefix.to_lowercase();
-        let split: Vec<&str> = lw.split(' ').collect();
 
-        if split.len() < 2 {
-            return false;
+        let lines: Vec<&str> = text.split('\n').collect();
+        let current_line = (position.line as usize).min(lines.len());
+
+        // Statement text from its beginning up to the cursor
+        let mut statement = prefix.to_lowercase();
+        for previous in lines[..current_line].iter().rev() {
+            if previous.contains(';') {
+                break;
+            }
+            statement = format!("{} {}", previous.to_lowercase(), statement);
         }
 
-        if split[0] == "create"
-            && split[1] == "table"
-            && line.contains("(")
-            && line.contains(")")
-            && (prefix.contains("(") && !prefix.contains(")"))
+        let splitted: Vec<&str> = statement.split_whitespace().collect();
+
+        if statement.contains(';')
+            || splitted.len() < 2
+            || (splitted[0] != "create" && splitted[0] != "alter")
+            || splitted[1] != "table"
         {
-            return true;
+            return false;
+        }
+
+        if splitted[0] == "create" && !statement.contains(')') {
+            return false;
         }
 
-        let documents = self.documents.read().await;
+        // Outside of column definitions and option maps
+        if statement.matches('(').count() != statement.matches(')').count()
+            || statement.matches('{').count() != statement.matches('}').count()
+        {
+            return false;
+        }
 
-        if let Some(document) = documents.get(document_url) {
-            let lw_doc_text = document;
-            let lines: Vec<&str> = lw_doc_text.split('\n').collect();
+        let typed = match statement.ends_with(char::is_whitespace) {
+            true => splitted.len(),
+            false => splitted.len().saturating_sub(1),
+        };
 
-            let current_line = position.line as usize;
-            if current_line >= lines.len() {
-                return false;
-            }
+        if typed == 0 || !splitted[..typed].contains(&"with") {
+            return false;
+        }
 
-            let mut found_create_table = false;
-            let mut search_index = current_line;
+        matches!(splitted[typed - 1], "with" | "and")
+    }
 
-            loop {
-                let line_content = lines[search_index].to_lowercase();
+    /*
+        Table of ALTER TABLE statement the cursor is in
 
-                if (line_content.contains("create table")
-                    || line_content.contains("create table if not exists"))
-                    && line_content.contains("(")
-                    && !line_content.contains(")")
-                {
-                    info!("Found CRT: {}", line_content);
-                    found_create_table = true;
-                    break;
-                }
+        ALTER TABLE ks.t WITH | -> (Some(ks), t)
+        CREATE TABLE ...        -> None
+    */
+    pub fn get_alter_table_target(
+        &self,
+        position: &Position,
+        text: &str,
+    ) -> Option<(Option<String>, String)> {
+        let offset = Self::position_to_offset(text, position);
+
+        // Range ends before trailing whitespace, WITH | is still inside
+        let (start, end) = Self::statement_ranges(text)
+            .into_iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)?;
+
+        if offset > end && text[start..end].ends_with(';') {
+            return None;
+        }
 
-                if self.line_contains_cql_kw(&line_content) {
-                    return false;
-                }
+        let words: Vec<&str> = text[start..end].split_whitespace().take(3).collect();
 
-                if search_index == 0 {
-                    break;
-                }
-                search_index -= 1;
+        match words.as_slice() {
+            [alter, table, name]
+                if alter.eq_ignore_ascii_case("alter") && table.eq_ignore_ascii_case("table") =>
+            {
+                Some(Self::parse_qualified_name(name))
             }
+            _ => None,
+        }
+    }
 
-            if !found_create_table {
-                return false;
-            }
+    /*
+        Current values of WITH options rendered as CQL literals
 
-            for i in (current_line + 1)..lines.len() {
-                let line_content = lines[i];
+        gc_grace_seconds -> 864000
+        comment          -> 'users'
+        compaction       -> {'class': 'SizeTieredCompactionStrategy', ...}
+    */
+    pub fn table_option_literals(&self, options: &TableOptions) -> Vec<(&'static str, String)> {
+        let text = |value: &str| format!("'{}'", value.replace('\'', "''"));
 
-                if self.line_contains_cql_kw(line_content) {
-                    return false;
-                }
+        // class goes first, the rest is sorted to keep the order stable
+        let map = |entries: &HashMap<String, String>| {
+            let mut keys: Vec<&String> = entries.keys().collect();
+            keys.sort_by_key(|key| (key.as_str() != "class", key.as_str()));
+
+            let pairs: Vec<String> = keys
+                .iter()
+                .map(|key| format!("{}: {}", text(key), text(&entries[*key])))
+                .collect();
+
+            format!("{{{}}}", pairs.join(", "))
+        };
 
-                if line_content.contains(")") {
-                    return true;
-                }
-            }
+        let mut literals = Vec::<(&'static str, String)>::new();
+
+        if let Some(value) = &options.compaction {
+            literals.push(("compaction", map(value)));
+        }
+        if let Some(value) = &options.compression {
+            literals.push(("compression", map(value)));
+        }
+        if let Some(value) = &options.caching {
+            literals.push(("caching", map(value)));
+        }
+        if let Some(value) = options.default_time_to_live {
+            literals.push(("default_time_to_live", value.to_string()));
+        }
+        if let Some(value) = options.gc_grace_seconds {
+            literals.push(("gc_grace_seconds", value.to_string()));
+        }
+        if let Some(value) = options.bloom_filter_fp_chance {
+            literals.push(("bloom_filter_fp_chance", value.to_string()));
+        }
+        if let Some(value) = options.cdc {
+            literals.push(("cdc", value.to_string()));
+        }
+        if let Some(value) = &options.comment {
+            literals.push(("comment", text(value)));
+        }
+        if let Some(value) = &options.speculative_retry {
+            literals.push(("speculative_retry", text(value)));
+        }
+        if let Some(value) = options.memtable_flush_period_in_ms {
+            literals.push(("memtable_flush_period_in_ms", value.to_string()));
+        }
+        if let Some(value) = options.min_index_interval {
+            literals.push(("min_index_interval", value.to_string()));
+        }
+        if let Some(value) = options.max_index_interval {
+            literals.push(("max_index_interval", value.to_string()));
+        }
+        if let Some(value) = options.crc_check_chance {
+            literals.push(("crc_check_chance", value.to_string()));
         }
 
-        false
+        literals
     }
 
     pub async fn should_suggest_types_completions(
         &self,
         line: &str,
         position: &Position,
-        document_url: &Url,
+        text: &str,
     ) -> bool {
-        if !self
-            .is_inside_create_table(line, position, document_url)
-            .await
-        {
+        if !self.is_inside_create_table(line, position, text) {
             return false;
         }
 
@@ -1436,6 +4277,88 @@ impl Backend {
         }
     }
 
+    /*
+        tags set<|
+        scores map<text, |
+        history list<frozen<tuple<int, |
+
+        Spaces after < && , are allowed e.g. map< text, |
+    */
+    pub async fn should_suggest_collection_inner_type(
+        &self,
+        line: &str,
+        position: &Position,
+        text: &str,
+    ) -> bool {
+        let prefix = match line.get(..position.character as usize) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if self.get_collection_type_slot(prefix).is_none() {
+            return false;
+        }
+
+        self.is_inside_create_table(line, position, text)
+    }
+
+    /*
+        Collection type of the innermost unclosed < and index of
+        the parameter that is being typed
+
+        tags map<text, | -> (map, 1)
+    */
+    pub fn get_collection_type_slot(&self, prefix: &str) -> Option<(String, usize)> {
+        // (collection, parameter index)
+        let mut stack = Vec::<(String, usize)>::new();
+        let mut slot_start = 0;
+
+        for (i, c) in prefix.char_indices() {
+            match c {
+                '<' => {
+                    let collection: String = prefix[..i]
+                        .trim_end()
+                        .chars()
+                        .rev()
+                        .take_while(|c| c.is_alphanumeric() || *c == '_')
+                        .collect::<Vec<char>>()
+                        .into_iter()
+                        .rev()
+                        .collect();
+
+                    stack.push((collection.to_lowercase(), 0));
+                    slot_start = i + 1;
+                }
+                '>' => {
+                    stack.pop();
+                }
+                ',' => {
+                    if let Some(top) = stack.last_mut() {
+                        top.1 += 1;
+                        slot_start = i + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (collection, index) = stack.pop()?;
+
+        if !matches!(
+            collection.as_str(),
+            "map" | "set" | "list" | "tuple" | "frozen"
+        ) {
+            return None;
+        }
+
+        let slot = prefix[slot_start..].trim_start();
+        if !slot.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        Some((collection, index))
+    }
+
     /*
         [field_name] [type] [type_modifier]
 
@@ -1447,12 +4370,9 @@ impl Backend {
         &self,
         line: &str,
         position: &Position,
-        document_url: &Url,
+        text: &str,
     ) -> bool {
-        if !self
-            .is_inside_create_table(line, position, document_url)
-            .await
-        {
+        if !self.is_inside_create_table(line, position, text) {
             return false;
         }
 
@@ -1560,24 +4480,8 @@ impl Backend {
         }
 
         if split.contains(&"create")
-            && ((split[split.len() - 1].to_lowercase() == "table"
-                || split[split.len() - 2].to_lowercase() == "table")
-                || (split[split.len() - 1].to_lowercase() == "view"
-                    || split[split.len() - 2].to_lowercase() == "view")
-                || (split[split.len() - 1].to_lowercase() == "keyspace"
-                    || split[split.len() - 2].to_lowercase() == "keyspace")
-                || (split[split.len() - 1].to_lowercase() == "aggregate"
-                    || split[split.len() - 2].to_lowercase() == "aggregate")
-                || (split[split.len() - 1].to_lowercase() == "function"
-                    || split[split.len() - 2].to_lowercase() == "function")
-                || (split[split.len() - 1].to_lowercase() == "index"
-                    || split[split.len() - 2].to_lowercase() == "index")
-                || (split[split.len() - 1].to_lowercase() == "role"
-                    || split[split.len() - 2].to_lowercase() == "role")
-                || (split[split.len() - 1].to_lowercase() == "type"
-                    || split[split.len() - 2].to_lowercase() == "type")
-                || (split[split.len() - 1].to_lowercase() == "user")
-                || split[split.len() - 2].to_lowercase() == "user")
+            && (DDL_OBJECTS_LWC.contains(&split[split.len() - 1].to_string())
+                || DDL_OBJECTS_LWC.contains(&split[split.len() - 2].to_string()))
         {
             return true;
         }
@@ -1645,7 +4549,931 @@ impl Backend {
         false
     }
 
-    pub fn should_edit_select_statement(&self, line: &str, lines: &Vec<String>) -> bool {
-        false
+    /*
+        TRUNCATE |        -> TABLE keyword && tables
+        TRUNCATE us|      -> TABLE keyword && tables
+    */
+    pub fn should_suggest_truncate(&self, line: &str, position: &Position) -> bool {
+        let Some(prefix) = line.get(..position.character as usize) else {
+            return false;
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw = prefix.to_lowercase();
+        let words: Vec<&str> = lw.split_whitespace().collect();
+        let ends_with_space = lw.ends_with(char::is_whitespace);
+
+        match words.as_slice() {
+            ["truncate"] => ends_with_space,
+            ["truncate", word] => !ends_with_space && *word != "table" && !word.contains('.'),
+            _ => false,
+        }
+    }
+
+    /*
+        TRUNCATE TABLE |
+        TRUNCATE TABLE us|
+    */
+    pub fn should_suggest_truncate_tables(&self, line: &str, position: &Position) -> bool {
+        let Some(prefix) = line.get(..position.character as usize) else {
+            return false;
+        };
+
+        if prefix.contains(';') {
+            return false;
+        }
+
+        let lw = prefix.to_lowercase();
+        let words: Vec<&str> = lw.split_whitespace().collect();
+        let ends_with_space = lw.ends_with(char::is_whitespace);
+
+        match words.as_slice() {
+            ["truncate", "table"] => ends_with_space,
+            ["truncate", "table", word] => !ends_with_space && !word.contains('.'),
+            _ => false,
+        }
+    }
+
+    pub fn should_suggest_describe(&self, line: &str, position: &Position) -> bool {
+        self.get_describe_clause(line, position).is_some()
+    }
+
+    /*
+        DESCRIBE |              -> objects
+        DESC TABLE |            -> table
+        DESCRIBE KEYSPACE my|   -> keyspace
+        DESCRIBE TYPE |         -> type
+    */
+    pub fn get_describe_clause(&self, line: &str, position: &Position) -> Option<&'static str> {
+        let prefix = line.get(..position.character as usize)?;
+
+        let tokens = lexer::code_tokens(prefix);
+        let words = lexer::words(lexer::completed_tokens(&tokens, prefix));
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+        match words.as_slice() {
+            ["describe" | "desc"] => Some("objects"),
+            ["describe" | "desc", "keyspace"] => Some("keyspace"),
+            ["describe" | "desc", "table" | "columnfamily"] => Some("table"),
+            ["describe" | "desc", "type"] => Some("type"),
+            _ => None,
+        }
+    }
+
+    pub fn should_edit_select_statement(&self, line: &str) -> bool {
+        let lw = line.trim().to_lowercase();
+
+        lw == "select" || lw.starts_with("select ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cqlsh::CqlSettings;
+    use crate::lsp::{Document, FormattingSettings};
+    use crate::schema::SchemaSnapshot;
+    use tokio::sync::RwLock;
+
+    async fn open(backend: &Backend, text: &str) {
+        let uri = Url::parse("file:///tmp/test.cql").unwrap();
+        *backend.current_document.write().await =
+            Some(RwLock::new(Document::new(uri, text.to_string())));
+    }
+
+    fn column(table: &str, name: &str, column_type: &str, kind: &str) -> Column {
+        Column {
+            keyspace_name: "ks".to_string(),
+            table_name: table.to_string(),
+            column_name: name.to_string(),
+            column_type: column_type.to_string(),
+            kind: kind.to_string(),
+        }
+    }
+
+    // ks.users (id int, created_at timestamp, name text, PRIMARY KEY (id, created_at))
+    fn service_with_schema(context_based_select: bool) -> tower_lsp::LspService<Backend> {
+        let mut settings = CqlSettings::new();
+        settings.context_based_select = context_based_select;
+
+        let service = Backend::test_service_with(
+            settings,
+            FormattingSettings::from_env("7", "false", "preserve", "false"),
+        );
+
+        *service.inner().schema_snapshot.try_write().unwrap() = Some(SchemaSnapshot {
+            keyspaces: vec!["ks".to_string()],
+            tables: vec![],
+            columns: vec![
+                column("users", "name", "text", "regular"),
+                column("users", "id", "int", "partition_key"),
+                column("users", "created_at", "timestamp", "clustering"),
+            ],
+        });
+
+        service
+    }
+
+    async fn set_tables(backend: &Backend, tables: &[(&str, &str)]) {
+        backend
+            .schema_snapshot
+            .write()
+            .await
+            .as_mut()
+            .unwrap()
+            .tables = tables
+            .iter()
+            .map(|(keyspace, table)| cqlsh::Table {
+                keyspace_name: keyspace.to_string(),
+                table_name: table.to_string(),
+            })
+            .collect();
+    }
+
+    fn completion_items(
+        response: tower_lsp::jsonrpc::Result<Option<CompletionResponse>>,
+    ) -> Vec<CompletionItem> {
+        match response {
+            Ok(Some(CompletionResponse::Array(items))) => items,
+            _ => vec![],
+        }
+    }
+
+    fn line(line: u32) -> Position {
+        Position { line, character: 0 }
+    }
+
+    #[test]
+    fn use_keyspace_lines_are_parsed() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+
+        for (line, keyspace) in [
+            (r#"USE "my_ks";"#, Some("my_ks")),
+            (r#"USE "MyKs";"#, Some("MyKs")),
+            ("use my_ks;", Some("my_ks")),
+            ("  Use   MY_KS ;", Some("my_ks")),
+            (r#"USE "キースペース";"#, Some("キースペース")),
+            ("USE だよ;", Some("だよ")),
+            ("USE", None),
+            ("us", None),
+            ("だよ", None),
+            ("USER ks;", None),
+            (r#"USE "ks;"#, None),
+            ("SELECT * FROM t;", None),
+        ] {
+            assert_eq!(
+                backend.get_use_keyspace(line).as_deref(),
+                keyspace,
+                "{}",
+                line
+            );
+            assert_eq!(
+                backend.is_use_keyspace_line(line),
+                keyspace.is_some(),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn latest_keyspace_is_taken_from_the_last_use() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+
+        open(
+            backend,
+            "use 'ks';\nSELECT * FROM t;\nUSE   \"Ks2\" ;\n\nuse \"キー\";\n",
+        )
+        .await;
+
+        assert_eq!(backend.latest_keyspace(&line(0)).await, None);
+        assert_eq!(
+            backend.latest_keyspace(&line(1)).await.as_deref(),
+            Some("ks")
+        );
+        assert_eq!(
+            backend.latest_keyspace(&line(3)).await.as_deref(),
+            Some("Ks2")
+        );
+        assert_eq!(
+            backend.latest_keyspace(&line(5)).await.as_deref(),
+            Some("キー")
+        );
+    }
+
+    #[test]
+    fn start_offset_is_the_start_of_the_typed_word() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+
+        for (line, character, offset) in [
+            ("name", 0, 0),
+            ("name", 4, 0),
+            ("    name", 4, 4),
+            ("    na", 6, 4),
+            ("\tna", 3, 1),
+            ("SELECT na", 9, 7),
+            ("SELECT name", 9, 7),
+            ("SELECT a,na", 11, 9),
+            ("SELECT a, na", 12, 10),
+            ("SELECT ", 7, 7),
+            ("SELECT だよ", 9, 7),
+            // Mid-word, only the part before the cursor counts
+            ("name", 2, 0),
+            ("    name", 6, 4),
+            ("SELECT a,name", 11, 9),
+            // 🚀 takes two UTF-16 units
+            ("SELECT '🚀', na", 15, 13),
+            ("SELECT '🚀', na", 14, 13),
+            ("🚀 na", 5, 3),
+            ("SELECT 🚀na", 11, 7),
+        ] {
+            assert_eq!(
+                backend.get_start_offset(line, &Position::new(0, character)),
+                offset,
+                "{:?} at {}",
+                line,
+                character
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn context_based_select_inserts_from_clause() {
+        let text = "USE ks;\nSELECT ";
+        let position = Position::new(1, 7);
+
+        let service = service_with_schema(true);
+        let backend = service.inner();
+        open(backend, text).await;
+
+        let items = completion_items(backend.get_fields(text, "SELECT ", &position).await);
+        let id = items
+            .iter()
+            .find(|item| item.label.starts_with("id |"))
+            .unwrap();
+
+        match &id.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => {
+                assert_eq!(edit.new_text, "id, FROM users;");
+                assert_eq!(edit.range.start, position);
+            }
+            _ => panic!("expected a text edit, got {:?}", id),
+        }
+    }
+
+    #[tokio::test]
+    async fn column_only_is_inserted_without_context_based_select() {
+        let text = "USE ks;\nSELECT ";
+        let position = Position::new(1, 7);
+
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        open(backend, text).await;
+
+        let items = completion_items(backend.get_fields(text, "SELECT ", &position).await);
+        let id = items
+            .iter()
+            .find(|item| item.label.starts_with("id |"))
+            .unwrap();
+
+        assert_eq!(id.insert_text.as_deref(), Some("id"));
+        assert!(id.text_edit.is_none());
+    }
+
+    #[tokio::test]
+    async fn from_target_is_found_on_a_later_line() {
+        let text = "USE ks;\nSELECT\n    id,\n    \nFROM users;\nSELECT * FROM other;";
+        let position = Position::new(3, 4);
+
+        let service = service_with_schema(true);
+        let backend = service.inner();
+        open(backend, text).await;
+
+        assert_eq!(
+            backend.get_select_from_target(text, &position),
+            Some((None, "users".to_string()))
+        );
+        assert_eq!(
+            backend.get_select_from_target(
+                "SELECT\n    id,\n    \nFROM ks.users;",
+                &Position::new(2, 4)
+            ),
+            Some((Some("ks".to_string()), "users".to_string()))
+        );
+
+        let items = completion_items(backend.get_fields(text, "    ", &position).await);
+        let labels: Vec<&str> = items
+            .iter()
+            .filter(|item| item.kind == Some(CompletionItemKind::FIELD))
+            .map(|item| item.label.as_str())
+            .collect();
+        assert_eq!(
+            labels,
+            ["name | ks.users", "id | ks.users", "created_at | ks.users"]
+        );
+
+        // FROM is already there, columns are inserted alone
+        assert!(items.iter().all(|item| item.text_edit.is_none()));
+    }
+
+    #[tokio::test]
+    async fn keys_sort_before_columns_and_functions() {
+        let text = "USE ks;\nSELECT ";
+
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        open(backend, text).await;
+
+        let mut items = completion_items(
+            backend
+                .get_fields(text, "SELECT ", &Position::new(1, 7))
+                .await,
+        );
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+
+        let position = |label: &str| {
+            items
+                .iter()
+                .position(|item| item.label.starts_with(label))
+                .unwrap()
+        };
+
+        assert!(position("id |") < position("created_at |"));
+        assert!(position("created_at |") < position("name |"));
+        assert!(position("name |") < position("count(*)"));
+        assert!(position("name |") < position("now"));
+
+        let sort_text = |label: &str| {
+            KEYWORDS
+                .iter()
+                .find(|item| item.label == label)
+                .and_then(|item| item.sort_text.clone())
+                .unwrap()
+        };
+        assert!(sort_text("SELECT") < sort_text("select"));
+    }
+
+    #[tokio::test]
+    async fn dot_scoped_tables_replace_the_typed_name() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        set_tables(backend, &[("ks", "users")]).await;
+
+        for (line, character, start) in [
+            ("SELECT * FROM ks.us", 19, 17),
+            ("SELECT * FROM ks.", 17, 17),
+            ("SELECT * FROM ks.\"us", 20, 17),
+            // だ is a single UTF-16 unit, 🚀 takes two
+            ("SELECT 'だ🚀' FROM ks.us", 23, 21),
+            ("SELECT * FROM ks.us -- だ🚀", 19, 17),
+        ] {
+            let position = Position::new(0, character);
+            let items = completion_items(backend.get_dot_scoped_tables(line, &position).await);
+            assert_eq!(items.len(), 1, "{}", line);
+
+            match &items[0].text_edit {
+                Some(CompletionTextEdit::Edit(edit)) => {
+                    assert_eq!(edit.range.start, Position::new(0, start), "{}", line);
+                    assert_eq!(edit.range.end, position);
+                    assert_eq!(edit.new_text, "users");
+                }
+                _ => panic!("expected a text edit, got {:?}", items[0]),
+            }
+        }
+
+        // Column past the end of the line
+        let items = completion_items(
+            backend
+                .get_dot_scoped_tables("FROM ks.", &Position::new(0, 40))
+                .await,
+        );
+        assert_eq!(items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tables_of_the_use_keyspace_are_unqualified() {
+        let text = "USE ks;\nSELECT * FROM ";
+        let position = Position::new(1, 14);
+
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        set_tables(
+            backend,
+            &[("ks", "users"), ("ks", "Events"), ("other", "users")],
+        )
+        .await;
+        open(backend, text).await;
+
+        let items = completion_items(
+            backend
+                .get_table_completions("SELECT * FROM ", &position)
+                .await,
+        );
+        let inserted: Vec<(&str, &str)> = items
+            .iter()
+            .map(|item| {
+                (
+                    item.label.as_str(),
+                    item.insert_text.as_deref().unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            inserted,
+            [
+                ("users", "users"),
+                ("Events", "\"Events\""),
+                ("other.users", "other.users"),
+            ]
+        );
+
+        // Without USE every table stays qualified
+        open(backend, "SELECT * FROM ").await;
+        let items = completion_items(
+            backend
+                .get_table_completions("SELECT * FROM ", &Position::new(0, 14))
+                .await,
+        );
+        assert!(items.iter().all(|item| {
+            item.insert_text
+                .as_deref()
+                .unwrap_or_default()
+                .contains('.')
+        }));
+    }
+
+    #[tokio::test]
+    async fn tables_are_filtered_by_the_typed_prefix() {
+        let text = "USE ks;\nSELECT * FROM us";
+        let position = Position::new(1, 16);
+
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        set_tables(
+            backend,
+            &[
+                ("ks", "users"),
+                ("ks", "events"),
+                ("other", "user_logs"),
+                ("other", "stats"),
+            ],
+        )
+        .await;
+        open(backend, text).await;
+
+        let items = completion_items(
+            backend
+                .get_table_completions("SELECT * FROM us", &position)
+                .await,
+        );
+        let filtered: Vec<(&str, &str)> = items
+            .iter()
+            .map(|item| {
+                (
+                    item.label.as_str(),
+                    item.filter_text.as_deref().unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            filtered,
+            [("users", "users"), ("other.user_logs", "other.user_logs")]
+        );
+
+        for (line, typed) in [
+            ("SELECT * FROM us", "us"),
+            ("SELECT * FROM \"Us", "us"),
+            ("SELECT * FROM ks.us", "ks.us"),
+            ("SELECT * FROM ", ""),
+        ] {
+            let position = Position::new(0, line.len() as u32);
+            assert_eq!(Backend::typed_identifier(line, &position), typed);
+        }
+    }
+
+    #[test]
+    fn alter_table_target_is_read_from_the_given_text() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        let text = "CREATE TABLE t (id int PRIMARY KEY);\nALTER TABLE Ks.\"Users\"\n    WITH ";
+
+        assert_eq!(
+            backend.get_alter_table_target(&Position::new(2, 9), text),
+            Some((Some("ks".to_string()), "Users".to_string()))
+        );
+        assert_eq!(
+            backend.get_alter_table_target(&Position::new(0, 20), text),
+            None
+        );
+        assert_eq!(
+            backend.get_alter_table_target(&Position::new(0, 26), "ALTER TABLE t WITH a = 1; "),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn keywords_use_the_requested_document() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///tmp/other.cql").unwrap();
+
+        // Last opened document has the cursor line inside of a comment
+        open(backend, "/* notes\nSEL\n*/").await;
+
+        let text = "SELECT * FROM t;\nSEL";
+        assert!(
+            backend
+                .should_suggest_keywords(text, "SEL", &Position::new(1, 3), &uri)
+                .await
+        );
+        assert!(
+            !backend
+                .should_suggest_keywords("/* notes\nSEL\n*/", "SEL", &Position::new(1, 3), &uri)
+                .await
+        );
+    }
+
+    // Schema is still served from the snapshot, queries outside of it time out
+    fn go_online(backend: &Backend) {
+        let mut settings = CqlSettings::new();
+        settings.offline = false;
+        settings.query_timeout_ms = 100;
+        *backend.config.write().unwrap() = std::sync::Arc::new(settings);
+    }
+
+    fn space_triggered(uri: &Url, position: Position) -> CompletionParams {
+        CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(CompletionContext {
+                trigger_kind: CompletionTriggerKind::TRIGGER_CHARACTER,
+                trigger_character: Some(" ".to_string()),
+            }),
+        }
+    }
+
+    /*
+        Debug build of the completion future outgrows the 2MB stack
+        of test threads, the server polls it on the main thread
+    */
+    fn complete(
+        backend: &Backend,
+        params: CompletionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        use tower_lsp::LanguageServer;
+
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn_scoped(scope, || {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap()
+                        .block_on(backend.completion(params))
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        })
+    }
+
+    #[tokio::test]
+    async fn space_after_operator_skips_completion() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+
+        go_online(backend);
+
+        let uri = Url::parse("file:///tmp/test.cql").unwrap();
+        let text = "USE ks;\nSELECT * FROM users WHERE id = \nSELECT ";
+        backend
+            .documents
+            .write()
+            .await
+            .insert(uri.clone(), text.to_string());
+        open(backend, text).await;
+
+        let idle = complete(backend, space_triggered(&uri, Position::new(1, 32)));
+        assert!(matches!(idle, Ok(None)), "{:?}", idle);
+
+        // Same trigger in a selector list still reaches get_fields
+        let fields = completion_items(complete(
+            backend,
+            space_triggered(&uri, Position::new(2, 7)),
+        ));
+        assert!(fields.iter().any(|item| item.label.starts_with("id |")));
+
+        let context = space_triggered(&uri, Position::new(0, 0)).context;
+        for (line, character, idle) in [
+            ("WHERE name = 'だ🚀' AND id = ", 28, true),
+            ("WHERE name = 'だ🚀 = ", 20, false),
+            ("WHERE name = 'だ🚀' AND ", 23, false),
+        ] {
+            assert_eq!(
+                backend.is_idle_space_trigger(line, &Position::new(0, character), context.as_ref()),
+                idle,
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn value_template_column_is_resolved_once() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        let text = "USE ks;\nSELECT * FROM users WHERE created_at = ";
+        let line = "SELECT * FROM users WHERE created_at = ";
+        let position = Position::new(1, 39);
+        open(backend, text).await;
+
+        // Offline mode never looks the column up
+        assert!(
+            backend
+                .should_suggest_value_template(line, &position)
+                .await
+                .is_none()
+        );
+
+        go_online(backend);
+        let column = backend
+            .should_suggest_value_template(line, &position)
+            .await
+            .unwrap();
+        assert_eq!(column.column_name, "created_at");
+
+        let items = completion_items(backend.handle_value_template_completion(&column));
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "?",
+                ":created_at",
+                "currentTimestamp()",
+                "toTimestamp(now())"
+            ]
+        );
+
+        assert!(
+            backend
+                .should_suggest_value_template("SELECT * FROM users WHERE missing = ", &position)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn truncate_offers_table_keyword_and_tables() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        set_tables(
+            backend,
+            &[("ks", "users"), ("ks", "events"), ("other", "stats")],
+        )
+        .await;
+        open(backend, "USE ks;\nTRUNCATE ").await;
+
+        let at_end = |line: &str| Position::new(1, line.len() as u32);
+        let labels = |items: Vec<CompletionItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.label).collect()
+        };
+
+        let line = "TRUNCATE ";
+        assert!(backend.should_suggest_truncate(line, &at_end(line)));
+        assert_eq!(
+            labels(completion_items(
+                backend
+                    .handle_truncate_completion(line, &at_end(line))
+                    .await
+            )),
+            ["TABLE", "table", "users", "events", "other.stats"]
+        );
+
+        // Prefix filters keywords && tables alike
+        for (line, expected) in [
+            ("TRUNCATE us", vec!["users"]),
+            ("truncate ta", vec!["TABLE", "table"]),
+            ("TRUNCATE st", vec!["other.stats"]),
+        ] {
+            assert!(
+                backend.should_suggest_truncate(line, &at_end(line)),
+                "{}",
+                line
+            );
+            assert_eq!(
+                labels(completion_items(
+                    backend
+                        .handle_truncate_completion(line, &at_end(line))
+                        .await
+                )),
+                expected,
+                "{}",
+                line
+            );
+        }
+
+        for line in ["TRUNCATE TABLE ", "TRUNCATE TABLE ev"] {
+            assert!(
+                !backend.should_suggest_truncate(line, &at_end(line)),
+                "{}",
+                line
+            );
+            assert!(
+                backend.should_suggest_truncate_tables(line, &at_end(line)),
+                "{}",
+                line
+            );
+        }
+
+        let line = "TRUNCATE TABLE ks.";
+        assert!(!backend.should_suggest_truncate(line, &at_end(line)));
+        assert!(!backend.should_suggest_truncate_tables(line, &at_end(line)));
+        assert!(backend.should_suggest_dot_scoped_tables(line, &at_end(line)));
+        assert_eq!(
+            labels(completion_items(
+                backend.get_dot_scoped_tables(line, &at_end(line)).await
+            )),
+            ["users", "events"]
+        );
+    }
+
+    #[tokio::test]
+    async fn case_sensitive_names_are_inserted_quoted() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        {
+            let mut snapshot = backend.schema_snapshot.write().await;
+            let snapshot = snapshot.as_mut().unwrap();
+            snapshot.keyspaces.push("CaseSensitive".to_string());
+            snapshot
+                .columns
+                .push(column("users", "firstName", "text", "regular"));
+        }
+
+        let edit = |item: &CompletionItem| match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => edit.new_text.clone(),
+            _ => panic!("{} has no text edit", item.label),
+        };
+
+        let line = "DROP KEYSPACE Ca";
+        let position = Position::new(0, line.len() as u32);
+        assert!(backend.should_suggest_drop_keyspaces(line, &position));
+        let items = completion_items(
+            backend
+                .handle_drop_keyspace_completions(line, &position)
+                .await,
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "CaseSensitive");
+        assert_eq!(edit(&items[0]), "\"CaseSensitive\";");
+
+        // Inside of quotes the name is inserted as is
+        let line = "USE \"Ca";
+        let position = Position::new(0, line.len() as u32);
+        let items = completion_items(
+            backend
+                .handle_in_string_keyspace_completion(line, &position)
+                .await,
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].insert_text.as_deref(), Some("CaseSensitive\";"));
+
+        let text = "USE ks;\nSELECT ";
+        let position = Position::new(1, 7);
+        open(backend, text).await;
+
+        let items = completion_items(backend.get_fields(text, "SELECT ", &position).await);
+        let first_name = items
+            .iter()
+            .find(|item| item.label.starts_with("firstName |"))
+            .unwrap();
+        assert_eq!(first_name.insert_text.as_deref(), Some("\"firstName\""));
+        let name = items
+            .iter()
+            .find(|item| item.label.starts_with("name |"))
+            .unwrap();
+        assert_eq!(name.insert_text.as_deref(), Some("name"));
+    }
+
+    #[tokio::test]
+    async fn alter_keyspace_inserts_the_bare_name() {
+        let service = service_with_schema(false);
+        let backend = service.inner();
+        backend
+            .schema_snapshot
+            .write()
+            .await
+            .as_mut()
+            .unwrap()
+            .keyspaces = vec!["my_keyspace".to_string(), "other".to_string()];
+        go_online(backend);
+
+        let uri = Url::parse("file:///tmp/test.cql").unwrap();
+        let text = "ALTER KEYSPACE my\nALTER KEYSPACE IF EXISTS \nUSE \"my";
+        backend
+            .documents
+            .write()
+            .await
+            .insert(uri.clone(), text.to_string());
+        open(backend, text).await;
+
+        let edits = |position: Position| -> Vec<(String, Range, String)> {
+            completion_items(complete(backend, space_triggered(&uri, position)))
+                .into_iter()
+                .map(|item| match item.text_edit {
+                    Some(CompletionTextEdit::Edit(edit)) => (item.label, edit.range, edit.new_text),
+                    _ => (
+                        item.label,
+                        Range::default(),
+                        item.insert_text.unwrap_or_default(),
+                    ),
+                })
+                .collect()
+        };
+
+        assert!(backend.should_suggest_alter_keyspaces("ALTER KEYSPACE my", &Position::new(0, 17)));
+        assert!(!backend.should_suggest_keyspaces("ALTER KEYSPACE my", &Position::new(0, 17)));
+        assert_eq!(
+            edits(Position::new(0, 17)),
+            [(
+                "my_keyspace".to_string(),
+                Range::new(Position::new(0, 15), Position::new(0, 17)),
+                "my_keyspace".to_string()
+            )]
+        );
+
+        let labels: Vec<String> = edits(Position::new(1, 25))
+            .into_iter()
+            .map(|(label, _, new_text)| {
+                assert_eq!(label, new_text);
+                label
+            })
+            .collect();
+        assert_eq!(labels, ["my_keyspace", "other"]);
+
+        // USE keeps completing inside of the quotes
+        assert!(!backend.should_suggest_alter_keyspaces("USE \"my", &Position::new(2, 7)));
+        assert_eq!(
+            edits(Position::new(2, 7)),
+            [(
+                "my_keyspace".to_string(),
+                Range::default(),
+                "my_keyspace\";".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn select_from_edit_ends_at_the_utf16_line_end() {
+        let service = service_with_schema(true);
+        let backend = service.inner();
+
+        // 名前 && 🚀 take 10 bytes but 4 UTF-16 units
+        let line = "SELECT \"名前🚀\", ";
+        let position = Position::new(1, 15);
+        open(backend, &format!("USE ks;\n{}", line)).await;
+
+        let items = completion_items(backend.get_fields("", line, &position).await);
+        let id = items
+            .iter()
+            .find(|item| item.label.starts_with("id |"))
+            .unwrap();
+
+        assert_eq!(id.kind, Some(CompletionItemKind::SNIPPET));
+        assert!(id.insert_text.is_none());
+        match &id.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => {
+                assert_eq!(edit.range, Range::new(position, position));
+                assert_eq!(edit.new_text, "id, FROM users;");
+            }
+            _ => panic!("expected a text edit, got {:?}", id),
+        }
+
+        // Typed word && the rest of the line are replaced
+        let line = "SELECT na 'だ🚀'";
+        let edit = backend.select_from_edit(
+            &column("users", "name", "text", "regular"),
+            Some("ks"),
+            line,
+            &Position::new(1, 9),
+        );
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 7), Position::new(1, 15))
+        );
+        assert_eq!(edit.new_text, "name, FROM users;");
     }
 }
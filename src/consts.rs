@@ -1,3 +1,4 @@
+use crate::lsp::Backend;
 use once_cell::sync::Lazy;
 use tower_lsp::lsp_types::*;
 
@@ -493,6 +494,43 @@ pub static CQL_TYPES_LWC: Lazy<Vec<String>> = Lazy::new(|| {
     ]
 });
 
+/*
+    Column types accepted by sum && avg aggregates
+*/
+pub static NUMERIC_TYPES_LWC: Lazy<Vec<String>> = Lazy::new(|| {
+    vec![
+        "bigint".to_string(),
+        "counter".to_string(),
+        "decimal".to_string(),
+        "double".to_string(),
+        "float".to_string(),
+        "int".to_string(),
+        "smallint".to_string(),
+        "tinyint".to_string(),
+        "varint".to_string(),
+    ]
+});
+
+/*
+    Object types of DDL statements
+
+    CREATE [object] IF NOT EXISTS ...
+    DROP [object] IF EXISTS ...
+*/
+pub static DDL_OBJECTS_LWC: Lazy<Vec<String>> = Lazy::new(|| {
+    vec![
+        "aggregate".to_string(),
+        "function".to_string(),
+        "index".to_string(),
+        "keyspace".to_string(),
+        "role".to_string(),
+        "table".to_string(),
+        "type".to_string(),
+        "user".to_string(),
+        "view".to_string(),
+    ]
+});
+
 /*
     List of keywords represented as String
 */
@@ -647,7 +685,7 @@ pub static KEYWORDS_STRINGS_LWC: Lazy<Vec<String>> = Lazy::new(|| {
 // XAR-1 2.7k lines of pure KEYWORDS だよ　www
 // XAR-1 2.7k lines of pure KEYWORDS that are working だよ :D
 pub static KEYWORDS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
-    vec![
+    let keywords = vec![
         // USE
         CompletionItem {
             label: "USE".to_string(),
@@ -3365,7 +3403,15 @@ pub static KEYWORDS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
             insert_text_format: Some(InsertTextFormat::SNIPPET),
             ..Default::default()
         },
-    ]
+    ];
+
+    keywords
+        .into_iter()
+        .map(|item| CompletionItem {
+            sort_text: Some(Backend::case_sort_text(&item.label)),
+            ..item
+        })
+        .collect()
 });
 
 // XAR-2 700 lines of pure TYPES だよ www
@@ -4078,6 +4124,515 @@ pub static COMMAND_SEQUENCE: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
     ]
 });
 
+/*
+    GRANT | REVOKE permissions
+
+    Each permission is followed by the ON resource snippet
+    GRANT SELECT ON KEYSPACE ks TO role;
+*/
+pub static PERMISSIONS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let permissions = [
+        ("SELECT", "Read data from tables"),
+        ("MODIFY", "INSERT, UPDATE, DELETE and TRUNCATE"),
+        ("CREATE", "Create keyspaces, tables, functions and roles"),
+        ("ALTER", "Alter keyspaces, tables, functions and roles"),
+        ("DROP", "Drop keyspaces, tables, functions and roles"),
+        ("AUTHORIZE", "GRANT and REVOKE permissions"),
+        ("DESCRIBE", "Describe roles"),
+        ("EXECUTE", "Execute functions"),
+        (
+            "ALL PERMISSIONS",
+            "All permissions applicable to the resource",
+        ),
+    ];
+
+    let mut items = Vec::<CompletionItem>::new();
+
+    for (permission, documentation) in permissions {
+        items.push(CompletionItem {
+            label: permission.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(format!("Upper case {} permission", permission)),
+            documentation: Some(Documentation::String(documentation.to_string())),
+            insert_text: Some(format!(
+                "{} ON ${{1|KEYSPACE,TABLE,ALL KEYSPACES|}} $0",
+                permission
+            )),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        });
+
+        let permission = permission.to_lowercase();
+        items.push(CompletionItem {
+            label: permission.clone(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(format!("Lower case {} permission", permission)),
+            documentation: Some(Documentation::String(documentation.to_string())),
+            insert_text: Some(format!(
+                "{} on ${{1|keyspace,table,all keyspaces|}} $0",
+                permission
+            )),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        });
+    }
+
+    items
+});
+
+/*
+    CREATE/ALTER TABLE ... WITH options
+
+    Suggested after WITH | AND outside of the column definitions
+*/
+pub static TABLE_WITH_OPTIONS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    vec![
+        CompletionItem {
+            label: "compaction".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("compaction table option".to_string()),
+            documentation: Some(Documentation::String("Compaction strategy".to_string())),
+            insert_text: Some(
+                r#"compaction = {'class': '${1:SizeTieredCompactionStrategy}'}"#.to_string(),
+            ),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "compression".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("compression table option".to_string()),
+            documentation: Some(Documentation::String("Compression options".to_string())),
+            insert_text: Some(
+                r#"compression = {'class': '${1:LZ4Compressor}', 'chunk_length_in_kb': ${2:64}}"#
+                    .to_string(),
+            ),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "caching".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("caching table option".to_string()),
+            documentation: Some(Documentation::String("Caching options".to_string())),
+            insert_text: Some(
+                r#"caching = {'keys': '${1:ALL}', 'rows_per_partition': '${2:NONE}'}"#.to_string(),
+            ),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "default_time_to_live".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("default_time_to_live table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Default TTL in seconds, 0 disables TTL".to_string(),
+            )),
+            insert_text: Some(r#"default_time_to_live = ${1:0}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "gc_grace_seconds".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("gc_grace_seconds table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Seconds before tombstones are garbage collected".to_string(),
+            )),
+            insert_text: Some(r#"gc_grace_seconds = ${1:864000}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "bloom_filter_fp_chance".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("bloom_filter_fp_chance table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Bloom filter false positive chance".to_string(),
+            )),
+            insert_text: Some(r#"bloom_filter_fp_chance = ${1:0.01}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "cdc".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("cdc table option".to_string()),
+            documentation: Some(Documentation::String("Change data capture".to_string())),
+            insert_text: Some(r#"cdc = ${1:true}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "comment".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("comment table option".to_string()),
+            documentation: Some(Documentation::String("Table comment".to_string())),
+            insert_text: Some(r#"comment = '${1}'"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "speculative_retry".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("speculative_retry table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Speculative retry policy".to_string(),
+            )),
+            insert_text: Some(r#"speculative_retry = '${1:99PERCENTILE}'"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "memtable_flush_period_in_ms".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("memtable_flush_period_in_ms table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Memtable flush period, 0 disables periodic flush".to_string(),
+            )),
+            insert_text: Some(r#"memtable_flush_period_in_ms = ${1:0}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "min_index_interval".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("min_index_interval table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Minimal gap between index entries".to_string(),
+            )),
+            insert_text: Some(r#"min_index_interval = ${1:128}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "max_index_interval".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("max_index_interval table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Maximal gap between index entries".to_string(),
+            )),
+            insert_text: Some(r#"max_index_interval = ${1:2048}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "crc_check_chance".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("crc_check_chance table option".to_string()),
+            documentation: Some(Documentation::String(
+                "Probability of checksum validation on read".to_string(),
+            )),
+            insert_text: Some(r#"crc_check_chance = ${1:1.0}"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "CLUSTERING ORDER BY".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Upper case CLUSTERING ORDER BY".to_string()),
+            documentation: Some(Documentation::String(
+                "Clustering order of the table".to_string(),
+            )),
+            insert_text: Some(r#"CLUSTERING ORDER BY (${1:column_name} ${2:ASC})"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "clustering order by".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Lower case CLUSTERING ORDER BY".to_string()),
+            documentation: Some(Documentation::String(
+                "Clustering order of the table".to_string(),
+            )),
+            insert_text: Some(r#"clustering order by (${1:column_name} ${2:ASC})"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
+    ]
+});
+
+/*
+    CONSISTENCY | USING CONSISTENCY levels
+*/
+pub static CONSISTENCY_LEVELS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let levels = [
+        (
+            "ANY",
+            "Write succeeds once stored on any node, hinted handoff included",
+        ),
+        ("ONE", "One replica has to respond"),
+        ("TWO", "Two replicas have to respond"),
+        ("THREE", "Three replicas have to respond"),
+        ("QUORUM", "Majority of replicas across all datacenters"),
+        ("ALL", "All replicas have to respond"),
+        (
+            "LOCAL_QUORUM",
+            "Majority of replicas in the local datacenter",
+        ),
+        ("EACH_QUORUM", "Majority of replicas in each datacenter"),
+        (
+            "SERIAL",
+            "Linearizable consistency for lightweight transactions",
+        ),
+        ("LOCAL_SERIAL", "SERIAL limited to the local datacenter"),
+        (
+            "LOCAL_ONE",
+            "One replica in the local datacenter has to respond",
+        ),
+    ];
+
+    let mut items = Vec::<CompletionItem>::new();
+
+    for (level, documentation) in levels {
+        for level in [level.to_string(), level.to_lowercase()] {
+            items.push(CompletionItem {
+                label: level.clone(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some("Consistency level".to_string()),
+                documentation: Some(Documentation::String(documentation.to_string())),
+                insert_text: Some(level),
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+});
+
+/*
+    INSERT ... USING | UPDATE ... USING |
+    USING TTL 60 AND |
+*/
+pub static USING_OPTIONS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let options = [
+        (
+            "TTL",
+            "${1:86400}",
+            "Time to live of written values in seconds",
+        ),
+        (
+            "TIMESTAMP",
+            "${1:timestamp}",
+            "Write timestamp in microseconds since epoch",
+        ),
+        (
+            "CONSISTENCY",
+            "${1:QUORUM}",
+            "Consistency level of the statement",
+        ),
+    ];
+
+    let mut items = Vec::<CompletionItem>::new();
+
+    for (option, value, documentation) in options {
+        for option in [option.to_string(), option.to_lowercase()] {
+            items.push(CompletionItem {
+                label: option.clone(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some(format!("USING {}", option)),
+                documentation: Some(Documentation::String(documentation.to_string())),
+                insert_text: Some(format!("{} {}$0", option, value)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+});
+
+/*
+    USING TTL |
+    USING TIMESTAMP 1700000000000000 AND TTL |
+*/
+pub static USING_TTL_VALUES: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let durations = [
+        ("60", "1 minute"),
+        ("3600", "1 hour"),
+        ("86400", "1 day"),
+        ("604800", "1 week"),
+        ("2592000", "30 days"),
+        ("0", "No expiration"),
+    ];
+
+    durations
+        .iter()
+        .enumerate()
+        .map(|(i, (seconds, duration))| CompletionItem {
+            label: format!("{} -- {}", seconds, duration),
+            kind: Some(CompletionItemKind::VALUE),
+            detail: Some("Time to live in seconds".to_string()),
+            filter_text: Some(seconds.to_string()),
+            // Keeps durations ordered instead of sorted as strings
+            sort_text: Some(i.to_string()),
+            insert_text: Some(seconds.to_string()),
+            ..Default::default()
+        })
+        .collect()
+});
+
+/*
+    USING TIMESTAMP |
+
+    Only literal microseconds are accepted,
+    now() && other functions are rejected by the cluster
+*/
+pub static USING_TIMESTAMP_VALUES: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    vec![CompletionItem {
+        label: "microseconds".to_string(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some("Write timestamp in microseconds since epoch".to_string()),
+        documentation: Some(Documentation::String(
+            "Has to be a literal e.g 1700000000000000, now() based timestamps aren't allowed here"
+                .to_string(),
+        )),
+        insert_text: Some(r#"${1:microseconds}$0"#.to_string()),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }]
+});
+
+/*
+    beg| -> BEGIN [UNLOGGED|COUNTER] BATCH
+*/
+pub static BATCH_BEGIN_VARIANTS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let variants = [
+        ("BATCH", "Logged batch, applied atomically"),
+        ("UNLOGGED BATCH", "Unlogged batch, no batch log is written"),
+        ("COUNTER BATCH", "Batch of counter updates only"),
+    ];
+
+    variants
+        .iter()
+        .flat_map(|(variant, detail)| {
+            let upper = format!("BEGIN {}", variant);
+            let lower = upper.to_lowercase();
+
+            [upper, lower].map(|label| CompletionItem {
+                label: label.clone(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some(detail.to_string()),
+                filter_text: Some(label.clone()),
+                insert_text: Some(format!("{}\n\t$0", label)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            })
+        })
+        .collect()
+});
+
+/*
+    BEGIN BATCH
+        INSERT ...;
+        ap| -> APPLY BATCH;
+*/
+pub static BATCH_APPLY: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    vec![
+        CompletionItem {
+            label: "APPLY BATCH;".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Closes the current batch".to_string()),
+            insert_text: Some("APPLY BATCH;".to_string()),
+            ..Default::default()
+        },
+        CompletionItem {
+            label: "apply batch;".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Closes the current batch".to_string()),
+            insert_text: Some("apply batch;".to_string()),
+            ..Default::default()
+        },
+    ]
+});
+
+/*
+    CREATE CUSTOM INDEX ... USING '|'
+*/
+pub const INDEX_CLASSES: &[(&str, &str)] = &[
+    ("StorageAttachedIndex", "Storage-attached index (SAI)"),
+    ("sai", "Storage-attached index (SAI), short name"),
+    (
+        "org.apache.cassandra.index.sasi.SASIIndex",
+        "SSTable-attached secondary index (SASI)",
+    ),
+    ("vector_index", "ScyllaDB vector search index"),
+];
+
+/*
+    CREATE CUSTOM INDEX ... USING 'sai' | -> WITH OPTIONS = {|}
+*/
+pub static INDEX_OPTIONS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    ["WITH OPTIONS", "with options"]
+        .map(|label| CompletionItem {
+            label: label.to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some("Custom index options map".to_string()),
+            insert_text: Some(format!("{} = {{$0}}", label)),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        })
+        .to_vec()
+});
+
+/*
+    DESCRIBE | -> KEYSPACES; TABLE ... SCHEMA;
+
+    Listing variants are terminated, the rest wait for a name
+*/
+pub static DESCRIBE_OBJECTS: Lazy<Vec<CompletionItem>> = Lazy::new(|| {
+    let objects = [
+        ("KEYSPACES", "Names of all keyspaces", true),
+        ("KEYSPACE", "Keyspace and every object in it", false),
+        ("TABLES", "Names of all tables", true),
+        ("TABLE", "CREATE TABLE statement of a table", false),
+        ("TYPES", "Names of all user-defined types", true),
+        (
+            "TYPE",
+            "CREATE TYPE statement of a user-defined type",
+            false,
+        ),
+        ("FUNCTIONS", "Names of all user-defined functions", true),
+        ("FUNCTION", "CREATE FUNCTION statement of a function", false),
+        ("AGGREGATES", "Names of all user-defined aggregates", true),
+        (
+            "AGGREGATE",
+            "CREATE AGGREGATE statement of an aggregate",
+            false,
+        ),
+        (
+            "MATERIALIZED VIEW",
+            "CREATE MATERIALIZED VIEW statement of a view",
+            false,
+        ),
+        ("INDEX", "CREATE INDEX statement of an index", false),
+        ("SCHEMA", "Schema of every non-system keyspace", true),
+        (
+            "FULL SCHEMA",
+            "Schema of every keyspace, system ones included",
+            true,
+        ),
+        ("CLUSTER", "Cluster name, partitioner and snitch", true),
+    ];
+
+    objects
+        .iter()
+        .flat_map(|(object, detail, terminated)| {
+            [object.to_string(), object.to_lowercase()].map(|label| CompletionItem {
+                label: label.clone(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some(detail.to_string()),
+                insert_text: Some(match terminated {
+                    true => format!("{};", label),
+                    false => format!("{} $0", label),
+                }),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            })
+        })
+        .collect()
+});
+
 // Advanced Completions
 //
 // CREATE -> CREATE [TABLE|KEYSPACE|MATERIALIZED VIEW|...]
@@ -1,13 +1,23 @@
-use futures::stream::StreamExt;
+use futures::stream::{Stream, StreamExt};
+use openssl::ssl::{SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
 use scylla::{
     DeserializeRow,
-    client::session_builder::SessionBuilder,
+    client::{session::Session, session_builder::SessionBuilder},
+    errors::{
+        DbError, DeserializationError, ExecutionError, IntoRowsResultError, MaybeFirstRowError,
+        NextRowError, PagerExecutionError, PrepareError, RequestAttemptError, RowsError,
+        TypeCheckError,
+    },
+    serialize::row::SerializeRow,
     statement::{Statement, prepared::PreparedStatement},
+    value::{CqlValue, Row},
 };
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::path::Path;
 use std::time::Duration;
 
-use log::info;
+use log::{error, info};
 
 /*
     cqlsh.rs
@@ -17,7 +27,7 @@ use log::info;
     databases, including ScyllaDB and Apache Cassandra.
 */
 
-#[derive(DeserializeRow)]
+#[derive(DeserializeRow, Debug, Clone, Serialize, Deserialize)]
 pub struct Table {
     pub keyspace_name: String,
     pub table_name: String,
@@ -36,20 +46,22 @@ pub struct KeySpace {
     pub replication: std::collections::HashMap<String, String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Column {
     pub keyspace_name: String,
     pub table_name: String,
     pub column_name: String,
     pub column_type: String,
+    // partition_key | clustering | regular | static
+    pub kind: String,
 }
 
 impl fmt::Display for Column {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Column [keyspace: {}, table: {}, column: {}, type: {}]",
-            self.keyspace_name, self.table_name, self.column_name, self.column_type
+            "Column [keyspace: {}, table: {}, column: {}, type: {}, kind: {}]",
+            self.keyspace_name, self.table_name, self.column_name, self.column_type, self.kind
         )
     }
 }
@@ -96,44 +108,570 @@ pub struct View {
     pub view_name: String,
 }
 
+/*
+    WITH options of system_schema.tables
+
+    Columns differ between Cassandra && ScyllaDB versions,
+    options missing in the schema are left as None
+*/
+#[derive(Debug, Default)]
+pub struct TableOptions {
+    pub keyspace_name: String,
+    pub table_name: String,
+    pub comment: Option<String>,
+    pub default_time_to_live: Option<i32>,
+    pub gc_grace_seconds: Option<i32>,
+    pub bloom_filter_fp_chance: Option<f64>,
+    pub crc_check_chance: Option<f64>,
+    pub speculative_retry: Option<String>,
+    pub memtable_flush_period_in_ms: Option<i32>,
+    pub min_index_interval: Option<i32>,
+    pub max_index_interval: Option<i32>,
+    pub cdc: Option<bool>,
+    pub caching: Option<std::collections::HashMap<String, String>>,
+    pub compaction: Option<std::collections::HashMap<String, String>>,
+    pub compression: Option<std::collections::HashMap<String, String>>,
+}
+
+#[derive(Debug)]
+pub struct TlsSettings {
+    pub ca_path: String,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+impl TlsSettings {
+    /*
+        Returns None if TLS is disabled,
+        empty cert/key paths are treated as not provided
+    */
+    pub fn from_env(
+        enabled: &str,
+        ca_path: &str,
+        client_cert: &str,
+        client_key: &str,
+    ) -> Option<Self> {
+        if enabled != "true" {
+            return None;
+        }
+
+        let non_empty = |value: &str| (!value.is_empty()).then(|| String::from(value));
+
+        Some(Self {
+            ca_path: String::from(ca_path),
+            client_cert: non_empty(client_cert),
+            client_key: non_empty(client_key),
+        })
+    }
+}
+
+/*
+    Database flavor completions are tailored for,
+    Scylla only syntax (BYPASS CACHE, USING TIMEOUT) is hidden otherwise
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Scylla,
+    Cassandra,
+    Hcd,
+}
+
+impl Dialect {
+    pub fn from_env(dialect: &str) -> Self {
+        match dialect.to_lowercase().as_str() {
+            "scylla" => Self::Scylla,
+            "hcd" => Self::Hcd,
+            "cassandra" => Self::Cassandra,
+            _ => {
+                info!(
+                    "Invalid dialect {:?}. Setting dialect to default(cassandra)",
+                    dialect
+                );
+                Self::Cassandra
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CqlSettings {
-    pub url: String,
+    pub urls: Vec<String>,
     pub pswd: String,
     pub user: String,
+    pub tls: Option<TlsSettings>,
+    pub connection_timeout_secs: u64,
+    pub enable_exec: bool,
+    pub allow_mutations: bool,
+    pub db_retries: u32,
+    pub validate_on_save: bool,
+    pub live_validation: bool,
+    // SELECT field completions insert the whole FROM clause
+    pub context_based_select: bool,
+    // Cluster is never contacted, only static completions are served
+    pub offline: bool,
+    // Schema is cached on disk && served until the cluster answers
+    pub schema_snapshot: bool,
+    // DROP completions insert keyspace.name even for the USE keyspace
+    pub qualified_drop: bool,
+    // Statement templates are suggested on empty lines
+    pub command_snippets: bool,
+    // Columns read per completion query, 0 reads all of them
+    pub max_completion_items: usize,
+    // Completion queries slower than this yield no items, 0 waits forever
+    pub query_timeout_ms: u64,
+    pub dialect: Dialect,
 }
 
 impl CqlSettings {
     pub fn new() -> Self {
         Self {
-            url: String::from("127.0.0.1:9042"),
+            urls: vec![String::from("127.0.0.1:9042")],
             pswd: String::from("cassandra"),
             user: String::from("cassandra"),
+            tls: None,
+            connection_timeout_secs: 3,
+            enable_exec: false,
+            allow_mutations: false,
+            db_retries: 2,
+            validate_on_save: false,
+            live_validation: false,
+            context_based_select: false,
+            offline: false,
+            schema_snapshot: false,
+            qualified_drop: false,
+            command_snippets: false,
+            max_completion_items: 5000,
+            query_timeout_ms: 2000,
+            dialect: Dialect::Cassandra,
         }
     }
 
-    pub fn from_env(url: &str, pswd: &str, user: &str) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_env(
+        url: &str,
+        pswd: &str,
+        user: &str,
+        timeout: &str,
+        tls: Option<TlsSettings>,
+        enable_exec: &str,
+        allow_mutations: &str,
+        db_retries: &str,
+        validate_on_save: &str,
+        live_validation: &str,
+        context_based_select: &str,
+        offline: &str,
+        schema_snapshot: &str,
+        qualified_drop: &str,
+        command_snippets: &str,
+        max_completion_items: &str,
+        query_timeout_ms: &str,
+        dialect: &str,
+    ) -> Self {
         Self {
-            url: String::from(url),
+            urls: Self::parse_urls(url),
             pswd: String::from(pswd),
             user: String::from(user),
+            tls,
+            connection_timeout_secs: timeout.parse().unwrap_or_else(|_| {
+                info!(
+                    "Invalid db timeout {:?}. Setting timeout to default(3)",
+                    timeout
+                );
+                3
+            }),
+            enable_exec: enable_exec == "true",
+            allow_mutations: allow_mutations == "true",
+            db_retries: db_retries.parse().unwrap_or_else(|_| {
+                info!(
+                    "Invalid db retries {:?}. Setting retries to default(2)",
+                    db_retries
+                );
+                2
+            }),
+            validate_on_save: validate_on_save == "true",
+            live_validation: live_validation == "true",
+            context_based_select: context_based_select == "true",
+            offline: offline == "true",
+            schema_snapshot: schema_snapshot == "true",
+            qualified_drop: qualified_drop == "true",
+            command_snippets: command_snippets == "true",
+            max_completion_items: max_completion_items.parse().unwrap_or_else(|_| {
+                info!(
+                    "Invalid max completion items {:?}. Setting max completion items to default(5000)",
+                    max_completion_items
+                );
+                5000
+            }),
+            query_timeout_ms: query_timeout_ms.parse().unwrap_or_else(|_| {
+                info!(
+                    "Invalid query timeout {:?}. Setting query timeout to default(2000)",
+                    query_timeout_ms
+                );
+                2000
+            }),
+            dialect: Dialect::from_env(dialect),
         }
     }
+
+    /*
+        10.0.0.1:9042,10.0.0.2:9042 -> [10.0.0.1:9042, 10.0.0.2:9042]
+
+        Every node is used as a contact point, session is opened
+        as long as one of them is reachable. Nodes are tried in the
+        listed order, the rest of the cluster is discovered from the
+        first node that answers
+    */
+    pub fn parse_urls(url: &str) -> Vec<String> {
+        let urls: Vec<String> = url
+            .split(',')
+            .map(|node| node.trim())
+            .filter(|node| !node.is_empty())
+            .map(String::from)
+            .collect();
+
+        if urls.is_empty() {
+            info!(
+                "Invalid db url {:?}. Setting url to default(127.0.0.1:9042)",
+                url
+            );
+            return vec![String::from("127.0.0.1:9042")];
+        }
+
+        urls
+    }
+
+    // Contact points joined for messages
+    pub fn url(&self) -> String {
+        self.urls.join(",")
+    }
+}
+
+/*
+    Error of cqlsh queries
+
+    Connection  - cluster is unreachable, timed out or TLS couldn't be set up
+    Auth        - credentials were rejected or user lacks permissions
+    Query       - statement was rejected by the cluster
+    Deserialize - rows don't match the expected types
+    NotFound    - keyspace, table or type doesn't exist
+    Offline     - CQL_LSP_OFFLINE is set, cluster isn't queried
+*/
+#[derive(Debug, Clone)]
+pub enum CqlError {
+    Connection(String),
+    Auth(String),
+    Query(String),
+    Deserialize(String),
+    NotFound(String),
+    Offline,
+}
+
+impl fmt::Display for CqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CqlError::Connection(message) => write!(f, "{}", message),
+            CqlError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            CqlError::Query(message) => write!(f, "Query failed: {}", message),
+            CqlError::Deserialize(message) => write!(f, "Unexpected query result: {}", message),
+            CqlError::NotFound(message) => write!(f, "{}", message),
+            CqlError::Offline => write!(f, "Offline mode is enabled, cluster isn't queried"),
+        }
+    }
+}
+
+impl std::error::Error for CqlError {}
+
+impl CqlError {
+    /*
+        Error returned by the cluster itself
+
+        unconfigured table t, Keyspace ks does not exist -> NotFound
+    */
+    fn from_db_error(db_error: &DbError, message: String) -> Self {
+        let lw_message = message.to_lowercase();
+
+        match db_error {
+            DbError::AuthenticationError | DbError::Unauthorized => CqlError::Auth(message),
+            DbError::Invalid
+                if lw_message.contains("does not exist")
+                    || lw_message.contains("doesn't exist")
+                    || lw_message.contains("unconfigured table") =>
+            {
+                CqlError::NotFound(message)
+            }
+            _ => CqlError::Query(message),
+        }
+    }
+}
+
+impl From<ExecutionError> for CqlError {
+    fn from(e: ExecutionError) -> Self {
+        match e {
+            ExecutionError::LastAttemptError(RequestAttemptError::DbError(db_error, message)) => {
+                CqlError::from_db_error(&db_error, message)
+            }
+            ExecutionError::PrepareError(e) => e.into(),
+            ExecutionError::ConnectionPoolError(_) | ExecutionError::RequestTimeout(_) => {
+                CqlError::Connection(e.to_string())
+            }
+            _ => CqlError::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<PrepareError> for CqlError {
+    fn from(e: PrepareError) -> Self {
+        match e {
+            PrepareError::AllAttemptsFailed {
+                first_attempt: RequestAttemptError::DbError(db_error, message),
+            } => CqlError::from_db_error(&db_error, message),
+            PrepareError::ConnectionPoolError(_) => CqlError::Connection(e.to_string()),
+            _ => CqlError::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<PagerExecutionError> for CqlError {
+    fn from(e: PagerExecutionError) -> Self {
+        match e {
+            PagerExecutionError::PrepareError(e) => e.into(),
+            _ => CqlError::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<NextRowError> for CqlError {
+    fn from(e: NextRowError) -> Self {
+        match e {
+            NextRowError::RowDeserializationError(_) => CqlError::Deserialize(e.to_string()),
+            _ => CqlError::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<openssl::error::ErrorStack> for CqlError {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        CqlError::Connection(format!("TLS setup failed: {}", e))
+    }
+}
+
+// Rows didn't match the types they're read as
+macro_rules! deserialize_error {
+    ($($error:ty),*) => {
+        $(
+            impl From<$error> for CqlError {
+                fn from(e: $error) -> Self {
+                    CqlError::Deserialize(e.to_string())
+                }
+            }
+        )*
+    };
+}
+
+deserialize_error!(
+    IntoRowsResultError,
+    RowsError,
+    MaybeFirstRowError,
+    TypeCheckError,
+    DeserializationError
+);
+
+/*
+    Builds OpenSSL context from TlsSettings
+
+    Missing CA file is an error, we never fall back to plaintext
+    when TLS was requested
+*/
+fn create_tls_context(tls: &TlsSettings) -> Result<SslContext, CqlError> {
+    if tls.ca_path.is_empty() || !Path::new(&tls.ca_path).is_file() {
+        error!("TLS is enabled but CA file wasn't found: {:?}", tls.ca_path);
+        return Err(CqlError::Connection(format!(
+            "TLS CA file not found: {:?}",
+            tls.ca_path
+        )));
+    }
+
+    let mut builder = SslContextBuilder::new(SslMethod::tls())?;
+    builder.set_ca_file(&tls.ca_path)?;
+    builder.set_verify(SslVerifyMode::PEER);
+
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            builder.set_certificate_file(cert, SslFiletype::PEM)?;
+            builder.set_private_key_file(key, SslFiletype::PEM)?;
+            builder.check_private_key()?;
+        }
+        (None, None) => {}
+        _ => {
+            error!("TLS client cert and key must be provided together");
+            return Err(CqlError::Connection(
+                "TLS client cert and key must be provided together".to_string(),
+            ));
+        }
+    }
+
+    Ok(builder.build())
+}
+
+/*
+    Opens session to the cluster using CqlSettings
+
+    Failed connections are retried db_retries times with
+    exponential backoff (200ms, 400ms, ...) before giving up.
+    No session is opened in offline mode
+*/
+async fn create_session(config: &CqlSettings) -> Result<Session, CqlError> {
+    if config.offline {
+        return Err(CqlError::Offline);
+    }
+
+    let timeout = Duration::from_secs(config.connection_timeout_secs);
+    let mut builder = SessionBuilder::new()
+        .known_nodes(&config.urls)
+        .user(&config.user, &config.pswd)
+        .connection_timeout(timeout);
+
+    if let Some(tls) = &config.tls {
+        builder = builder.tls_context(Some(create_tls_context(tls)?));
+    }
+
+    let connection_timeout = || {
+        format!(
+            "Connection to {} timed out after {}s",
+            config.url(),
+            config.connection_timeout_secs
+        )
+    };
+
+    let mut attempt = 0;
+
+    loop {
+        /*
+            connection_timeout only covers opening the connection,
+            initial metadata fetch is bounded by the same timeout
+        */
+        let (reason, timed_out) = match tokio::time::timeout(timeout, builder.build()).await {
+            Ok(Ok(session)) => return Ok(session),
+            Ok(Err(e)) => {
+                let reason = e.to_string();
+                let timed_out = reason.to_lowercase().contains("timeout");
+                (reason, timed_out)
+            }
+            Err(_) => (connection_timeout(), true),
+        };
+
+        error!(
+            "Failed to connect to {} (attempt {}/{}): {}",
+            config.url(),
+            attempt + 1,
+            config.db_retries + 1,
+            reason
+        );
+
+        // Rejected credentials won't be accepted on retry
+        if reason.to_lowercase().contains("authentication") {
+            return Err(CqlError::Auth(reason));
+        }
+
+        if attempt >= config.db_retries {
+            if timed_out {
+                return Err(CqlError::Connection(connection_timeout()));
+            }
+            return Err(CqlError::Connection(format!(
+                "Failed to connect to {} after {} attempts: {}",
+                config.url(),
+                attempt + 1,
+                reason
+            )));
+        }
+
+        tokio::time::sleep(Duration::from_millis(200 * 2u64.pow(attempt))).await;
+        attempt += 1;
+    }
+}
+
+/*
+    Executes user provided statement && renders result as a text table
+
+    col_a | col_b
+    ------+------
+    1     | foo
+
+    (1 rows)
+*/
+pub async fn execute_statement(config: &CqlSettings, statement: &str) -> Result<String, CqlError> {
+    let session = create_session(config).await?;
+
+    let result = session.query_unpaged(statement, &[]).await?;
+
+    if !result.is_rows() {
+        return Ok(String::from("Statement executed successfully"));
+    }
+
+    let result_rows = result.into_rows_result()?;
+
+    let header: Vec<String> = result_rows
+        .column_specs()
+        .iter()
+        .map(|spec| spec.name().to_string())
+        .collect();
+
+    let mut rows = Vec::<Vec<String>>::new();
+    for row in result_rows.rows::<Row>()? {
+        let row = row?;
+        rows.push(
+            row.columns
+                .iter()
+                .map(|value| match value {
+                    Some(value) => value.to_string(),
+                    None => String::from("null"),
+                })
+                .collect(),
+        );
+    }
+
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in &rows {
+        for (i, value) in row.iter().enumerate() {
+            widths[i] = widths[i].max(value.chars().count());
+        }
+    }
+
+    let render = |values: &[String]| -> String {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("{:<width$}", value, width = widths[i]))
+            .collect::<Vec<String>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut output = Vec::<String>::new();
+    output.push(render(&header));
+    output.push(
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<String>>()
+            .join("-+-"),
+    );
+    for row in &rows {
+        output.push(render(row));
+    }
+    output.push(String::new());
+    output.push(format!("({} rows)", rows.len()));
+
+    Ok(output.join("\n"))
 }
 
 /*
     Queries all keyspaces from system_schema
 */
-pub async fn query_keyspaces(
-    config: &CqlSettings,
-) -> Result<Vec<KeySpace>, Box<dyn std::error::Error>> {
+pub async fn query_keyspaces(config: &CqlSettings) -> Result<Vec<KeySpace>, CqlError> {
     info!("Start transaction");
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+    let session = create_session(config).await?;
 
     let select_statement: Statement = Statement::new("SELECT * FROM system_schema.keyspaces;");
     let statement: PreparedStatement = session.prepare(select_statement).await?;
@@ -156,52 +694,25 @@ pub async fn query_keyspaces(
     Ok(items)
 }
 
-pub async fn query_g_fields(
-    config: &CqlSettings,
-) -> Result<Vec<Column>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
-    let mut items = Vec::<Column>::new();
-
-    let tables = query_g_tables(config).await?;
-
-    for table in tables {
-        let query = format!(
-            "SELECT column_name, type  FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';",
-            table.keyspace_name, table.table_name
-        );
-
-        let result_rows = session
-            .query_unpaged(query, &[])
-            .await?
-            .into_rows_result()?;
-
-        for row in result_rows.rows::<(String, String)>()? {
-            let column = row?;
-            info!("Found field: {}", column.0);
-            items.push(Column {
-                column_name: column.0,
-                keyspace_name: table.keyspace_name.clone(),
-                table_name: table.table_name.clone(),
-                column_type: column.1,
-            });
-        }
-    }
+/*
+    Columns of every table, system_schema.columns is read page by page
 
-    Ok(items)
+    Rows come ordered by keyspace, table && column name, same as
+    querying it table by table. Reading stops once limit columns
+    were collected, 0 reads all of them
+*/
+pub async fn query_g_fields(config: &CqlSettings, limit: usize) -> Result<Vec<Column>, CqlError> {
+    query_columns_paged(
+        config,
+        "SELECT keyspace_name, table_name, column_name, type, kind FROM system_schema.columns;",
+        (),
+        limit,
+    )
+    .await
 }
 
-pub async fn check_connection(config: &CqlSettings) -> Result<bool, Box<dyn std::error::Error>> {
-    _ = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn check_connection(config: &CqlSettings) -> Result<bool, CqlError> {
+    _ = create_session(config).await?;
 
     Ok(true)
 }
@@ -209,13 +720,8 @@ pub async fn check_connection(config: &CqlSettings) -> Result<bool, Box<dyn std:
 pub async fn query_keyspace_scoped_tables(
     config: &CqlSettings,
     keyspace: &str,
-) -> Result<Vec<Table>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+) -> Result<Vec<Table>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!(
         "SELECT keyspace_name, table_name FROM system_schema.tables WHERE keyspace_name = '{keyspace}';"
@@ -235,9 +741,7 @@ pub async fn query_keyspace_scoped_tables(
     Ok(items)
 }
 
-pub async fn query_g_tables(
-    config: &CqlSettings,
-) -> Result<Vec<Table>, Box<dyn std::error::Error>> {
+pub async fn query_g_tables(config: &CqlSettings) -> Result<Vec<Table>, CqlError> {
     let keyspaces = query_keyspaces(&config).await?;
     let mut items = Vec::<Table>::new();
 
@@ -252,52 +756,64 @@ pub async fn query_g_tables(
 pub async fn query_keyspace_scoped_fields(
     config: &CqlSettings,
     keyspace: &str,
-) -> Result<Vec<Column>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+    limit: usize,
+) -> Result<Vec<Column>, CqlError> {
+    query_columns_paged(
+        config,
+        "SELECT keyspace_name, table_name, column_name, type, kind FROM system_schema.columns WHERE keyspace_name = ?;",
+        (keyspace,),
+        limit,
+    )
+    .await
+}
 
-    // SELECT table_name FROM system_schema.tables WHERE keyspace_name = '{}';
-    // Sshort row_result query instead of using query_g_tables()
-    // Ccause query_g_tables() returns not just table names, but a Ve<Tables> insteads
-    let select_tables_query =
-        format!("SELECT table_name FROM system_schema.tables WHERE keyspace_name = '{keyspace}';");
+// Rows of system_schema.columns fetched per page
+const COLUMNS_PAGE_SIZE: i32 = 500;
 
-    let result_rows = session
-        .query_unpaged(select_tables_query, &[])
+async fn query_columns_paged(
+    config: &CqlSettings,
+    query: &str,
+    values: impl SerializeRow,
+    limit: usize,
+) -> Result<Vec<Column>, CqlError> {
+    let session = create_session(config).await?;
+
+    let mut statement = Statement::new(query);
+    statement.set_page_size(COLUMNS_PAGE_SIZE);
+    let statement: PreparedStatement = session.prepare(statement).await?;
+
+    let rows_stream = session
+        .execute_iter(statement, values)
         .await?
-        .into_rows_result()?;
-
-    let mut items = Vec::<Column>::new();
+        .rows_stream::<(String, String, String, String, String)>()?;
 
-    for row in result_rows.rows::<(String,)>()? {
-        let row_result = row?;
-        info!("Table_name: {}", row_result.0);
-        let table = row_result.0;
-
-        // SELECT * FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';
-        let select_columns_query = format!(
-            "SELECT keyspace_name, table_name, column_name, type FROM system_schema.columns WHERE keyspace_name = '{keyspace}' AND table_name = '{table}'"
-        );
+    collect_columns(rows_stream, limit).await
+}
 
-        let result_rows = session
-            .query_unpaged(select_columns_query, &[])
-            .await?
-            .into_rows_result()?;
+/*
+    Rows past the limit aren't polled,
+    so the pager never requests their pages
+*/
+async fn collect_columns<S>(mut rows_stream: S, limit: usize) -> Result<Vec<Column>, CqlError>
+where
+    S: Stream<Item = Result<(String, String, String, String, String), NextRowError>> + Unpin,
+{
+    let mut items = Vec::<Column>::new();
 
-        for jrow in result_rows.rows::<(String, String, String, String)>()? {
-            let jrow_result = jrow?;
-            let column = Column {
-                keyspace_name: jrow_result.0,
-                table_name: jrow_result.1,
-                column_name: jrow_result.2,
-                column_type: jrow_result.3,
-            };
+    while let Some(row) = rows_stream.next().await {
+        let (keyspace_name, table_name, column_name, column_type, kind) = row?;
+        items.push(Column {
+            keyspace_name,
+            table_name,
+            column_name,
+            column_type,
+            kind,
+        });
 
-            items.push(column);
+        // Next page isn't fetched once there's enough candidates
+        if limit != 0 && items.len() >= limit {
+            info!("Column query stopped at {} items", limit);
+            break;
         }
     }
 
@@ -308,16 +824,11 @@ pub async fn query_hard_scoped_fields(
     config: &CqlSettings,
     keyspace_name: &str,
     table_name: &str,
-) -> Result<Vec<Column>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+) -> Result<Vec<Column>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!(
-        "SELECT column_name, type  FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';",
+        "SELECT column_name, type, kind FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';",
         keyspace_name, table_name
     );
 
@@ -328,21 +839,425 @@ pub async fn query_hard_scoped_fields(
 
     let mut items = Vec::<Column>::new();
 
-    for row in result_rows.rows::<(String, String)>()? {
+    for row in result_rows.rows::<(String, String, String)>()? {
         let row_result = row?;
         let column_name = row_result.0;
         let column_type = row_result.1;
+        let kind = row_result.2;
         items.push(Column {
             keyspace_name: keyspace_name.to_string(),
             table_name: table_name.to_string(),
             column_name,
             column_type,
+            kind,
         });
     }
 
     Ok(items)
 }
 
+/*
+    Prepares statements without executing them
+
+    Yields index, message && whether it's a syntax error for every
+    statement the cluster rejected. USE statements are executed,
+    otherwise tables without keyspace couldn't be resolved
+*/
+pub async fn prepare_statements(
+    config: &CqlSettings,
+    statements: &[String],
+) -> Result<Vec<(usize, String, bool)>, CqlError> {
+    let session = create_session(config).await?;
+
+    let mut errors = Vec::<(usize, String, bool)>::new();
+
+    for (index, statement) in statements.iter().enumerate() {
+        let is_use = statement
+            .split_whitespace()
+            .next()
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("use"));
+
+        if is_use {
+            if let Err(e) = session.query_unpaged(statement.as_str(), &[]).await {
+                errors.push((index, e.to_string(), false));
+            }
+            continue;
+        }
+
+        match session.prepare(statement.as_str()).await {
+            Ok(_) => {}
+            Err(PrepareError::AllAttemptsFailed {
+                first_attempt: RequestAttemptError::DbError(db_error, message),
+            }) => {
+                errors.push((index, message, matches!(db_error, DbError::SyntaxError)));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(errors)
+}
+
+/*
+    Reconstructs CREATE TABLE statement of an existing table
+
+    Partition && clustering columns are ordered by their position,
+    other columns follow in alphabetical order like in cqlsh DESCRIBE.
+    Table options are taken from system_schema.tables as is, so options
+    specific to ScyllaDB or Cassandra are kept
+*/
+pub async fn describe_table(
+    config: &CqlSettings,
+    keyspace_name: &str,
+    table_name: &str,
+) -> Result<String, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT column_name, type, kind, position, clustering_order FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';",
+        keyspace_name, table_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    // (column, position, clustering order)
+    let mut columns = Vec::<(Column, i32, String)>::new();
+
+    for row in result_rows.rows::<(String, String, String, i32, String)>()? {
+        let (column_name, column_type, kind, position, clustering_order) = row?;
+        columns.push((
+            Column {
+                keyspace_name: keyspace_name.to_string(),
+                table_name: table_name.to_string(),
+                column_name,
+                column_type,
+                kind,
+            },
+            position,
+            clustering_order,
+        ));
+    }
+
+    if columns.is_empty() {
+        return Err(CqlError::NotFound(format!(
+            "Table {}.{} doesn't exist",
+            keyspace_name, table_name
+        )));
+    }
+
+    let key_columns = |kind: &str| -> Vec<&(Column, i32, String)> {
+        let mut key: Vec<&(Column, i32, String)> =
+            columns.iter().filter(|(c, _, _)| c.kind == kind).collect();
+        key.sort_by_key(|(_, position, _)| *position);
+        key
+    };
+
+    let partition_key = key_columns("partition_key");
+    let clustering_key = key_columns("clustering");
+
+    let mut regular: Vec<&Column> = columns
+        .iter()
+        .map(|(c, _, _)| c)
+        .filter(|c| c.kind == "regular" || c.kind == "static")
+        .collect();
+    regular.sort_by(|a, b| a.column_name.cmp(&b.column_name));
+
+    let is_simple_key = partition_key.len() == 1 && clustering_key.is_empty();
+
+    let mut definitions = Vec::<String>::new();
+
+    for (column, _, _) in partition_key.iter().chain(clustering_key.iter()) {
+        let definition = format!("{} {}", quote_name(&column.column_name), column.column_type);
+        match is_simple_key {
+            true => definitions.push(format!("{} PRIMARY KEY", definition)),
+            false => definitions.push(definition),
+        }
+    }
+
+    for column in regular {
+        let mut definition = format!("{} {}", quote_name(&column.column_name), column.column_type);
+        if column.kind == "static" {
+            definition.push_str(" static");
+        }
+        definitions.push(definition);
+    }
+
+    if !is_simple_key {
+        let partition: Vec<String> = partition_key
+            .iter()
+            .map(|(c, _, _)| quote_name(&c.column_name))
+            .collect();
+        let partition = match partition.len() {
+            1 => partition[0].clone(),
+            _ => format!("({})", partition.join(", ")),
+        };
+
+        let mut primary_key = vec![partition];
+        primary_key.extend(
+            clustering_key
+                .iter()
+                .map(|(c, _, _)| quote_name(&c.column_name)),
+        );
+
+        definitions.push(format!("PRIMARY KEY ({})", primary_key.join(", ")));
+    }
+
+    let mut options = Vec::<String>::new();
+
+    if !clustering_key.is_empty() {
+        let order: Vec<String> = clustering_key
+            .iter()
+            .map(|(c, _, order)| format!("{} {}", quote_name(&c.column_name), order.to_uppercase()))
+            .collect();
+        options.push(format!("CLUSTERING ORDER BY ({})", order.join(", ")));
+    }
+
+    let query = format!(
+        "SELECT * FROM system_schema.tables WHERE keyspace_name = '{}' AND table_name = '{}';",
+        keyspace_name, table_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let names: Vec<String> = result_rows
+        .column_specs()
+        .iter()
+        .map(|spec| spec.name().to_string())
+        .collect();
+
+    if let Some(row) = result_rows.maybe_first_row::<Row>()? {
+        for (name, value) in names.iter().zip(row.columns.iter()) {
+            if matches!(
+                name.as_str(),
+                "keyspace_name" | "table_name" | "id" | "flags" | "extensions"
+            ) {
+                continue;
+            }
+
+            if let Some(value) = value {
+                options.push(format!("{} = {}", name, value));
+            }
+        }
+    }
+
+    let mut statement = format!(
+        "CREATE TABLE {}.{} (\n    {}\n)",
+        quote_name(keyspace_name),
+        quote_name(table_name),
+        definitions.join(",\n    ")
+    );
+
+    if !options.is_empty() {
+        statement.push_str(&format!(" WITH {}", options.join("\n    AND ")));
+    }
+
+    statement.push(';');
+
+    Ok(statement)
+}
+
+/*
+    SELECT * is used on purpose, listing option columns
+    explicitly fails on versions that don't have one of them
+
+    None if table doesn't exist
+*/
+pub async fn query_tables_with_options(
+    config: &CqlSettings,
+    keyspace_name: &str,
+    table_name: &str,
+) -> Result<Option<TableOptions>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT * FROM system_schema.tables WHERE keyspace_name = '{}' AND table_name = '{}';",
+        keyspace_name, table_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let names: Vec<String> = result_rows
+        .column_specs()
+        .iter()
+        .map(|spec| spec.name().to_string())
+        .collect();
+
+    let Some(row) = result_rows.maybe_first_row::<Row>()? else {
+        return Ok(None);
+    };
+
+    let text_map = |value: &CqlValue| {
+        value.as_map().map(|entries| {
+            entries
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_text()?.clone(), v.as_text()?.clone())))
+                .collect::<std::collections::HashMap<String, String>>()
+        })
+    };
+
+    let mut options = TableOptions {
+        keyspace_name: keyspace_name.to_string(),
+        table_name: table_name.to_string(),
+        ..Default::default()
+    };
+
+    for (name, value) in names.iter().zip(row.columns.iter()) {
+        let Some(value) = value else {
+            continue;
+        };
+
+        match name.as_str() {
+            "comment" => options.comment = value.as_text().cloned(),
+            "default_time_to_live" => options.default_time_to_live = value.as_int(),
+            "gc_grace_seconds" => options.gc_grace_seconds = value.as_int(),
+            "bloom_filter_fp_chance" => options.bloom_filter_fp_chance = value.as_double(),
+            "crc_check_chance" => options.crc_check_chance = value.as_double(),
+            "speculative_retry" => options.speculative_retry = value.as_text().cloned(),
+            "memtable_flush_period_in_ms" => options.memtable_flush_period_in_ms = value.as_int(),
+            "min_index_interval" => options.min_index_interval = value.as_int(),
+            "max_index_interval" => options.max_index_interval = value.as_int(),
+            "cdc" => options.cdc = value.as_boolean(),
+            "caching" => options.caching = text_map(value),
+            "compaction" => options.compaction = text_map(value),
+            "compression" => options.compression = text_map(value),
+            _ => {}
+        }
+    }
+
+    Ok(Some(options))
+}
+
+/*
+    Names that aren't lower case identifiers must be quoted
+*/
+fn quote_name(name: &str) -> String {
+    let is_plain = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    match is_plain {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/*
+    Datacenter names of the cluster
+    used for NetworkTopologyStrategy completions
+*/
+pub async fn query_datacenters(config: &CqlSettings) -> Result<Vec<String>, CqlError> {
+    let session = create_session(config).await?;
+
+    let mut items = Vec::<String>::new();
+
+    for query in [
+        "SELECT data_center FROM system.local;",
+        "SELECT data_center FROM system.peers;",
+    ] {
+        let result_rows = session
+            .query_unpaged(query, &[])
+            .await?
+            .into_rows_result()?;
+
+        for row in result_rows.rows::<(Option<String>,)>()? {
+            if let (Some(data_center),) = row?
+                && !items.contains(&data_center)
+            {
+                items.push(data_center);
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+/*
+    Clustering order of clustering columns
+
+    column_name | asc/desc
+*/
+pub async fn query_clustering_order(
+    config: &CqlSettings,
+    keyspace_name: &str,
+    table_name: &str,
+) -> Result<Vec<(String, String)>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT column_name, kind, clustering_order, position FROM system_schema.columns WHERE keyspace_name = '{}' AND table_name = '{}';",
+        keyspace_name, table_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<(i32, String, String)>::new();
+
+    for row in result_rows.rows::<(String, String, String, i32)>()? {
+        let (column_name, kind, clustering_order, position) = row?;
+        if kind == "clustering" {
+            items.push((position, column_name, clustering_order));
+        }
+    }
+
+    items.sort_by_key(|item| item.0);
+
+    Ok(items
+        .into_iter()
+        .map(|(_, column_name, clustering_order)| (column_name, clustering_order))
+        .collect())
+}
+
+/*
+    Columns of the table that have secondary index
+*/
+pub async fn query_indexed_columns(
+    config: &CqlSettings,
+    keyspace_name: &str,
+    table_name: &str,
+) -> Result<Vec<String>, CqlError> {
+    let session = create_session(config).await?;
+
+    let mut items = Vec::<String>::new();
+
+    let query = format!(
+        "SELECT options FROM system_schema.indexes WHERE keyspace_name = '{}' AND table_name = '{}';",
+        keyspace_name, table_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    for row in result_rows.rows::<(std::collections::HashMap<String, String>,)>()? {
+        let (options,) = row?;
+
+        // target is either column_name or keys(column_name), values(...), etc.
+        if let Some(target) = options.get("target") {
+            let column_name = match (target.find('('), target.rfind(')')) {
+                (Some(start), Some(end)) if start < end => &target[start + 1..end],
+                _ => target.as_str(),
+            };
+            items.push(column_name.trim_matches('"').to_string());
+        }
+    }
+
+    Ok(items)
+}
+
 /*
     keyspace_name |
     aggregate_name |
@@ -353,15 +1268,8 @@ pub async fn query_hard_scoped_fields(
     state_func |
     state_type
 */
-pub async fn query_aggregates(
-    config: &CqlSettings,
-) -> Result<Vec<Aggregate>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn query_aggregates(config: &CqlSettings) -> Result<Vec<Aggregate>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!("SELECT keyspace_name, aggregate_name FROM system_schema.aggregates;");
 
@@ -385,6 +1293,34 @@ pub async fn query_aggregates(
     Ok(items)
 }
 
+pub async fn query_keyspace_scoped_aggregates(
+    config: &CqlSettings,
+    keyspace: &str,
+) -> Result<Vec<Aggregate>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT keyspace_name, aggregate_name FROM system_schema.aggregates WHERE keyspace_name = '{keyspace}';"
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<Aggregate>::new();
+
+    for row in result_rows.rows::<(String, String)>()? {
+        let row_result = row?;
+        items.push(Aggregate {
+            keyspace_name: row_result.0,
+            aggregate_name: row_result.1,
+        });
+    }
+
+    Ok(items)
+}
+
 /*
     keyspace_name |
     function_name |
@@ -395,15 +1331,8 @@ pub async fn query_aggregates(
     language |
     return_type
 */
-pub async fn query_functions(
-    config: &CqlSettings,
-) -> Result<Vec<Function>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn query_functions(config: &CqlSettings) -> Result<Vec<Function>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!("SELECT keyspace_name, function_name FROM system_schema.functions;");
 
@@ -427,6 +1356,34 @@ pub async fn query_functions(
     Ok(items)
 }
 
+pub async fn query_keyspace_scoped_functions(
+    config: &CqlSettings,
+    keyspace: &str,
+) -> Result<Vec<Function>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT keyspace_name, function_name FROM system_schema.functions WHERE keyspace_name = '{keyspace}';"
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<Function>::new();
+
+    for row in result_rows.rows::<(String, String)>()? {
+        let row_result = row?;
+        items.push(Function {
+            keyspace_name: row_result.0,
+            function_name: row_result.1,
+        });
+    }
+
+    Ok(items)
+}
+
 /*
     keyspace_name |
     table_name |
@@ -434,13 +1391,8 @@ pub async fn query_functions(
     kind |
     options
 */
-pub async fn query_indexes(config: &CqlSettings) -> Result<Vec<Index>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn query_indexes(config: &CqlSettings) -> Result<Vec<Index>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!("SELECT keyspace_name, index_name FROM system_schema.indexes;");
 
@@ -464,21 +1416,44 @@ pub async fn query_indexes(config: &CqlSettings) -> Result<Vec<Index>, Box<dyn s
     Ok(items)
 }
 
+pub async fn query_keyspace_scoped_indexes(
+    config: &CqlSettings,
+    keyspace: &str,
+) -> Result<Vec<Index>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT keyspace_name, index_name FROM system_schema.indexes WHERE keyspace_name = '{keyspace}';"
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<Index>::new();
+
+    for row in result_rows.rows::<(String, String)>()? {
+        let row_result = row?;
+        items.push(Index {
+            keyspace_name: row_result.0,
+            index_name: row_result.1,
+        });
+    }
+
+    Ok(items)
+}
+
 /*
     keyspace_name |
     type_name   |
     field_names |
     field_type
 */
-pub async fn query_types(config: &CqlSettings) -> Result<Vec<Type>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn query_types(config: &CqlSettings) -> Result<Vec<Type>, CqlError> {
+    let session = create_session(config).await?;
 
-    let query = format!("SELECT keyspace_name, type_name FROM system_schema.types;");
+    let query = "SELECT keyspace_name, type_name FROM system_schema.types;".to_string();
 
     let result_rows = session
         .query_unpaged(query, &[])
@@ -500,6 +1475,104 @@ pub async fn query_types(config: &CqlSettings) -> Result<Vec<Type>, Box<dyn std:
     Ok(items)
 }
 
+pub async fn query_keyspace_scoped_types(
+    config: &CqlSettings,
+    keyspace: &str,
+) -> Result<Vec<Type>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT keyspace_name, type_name FROM system_schema.types WHERE keyspace_name = '{keyspace}';"
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<Type>::new();
+
+    for row in result_rows.rows::<(String, String)>()? {
+        let row_result = row?;
+        items.push(Type {
+            keyspace_name: row_result.0,
+            type_name: row_result.1,
+        });
+    }
+
+    Ok(items)
+}
+
+/*
+    Queries role names
+
+    Roles live in system_auth.roles, newer ScyllaDB versions
+    keep them in system.roles. Clusters without auth tables
+    yield no roles instead of an error
+*/
+pub async fn query_roles(config: &CqlSettings) -> Result<Vec<Role>, CqlError> {
+    let session = create_session(config).await?;
+
+    for roles_table in ["system_auth.roles", "system.roles"] {
+        let query = format!("SELECT role FROM {};", roles_table);
+
+        let result_rows = match session.query_unpaged(query, &[]).await {
+            Ok(result) => result.into_rows_result()?,
+            Err(e) => {
+                info!("Failed to query roles from {}: {}", roles_table, e);
+                continue;
+            }
+        };
+
+        let mut items = Vec::<Role>::new();
+
+        for row in result_rows.rows::<(String,)>()? {
+            let (name,) = row?;
+            items.push(Role { name });
+        }
+
+        return Ok(items);
+    }
+
+    info!("Roles table wasn't found. Falling back to empty roles list");
+
+    Ok(vec![])
+}
+
+/*
+    Field names && types of a single UDT
+
+    Kept separate from query_types, which only lists type names
+
+    [(street, text), (zip, int)]
+*/
+pub async fn query_udt_fields(
+    config: &CqlSettings,
+    keyspace_name: &str,
+    type_name: &str,
+) -> Result<Vec<(String, String)>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT field_names, field_types FROM system_schema.types WHERE keyspace_name = '{}' AND type_name = '{}';",
+        keyspace_name, type_name
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<(String, String)>::new();
+
+    for row in result_rows.rows::<(Vec<String>, Vec<String>)>()? {
+        let (field_names, field_types) = row?;
+        items.extend(field_names.into_iter().zip(field_types));
+    }
+
+    Ok(items)
+}
+
 /*
     keyspace_name |
     view_name |
@@ -522,13 +1595,8 @@ pub async fn query_types(config: &CqlSettings) -> Result<Vec<Type>, Box<dyn std:
     speculative_retry |
     where_clause
 */
-pub async fn query_views(config: &CqlSettings) -> Result<Vec<View>, Box<dyn std::error::Error>> {
-    let session = SessionBuilder::new()
-        .known_node(&config.url)
-        .user(&config.user, &config.pswd)
-        .connection_timeout(Duration::from_secs(3))
-        .build()
-        .await?;
+pub async fn query_views(config: &CqlSettings) -> Result<Vec<View>, CqlError> {
+    let session = create_session(config).await?;
 
     let query = format!("SELECT keyspace_name, view_name FROM system_schema.views;");
 
@@ -551,3 +1619,149 @@ pub async fn query_views(config: &CqlSettings) -> Result<Vec<View>, Box<dyn std:
 
     Ok(items)
 }
+
+pub async fn query_keyspace_scoped_views(
+    config: &CqlSettings,
+    keyspace: &str,
+) -> Result<Vec<View>, CqlError> {
+    let session = create_session(config).await?;
+
+    let query = format!(
+        "SELECT keyspace_name, view_name FROM system_schema.views WHERE keyspace_name = '{keyspace}';"
+    );
+
+    let result_rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .into_rows_result()?;
+
+    let mut items = Vec::<View>::new();
+
+    for row in result_rows.rows::<(String, String)>()? {
+        let row_result = row?;
+        items.push(View {
+            keyspace_name: row_result.0,
+            view_name: row_result.1,
+        });
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn offline_mode_never_opens_a_session() {
+        let mut config = CqlSettings::new();
+        config.offline = true;
+        // Would hang on connect if the session was built
+        config.urls = vec!["10.255.255.1:9042".to_string()];
+        config.connection_timeout_secs = 60;
+
+        assert!(matches!(
+            create_session(&config).await,
+            Err(CqlError::Offline)
+        ));
+        assert!(matches!(
+            query_keyspaces(&config).await,
+            Err(CqlError::Offline)
+        ));
+    }
+
+    #[test]
+    fn contact_points_are_split() {
+        for (url, urls) in [
+            ("10.0.0.1:9042", vec!["10.0.0.1:9042"]),
+            (
+                "10.0.0.1:9042,10.0.0.2:9042",
+                vec!["10.0.0.1:9042", "10.0.0.2:9042"],
+            ),
+            (
+                " 10.0.0.1:9042 ,\t10.0.0.2:9042 ",
+                vec!["10.0.0.1:9042", "10.0.0.2:9042"],
+            ),
+            (
+                ",10.0.0.1:9042,,10.0.0.2:9042,",
+                vec!["10.0.0.1:9042", "10.0.0.2:9042"],
+            ),
+            ("", vec!["127.0.0.1:9042"]),
+            (" , ,", vec!["127.0.0.1:9042"]),
+        ] {
+            assert_eq!(CqlSettings::parse_urls(url), urls, "{:?}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn column_paging_stops_at_limit() {
+        let rows = COLUMNS_PAGE_SIZE as usize * 2 + 100;
+
+        for (limit, expected) in [(0, rows), (600, 600), (rows + 1, rows)] {
+            let polled = std::cell::Cell::new(0);
+            let stream = futures::stream::iter((0..rows).map(|i| {
+                Ok::<_, NextRowError>((
+                    "ks".to_string(),
+                    format!("t{}", i / 10),
+                    format!("c{}", i),
+                    "text".to_string(),
+                    "regular".to_string(),
+                ))
+            }))
+            .inspect(|_| polled.set(polled.get() + 1));
+
+            let columns = collect_columns(stream, limit).await.unwrap();
+
+            assert_eq!(columns.len(), expected, "limit {}", limit);
+            assert_eq!(polled.get(), expected, "limit {}", limit);
+            assert_eq!(
+                columns[expected - 1].column_name,
+                format!("c{}", expected - 1)
+            );
+        }
+    }
+
+    /*
+        Needs a cluster at CQL_LSP_DB_URL (127.0.0.1:9042 by default)
+
+        cargo test --features integration
+    */
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn udt_fields_are_queried() {
+        let config = crate::setup::load_settings(&Default::default());
+        let session = create_session(&config).await.unwrap();
+
+        for statement in [
+            "CREATE KEYSPACE IF NOT EXISTS cql_lsp_test WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1};",
+            "DROP TYPE IF EXISTS cql_lsp_test.address;",
+            "CREATE TYPE cql_lsp_test.address (street text, zip int, tags frozen<set<text>>);",
+        ] {
+            session.query_unpaged(statement, &[]).await.unwrap();
+        }
+
+        let fields = query_udt_fields(&config, "cql_lsp_test", "address")
+            .await
+            .unwrap();
+        assert_eq!(
+            fields,
+            [
+                ("street".to_string(), "text".to_string()),
+                ("zip".to_string(), "int".to_string()),
+                ("tags".to_string(), "frozen<set<text>>".to_string()),
+            ]
+        );
+
+        assert!(
+            query_udt_fields(&config, "cql_lsp_test", "missing")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        session
+            .query_unpaged("DROP KEYSPACE cql_lsp_test;", &[])
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use log::info;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::task::{AbortHandle, JoinHandle};
+use tower_lsp::lsp_types::Url;
+
+/*
+    Per document debounce
+
+    Every change of the document aborts the pause of the previous one,
+    only the latest change gets past debounce once typing stops
+
+    let Some(token) = self.debouncer.debounce(&uri).await else {
+        return;
+    };
+
+    // Expensive work, e.g. prepare statements on the cluster
+
+    if !self.debouncer.is_current(&token).await {
+        return;
+    }
+*/
+#[derive(Debug)]
+pub struct Debouncer {
+    pub delay: Duration,
+    pending: Mutex<HashMap<Url, (u64, AbortHandle)>>,
+    generation: AtomicU64,
+}
+
+/*
+    Change that got past debounce, cancelled
+    as soon as a newer change of the document comes in
+*/
+#[derive(Debug, Clone)]
+pub struct DebounceToken {
+    pub uri: Url,
+    generation: u64,
+}
+
+impl Debouncer {
+    pub fn new(delay_ms: u64) -> Self {
+        Self {
+            delay: Duration::from_millis(delay_ms),
+            pending: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /*
+        "300" -> 300ms
+
+        Invalid values fall back to default(300)
+    */
+    pub fn from_env(delay_ms: &str) -> Self {
+        Self::new(delay_ms.parse().unwrap_or_else(|_| {
+            info!(
+                "Invalid debounce {:?}. Setting debounce to default(300)",
+                delay_ms
+            );
+            300
+        }))
+    }
+
+    /*
+        None if a newer change of the document came in during the pause
+    */
+    pub async fn debounce(&self, uri: &Url) -> Option<DebounceToken> {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let delay = self.delay;
+        let pause: JoinHandle<()> = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+        });
+
+        if let Some((_, previous)) = self
+            .pending
+            .lock()
+            .await
+            .insert(uri.clone(), (generation, pause.abort_handle()))
+        {
+            previous.abort();
+        }
+
+        pause.await.ok()?;
+
+        Some(DebounceToken {
+            uri: uri.clone(),
+            generation,
+        })
+    }
+
+    /*
+        Aborts every pause, none of the pending
+        changes gets past debounce afterwards
+    */
+    pub async fn cancel_all(&self) {
+        for (_, (_, pause)) in self.pending.lock().await.drain() {
+            pause.abort();
+        }
+    }
+
+    // Work started with outdated token must be dropped
+    pub async fn is_current(&self, token: &DebounceToken) -> bool {
+        self.pending
+            .lock()
+            .await
+            .get(&token.uri)
+            .is_some_and(|(generation, _)| *generation == token.generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///tmp/{}.cql", name)).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rapid_changes_run_analysis_once() {
+        let debouncer = Arc::new(Debouncer::new(300));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut changes = Vec::new();
+
+        for _ in 0..5 {
+            let (debouncer, runs) = (debouncer.clone(), runs.clone());
+            changes.push(tokio::spawn(async move {
+                if debouncer.debounce(&uri("a")).await.is_some() {
+                    runs.fetch_add(1, Ordering::Relaxed);
+                }
+            }));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        for change in changes {
+            change.await.unwrap();
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn documents_are_debounced_separately() {
+        let debouncer = Arc::new(Debouncer::new(300));
+
+        let other = {
+            let debouncer = debouncer.clone();
+            tokio::spawn(async move { debouncer.debounce(&uri("b")).await })
+        };
+
+        assert!(debouncer.debounce(&uri("a")).await.is_some());
+        assert!(other.await.unwrap().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stale_token_is_not_current() {
+        let debouncer = Debouncer::new(300);
+
+        let token = debouncer.debounce(&uri("a")).await.unwrap();
+        assert!(debouncer.is_current(&token).await);
+
+        // Change that came in while the analysis of the older one was running
+        let newer = debouncer.debounce(&uri("a")).await.unwrap();
+        assert!(!debouncer.is_current(&token).await);
+        assert!(debouncer.is_current(&newer).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_all_drops_pending_changes() {
+        let debouncer = Arc::new(Debouncer::new(300));
+
+        let pending = {
+            let debouncer = debouncer.clone();
+            tokio::spawn(async move { debouncer.debounce(&uri("a")).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        debouncer.cancel_all().await;
+
+        assert!(pending.await.unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_delay_falls_back_to_default() {
+        assert_eq!(Debouncer::from_env("150").delay, Duration::from_millis(150));
+        assert_eq!(
+            Debouncer::from_env("soon").delay,
+            Duration::from_millis(300)
+        );
+    }
+}
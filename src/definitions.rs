@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use tower_lsp::jsonrpc;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::consts::CQL_KEYWORDS_LWC;
+use crate::lsp::Backend;
+
+/*
+    Go-to-definition && rename for tables and user defined types
+
+    Only definitions inside of the current file are resolved,
+    objects that exist only in the cluster yield None
+*/
+impl Backend {
+    pub async fn find_definition(&self, uri: &Url, position: &Position) -> Option<Location> {
+        let text = self.documents.read().await.get(uri)?.clone();
+        let tree = self.tree_for(uri).await?;
+
+        let offset = Self::position_to_offset(&text, position);
+        let node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+
+        let name = Self::referenced_name(node, &text)?;
+
+        let mut cursor = tree.root_node().walk();
+        for command in tree.root_node().children(&mut cursor) {
+            let Some((_, defined)) = Self::defined_name(command, &text) else {
+                continue;
+            };
+
+            if Self::names_match(&name, &defined) {
+                return Some(Location {
+                    uri: uri.clone(),
+                    range: Range {
+                        start: Self::offset_to_position(&text, command.start_byte()),
+                        end: Self::offset_to_position(&text, command.end_byte()),
+                    },
+                });
+            }
+        }
+
+        None
+    }
+
+    /*
+        Name under the cursor
+
+        Identifiers inside of table_keyspace_name are resolved
+        as a whole e.g ks.users when the cursor is on users
+    */
+    pub fn referenced_name(node: Node, text: &str) -> Option<String> {
+        let mut current = Some(node);
+
+        while let Some(n) = current {
+            if n.kind() == "table_keyspace_name" {
+                return n.utf8_text(text.as_bytes()).ok().map(|s| s.to_string());
+            }
+            if n.kind() == "cql_commands" {
+                break;
+            }
+            current = n.parent();
+        }
+
+        if node.kind() != "identifier" {
+            return None;
+        }
+
+        node.utf8_text(text.as_bytes()).ok().map(|s| s.to_string())
+    }
+
+    /*
+        Object type (table | type) and name defined by CREATE TABLE / CREATE TYPE command
+    */
+    fn defined_name(command: Node, text: &str) -> Option<(String, String)> {
+        let command_text = command.utf8_text(text.as_bytes()).ok()?;
+        let split: Vec<String> = command_text
+            .split_whitespace()
+            .take(2)
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        if split.len() < 2 || split[0] != "create" || (split[1] != "table" && split[1] != "type") {
+            return None;
+        }
+
+        let mut cursor = command.walk();
+        let name = command
+            .children(&mut cursor)
+            .find(|child| child.kind() == "table_keyspace_name")?;
+
+        let name = name.utf8_text(text.as_bytes()).ok()?.to_string();
+
+        Some((split[1].clone(), name))
+    }
+
+    /*
+        users matches ks.users and the other way around,
+        ks1.users doesn't match ks2.users,
+        "Users" doesn't match users
+    */
+    pub fn names_match(reference: &str, definition: &str) -> bool {
+        let (ref_keyspace, ref_name) = Self::parse_qualified_name(reference);
+        let (def_keyspace, def_name) = Self::parse_qualified_name(definition);
+
+        if ref_name != def_name {
+            return false;
+        }
+
+        match (ref_keyspace, def_keyspace) {
+            (Some(r), Some(d)) => r == d,
+            _ => true,
+        }
+    }
+
+    pub async fn rename_symbol(
+        &self,
+        uri: &Url,
+        position: &Position,
+        new_name: &str,
+    ) -> jsonrpc::Result<Option<WorkspaceEdit>> {
+        let new_name = Self::validate_new_name(new_name)?;
+
+        let Some(text) = self.documents.read().await.get(uri).cloned() else {
+            return Ok(None);
+        };
+
+        let Some(tree) = self.tree_for(uri).await else {
+            return Ok(None);
+        };
+
+        let offset = Self::position_to_offset(&text, position);
+        let Some(name) = tree
+            .root_node()
+            .descendant_for_byte_range(offset, offset)
+            .and_then(|node| Self::referenced_name(node, &text))
+        else {
+            return Ok(None);
+        };
+
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let commands: Vec<Node> = root.children(&mut cursor).collect();
+
+        let Some((object_type, defined)) = commands
+            .iter()
+            .filter_map(|command| Self::defined_name(*command, &text))
+            .find(|(_, defined)| Self::names_match(&name, defined))
+        else {
+            return Ok(None);
+        };
+
+        let mut occurrences = Vec::<Node>::new();
+
+        for command in commands {
+            let command_text = command
+                .utf8_text(text.as_bytes())
+                .unwrap_or("")
+                .to_lowercase();
+            let split: Vec<&str> = command_text.split_whitespace().take(2).collect();
+            let is_type_command = split.len() == 2 && split[1] == "type";
+            let is_create_command = split.len() == 2
+                && split[0] == "create"
+                && (split[1] == "table" || split[1] == "type");
+
+            // Table and type names of the command itself
+            if (object_type == "table") != is_type_command {
+                let mut cursor = command.walk();
+                for child in command.children(&mut cursor) {
+                    if child.kind() == "table_keyspace_name"
+                        && Self::names_match(
+                            child.utf8_text(text.as_bytes()).unwrap_or(""),
+                            &defined,
+                        )
+                    {
+                        occurrences.extend(Self::last_identifier(child));
+                    }
+                }
+            }
+
+            // Type references inside of column definitions
+            if object_type == "type" && is_create_command {
+                let mut identifiers = Vec::<Node>::new();
+                Self::collect_identifiers(command, &mut identifiers);
+
+                for identifier in identifiers {
+                    if Self::is_type_reference(identifier)
+                        && Self::names_match(
+                            identifier.utf8_text(text.as_bytes()).unwrap_or(""),
+                            &defined,
+                        )
+                    {
+                        occurrences.push(identifier);
+                    }
+                }
+            }
+        }
+
+        let edits: Vec<TextEdit> = occurrences
+            .iter()
+            .map(|node| TextEdit {
+                range: Range {
+                    start: Self::offset_to_position(&text, node.start_byte()),
+                    end: Self::offset_to_position(&text, node.end_byte()),
+                },
+                new_text: new_name.clone(),
+            })
+            .collect();
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }))
+    }
+
+    /*
+        Validates new name against CQL identifier rules
+
+        Names with uppercase/special chars are quoted,
+        reserved keywords are rejected
+    */
+    fn validate_new_name(name: &str) -> jsonrpc::Result<String> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err(jsonrpc::Error::invalid_params("New name can't be empty"));
+        }
+
+        if name.len() > 1 && name.starts_with('"') && name.ends_with('"') {
+            return Ok(name.to_string());
+        }
+
+        if CQL_KEYWORDS_LWC.contains(&name.to_lowercase()) {
+            return Err(jsonrpc::Error::invalid_params(format!(
+                "'{}' is a reserved CQL keyword",
+                name
+            )));
+        }
+
+        Ok(Self::quote_identifier(name))
+    }
+
+    /*
+        ks.users -> users
+    */
+    pub fn last_identifier(node: Node) -> Option<Node> {
+        let mut identifiers = Vec::<Node>::new();
+        Self::collect_identifiers(node, &mut identifiers);
+        identifiers.pop()
+    }
+
+    pub fn collect_identifiers<'a>(node: Node<'a>, identifiers: &mut Vec<Node<'a>>) {
+        if node.kind() == "identifier" {
+            identifiers.push(node);
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            Self::collect_identifiers(child, identifiers);
+        }
+    }
+
+    /*
+        frozen<address>, list<address> or column_name address
+    */
+    pub fn is_type_reference(identifier: Node) -> bool {
+        if identifier
+            .parent()
+            .is_some_and(|parent| parent.kind() == "key_space_name")
+        {
+            return false;
+        }
+
+        if identifier
+            .prev_named_sibling()
+            .is_some_and(|sibling| sibling.kind() == "identifier")
+        {
+            return true;
+        }
+
+        let mut current = identifier.parent();
+        while let Some(node) = current {
+            if node.kind() == "table_keyspace_name" {
+                return false;
+            }
+            if node.kind().starts_with("cql_types") {
+                return true;
+            }
+            if node.kind() == "cql_commands" {
+                break;
+            }
+            current = node.parent();
+        }
+
+        false
+    }
+}
@@ -0,0 +1,501 @@
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::consts::CQL_TYPES_LWC;
+use crate::cqlsh;
+use crate::lexer;
+use crate::lsp::Backend;
+use crate::tree_sitter::parse_cql;
+
+/*
+    Document diagnostics
+
+    list<address>       -> list<frozen<address>>
+    map<text, address>  -> map<text, frozen<address>>
+
+    User defined types nested inside of a collection must be frozen,
+    the whole collection being frozen (frozen<list<address>>) is enough
+
+    Statements rejected by the cluster are reported on save
+    when CQL_LSP_VALIDATE_ON_SAVE is set, or shortly after typing
+    stops when CQL_LSP_LIVE_VALIDATION is set, along with materialized
+    views missing primary key columns of the base table
+
+    Counter tables can't mix counter && regular columns,
+    everything outside of the primary key has to be a counter
+*/
+
+// Only CQL statements can be prepared, cqlsh commands like DESCRIBE can't
+const PREPARED_KEYWORDS: [&str; 11] = [
+    "alter", "create", "delete", "drop", "grant", "insert", "revoke", "select", "truncate",
+    "update", "use",
+];
+
+impl Backend {
+    pub async fn publish_diagnostics(&self, uri: &Url, text: &str) {
+        let diagnostics = self.get_diagnostics(text);
+
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+    }
+
+    pub fn get_diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = self.get_unfrozen_udt_diagnostics(text);
+        diagnostics.extend(self.get_counter_table_diagnostics(text));
+        diagnostics
+    }
+
+    pub async fn publish_validated_diagnostics(&self, uri: &Url, text: &str) {
+        let mut diagnostics = self.get_diagnostics(text);
+        diagnostics.extend(self.get_prepare_diagnostics(text).await);
+        diagnostics.extend(self.get_view_primary_key_diagnostics(text).await);
+
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+    }
+
+    /*
+        Newer changes of the document cancel validation of the older ones,
+        diagnostics of outdated text are never published
+    */
+    pub async fn validate_after_pause(&self, uri: &Url, version: i32) {
+        let Some(token) = self.debouncer.debounce(uri).await else {
+            return;
+        };
+
+        let Some(text) = self.documents.read().await.get(uri).cloned() else {
+            return;
+        };
+
+        let mut diagnostics = self.get_diagnostics(&text);
+        diagnostics.extend(self.get_prepare_diagnostics(&text).await);
+        diagnostics.extend(self.get_view_primary_key_diagnostics(&text).await);
+
+        if !self.debouncer.is_current(&token).await {
+            return;
+        }
+
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, Some(version))
+            .await;
+    }
+
+    /*
+        Statements are prepared, never executed. Batches can't be
+        prepared as a whole, so they are skipped
+    */
+    pub async fn get_prepare_diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let ranges: Vec<(usize, usize)> = Self::statement_ranges(text)
+            .into_iter()
+            .filter(|(start, _)| {
+                text[*start..]
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|keyword| {
+                        PREPARED_KEYWORDS.contains(&keyword.to_lowercase().as_str())
+                    })
+            })
+            .collect();
+
+        let statements: Vec<String> = ranges
+            .iter()
+            .map(|(start, end)| text[*start..*end].to_string())
+            .collect();
+
+        let errors = self
+            .unwrap_query(cqlsh::prepare_statements(&self.config(), &statements))
+            .await;
+
+        errors
+            .into_iter()
+            .map(|(index, message, is_syntax_error)| {
+                let (start, end) = ranges[index];
+                Diagnostic {
+                    range: Range {
+                        start: Self::offset_to_position(text, start),
+                        end: Self::offset_to_position(text, end),
+                    },
+                    severity: Some(match is_syntax_error {
+                        true => DiagnosticSeverity::ERROR,
+                        false => DiagnosticSeverity::WARNING,
+                    }),
+                    source: Some("cql-lsp".to_string()),
+                    message,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /*
+        Every primary key column of the base table
+        has to be a part of the view primary key
+
+        CREATE MATERIALIZED VIEW v AS SELECT * FROM users
+        WHERE ... PRIMARY KEY (email, id);
+    */
+    pub async fn get_view_primary_key_diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let Some(tree) = parse_cql(text) else {
+            return vec![];
+        };
+
+        let mut views = Vec::<Node>::new();
+        let mut cursor = tree.root_node().walk();
+
+        for node in tree.root_node().children(&mut cursor) {
+            let is_view = node.kind() == "cql_commands"
+                && node.utf8_text(text.as_bytes()).is_ok_and(|command| {
+                    let words: Vec<String> = command
+                        .split_whitespace()
+                        .take(3)
+                        .map(|w| w.to_lowercase())
+                        .collect();
+                    words == ["create", "materialized", "view"]
+                });
+
+            if is_view {
+                views.push(node);
+            }
+        }
+
+        let mut diagnostics = Vec::<Diagnostic>::new();
+
+        for view in views {
+            let Ok(command) = view.utf8_text(text.as_bytes()) else {
+                continue;
+            };
+
+            let mut cursor = view.walk();
+            // View name && base table
+            let names: Vec<Node> = view
+                .children(&mut cursor)
+                .filter(|child| child.kind() == "table_keyspace_name")
+                .collect();
+
+            let Some(base_table) = names
+                .get(1)
+                .and_then(|name| name.utf8_text(text.as_bytes()).ok())
+            else {
+                continue;
+            };
+
+            let base_table = base_table.replace('"', "");
+            let (keyspace, table) = match base_table.split_once('.') {
+                Some((keyspace, table)) => (keyspace.to_string(), table.to_string()),
+                None => {
+                    let position = Self::offset_to_position(text, view.start_byte());
+                    match self.latest_keyspace(&position).await {
+                        Some(keyspace) => (keyspace, base_table.clone()),
+                        None => continue,
+                    }
+                }
+            };
+
+            // PRIMARY KEY ((a, b), c) -> [a, b, c]
+            let lw_command = command.to_lowercase();
+            let Some(key_pos) = lw_command
+                .find("primary")
+                .and_then(|p| lw_command[p..].find('(').map(|k| p + k))
+            else {
+                continue;
+            };
+
+            let mut depth = 0;
+            let key_clause: String = lw_command[key_pos..]
+                .chars()
+                .take_while(|c| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth > 0
+                })
+                .collect();
+
+            let view_key: Vec<String> = key_clause
+                .split(|c: char| c == ',' || c == '(' || c.is_whitespace())
+                .map(|name| name.replace('"', ""))
+                .filter(|name| !name.is_empty())
+                .collect();
+
+            let columns = self
+                .unwrap_query(cqlsh::query_hard_scoped_fields(
+                    &self.config(),
+                    &keyspace,
+                    &table,
+                ))
+                .await;
+
+            let missing: Vec<&str> = columns
+                .iter()
+                .filter(|c| c.kind == "partition_key" || c.kind == "clustering")
+                .filter(|c| !view_key.contains(&c.column_name.to_lowercase()))
+                .map(|c| c.column_name.as_str())
+                .collect();
+
+            if missing.is_empty() {
+                continue;
+            }
+
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Self::offset_to_position(text, view.start_byte()),
+                    end: Self::offset_to_position(text, view.end_byte()),
+                },
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some("cql-lsp".to_string()),
+                message: format!(
+                    "View primary key omits primary key columns of {}.{}: {}",
+                    keyspace,
+                    table,
+                    missing.join(", ")
+                ),
+                ..Default::default()
+            });
+        }
+
+        diagnostics
+    }
+
+    /*
+        CREATE TABLE t (id int PRIMARY KEY, views counter, name text);
+
+        Key columns are the inline PRIMARY KEY one or
+        the ones listed in PRIMARY KEY ((a, b), c)
+    */
+    fn get_counter_table_diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::<Diagnostic>::new();
+
+        for (start, end) in Self::statement_ranges(text) {
+            let statement = &text[start..end];
+            let tokens = lexer::code_tokens(statement);
+
+            if !(tokens.len() > 2
+                && tokens[0].is_keyword("create")
+                && tokens[1].is_keyword("table"))
+            {
+                continue;
+            }
+
+            // Closing ) of the column list, unfinished statements are skipped
+            let Some(open) = tokens.iter().position(|t| t.is_punct("(")) else {
+                continue;
+            };
+
+            let mut depth = 0;
+            let Some(close) = tokens[open..].iter().position(|t| {
+                match t.text {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            }) else {
+                continue;
+            };
+            let close = open + close;
+
+            let declarations = Self::column_declarations(&statement[..tokens[close].start]);
+
+            let mut key: Vec<String> = declarations
+                .iter()
+                .filter(|(_, _, primary_key)| *primary_key)
+                .map(|(name, _, _)| name.clone())
+                .collect();
+
+            if let Some(primary) = tokens[..close].windows(3).position(|w| {
+                w[0].is_keyword("primary") && w[1].is_keyword("key") && w[2].is_punct("(")
+            }) {
+                key.extend(
+                    tokens[primary + 2..close]
+                        .iter()
+                        .filter(|t| t.is_word())
+                        .map(|t| Self::parse_identifier(t.text)),
+                );
+            }
+
+            let (counters, regular): (Vec<_>, Vec<_>) = declarations
+                .iter()
+                .filter(|(name, _, _)| !key.contains(name))
+                .partition(|(_, column_type, _)| column_type.eq_ignore_ascii_case("counter"));
+
+            if counters.is_empty() || regular.is_empty() {
+                continue;
+            }
+
+            let regular: Vec<&str> = regular.iter().map(|(name, _, _)| name.as_str()).collect();
+
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Self::offset_to_position(text, start),
+                    end: Self::offset_to_position(text, end),
+                },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("cql-lsp".to_string()),
+                message: format!(
+                    "Counter table can't have non-counter columns outside of the primary key: {}",
+                    regular.join(", ")
+                ),
+                ..Default::default()
+            });
+        }
+
+        diagnostics
+    }
+
+    fn get_unfrozen_udt_diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::<Diagnostic>::new();
+
+        // Type names of the unclosed <
+        let mut stack = Vec::<String>::new();
+        // Word waiting for the next char, map < would otherwise look like a type
+        let mut pending: Option<(Range, String)> = None;
+        let mut word: Option<(Position, String)> = None;
+
+        let mut in_block_comment = false;
+        let mut in_single_quotes = false;
+        let mut in_double_quotes = false;
+
+        for (index, line) in text.split('\n').enumerate() {
+            let mut chars = line.chars().peekable();
+            let mut column = 0;
+
+            while let Some(c) = chars.next() {
+                let next = chars.peek().copied();
+                let position = Position {
+                    line: index as u32,
+                    character: column,
+                };
+                column += c.len_utf16() as u32;
+
+                if in_block_comment {
+                    if c == '*' && next == Some('/') {
+                        chars.next();
+                        column += 1;
+                        in_block_comment = false;
+                    }
+                    continue;
+                }
+
+                if in_single_quotes {
+                    in_single_quotes = c != '\'';
+                    continue;
+                }
+
+                let is_word_char = c.is_alphanumeric() || c == '_' || c == '.' || c == '"';
+
+                if is_word_char && !in_double_quotes {
+                    match word {
+                        Some((_, ref mut w)) => w.push(c),
+                        None => word = Some((position, c.to_string())),
+                    }
+                    if c == '"' {
+                        in_double_quotes = true;
+                    }
+                    continue;
+                }
+
+                if in_double_quotes {
+                    if let Some((_, ref mut w)) = word {
+                        w.push(c);
+                    }
+                    in_double_quotes = c != '"';
+                    continue;
+                }
+
+                if let Some((start, w)) = word.take() {
+                    let range = Range {
+                        start,
+                        end: position,
+                    };
+                    pending = Some((range, w));
+                }
+
+                if c.is_whitespace() {
+                    continue;
+                }
+
+                if (c == '-' && next == Some('-')) || (c == '/' && next == Some('/')) {
+                    break;
+                }
+
+                if c == '/' && next == Some('*') {
+                    chars.next();
+                    column += 1;
+                    in_block_comment = true;
+                    continue;
+                }
+
+                match (c, pending.take()) {
+                    ('<', pending) => {
+                        stack.push(pending.map(|(_, w)| w.to_lowercase()).unwrap_or_default());
+                    }
+                    (_, Some((range, w))) => {
+                        if let Some(diagnostic) = self.unfrozen_udt_diagnostic(&stack, range, &w) {
+                            diagnostics.push(diagnostic);
+                        }
+                    }
+                    _ => {}
+                }
+
+                match c {
+                    '>' => {
+                        stack.pop();
+                    }
+                    ';' => stack.clear(),
+                    '\'' => in_single_quotes = true,
+                    _ => {}
+                }
+            }
+
+            // Words can't continue on the next line
+            if let Some((start, w)) = word.take() {
+                let range = Range {
+                    start,
+                    end: Position {
+                        line: index as u32,
+                        character: column,
+                    },
+                };
+                pending = Some((range, w));
+            }
+        }
+
+        diagnostics
+    }
+
+    fn unfrozen_udt_diagnostic(
+        &self,
+        stack: &[String],
+        range: Range,
+        type_name: &str,
+    ) -> Option<Diagnostic> {
+        let collection = stack.last()?;
+
+        if !matches!(collection.as_str(), "list" | "set" | "map")
+            || stack.iter().any(|t| t == "frozen")
+        {
+            return None;
+        }
+
+        if type_name.starts_with(|c: char| c.is_ascii_digit())
+            || CQL_TYPES_LWC.contains(&type_name.to_lowercase())
+        {
+            return None;
+        }
+
+        Some(Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("cql-lsp".to_string()),
+            message: format!(
+                "User defined type {} inside of {} must be frozen, use frozen<{}>",
+                type_name, collection, type_name
+            ),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::Backend;
+
+/*
+    Folding ranges
+
+    CREATE TABLE / CREATE TYPE bodies       (...)
+    Multi-line comments                     /* ... */
+    Batches                                 BEGIN BATCH ... APPLY BATCH;
+*/
+impl Backend {
+    pub fn get_folding_ranges(&self, text: &str) -> Vec<FoldingRange> {
+        let mut ranges = Vec::<FoldingRange>::new();
+
+        let mut comment_start: Option<usize> = None;
+        let mut batch_start: Option<usize> = None;
+        let mut body_start: Option<usize> = None;
+        let mut in_create_statement = false;
+        let mut depth = 0;
+
+        let folding_range =
+            |start: usize, end: usize, kind: Option<FoldingRangeKind>| FoldingRange {
+                start_line: start as u32,
+                end_line: end as u32,
+                kind,
+                ..Default::default()
+            };
+
+        for (index, line) in text.split('\n').enumerate() {
+            let lw_line = line.trim().to_lowercase();
+
+            if let Some(start) = comment_start {
+                if line.contains("*/") {
+                    ranges.push(folding_range(start, index, Some(FoldingRangeKind::Comment)));
+                    comment_start = None;
+                }
+                continue;
+            }
+
+            if let Some(offset) = line.find("/*")
+                && !line[offset..].contains("*/")
+            {
+                comment_start = Some(index);
+            }
+
+            let split: Vec<&str> = lw_line.split_whitespace().collect();
+
+            if self.is_batch_begin(line) {
+                batch_start = Some(index);
+            }
+
+            if self.is_batch_end(line)
+                && let Some(start) = batch_start.take()
+            {
+                ranges.push(folding_range(start, index, Some(FoldingRangeKind::Region)));
+            }
+
+            if split.first() == Some(&"create")
+                && split
+                    .iter()
+                    .take(3)
+                    .any(|w| w.starts_with("table") || w.starts_with("type"))
+            {
+                in_create_statement = true;
+            }
+
+            /*
+                Only parentheses are counted,
+                set<...> / frozen<...> don't affect depth
+            */
+            let (opened, closed) = self.count_parenthesis(line);
+
+            if depth == 0 && opened > closed && in_create_statement {
+                body_start = Some(index);
+            }
+
+            depth = (depth + opened).saturating_sub(closed);
+
+            if depth == 0
+                && let Some(start) = body_start.take()
+                && start < index
+            {
+                ranges.push(folding_range(start, index, None));
+            }
+
+            if depth == 0 && lw_line.contains(';') {
+                in_create_statement = false;
+            }
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(text: &str) -> Vec<(u32, u32, Option<FoldingRangeKind>)> {
+        let service = Backend::test_service();
+        service
+            .inner()
+            .get_folding_ranges(text)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect()
+    }
+
+    #[test]
+    fn nested_collection_types_fold_with_their_table() {
+        let text = [
+            "CREATE TABLE ks.profiles (",
+            "    id uuid,",
+            "    tags set<text>,",
+            "    scores map<text, frozen<list<int>>>,",
+            "    location frozen<tuple<double, double>>,",
+            "    history map<timestamp, frozen<set<frozen<tuple<int, text>>>>>,",
+            "    PRIMARY KEY ((id), tags)",
+            ");",
+            "CREATE TYPE ks.address (",
+            "    lines list<text>",
+            ");",
+        ]
+        .join("\n");
+
+        assert_eq!(folded(&text), [(0, 7, None), (8, 10, None)]);
+    }
+
+    #[test]
+    fn comments_and_batches_are_folded() {
+        let text = [
+            "/* seed",
+            "   data */",
+            "BEGIN COUNTER BATCH",
+            "UPDATE stats SET views = views + 1 WHERE id = 1;",
+            "APPLY BATCH;",
+            "/* single line */",
+            "SELECT (1) FROM t;",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            folded(&text),
+            [
+                (0, 1, Some(FoldingRangeKind::Comment)),
+                (2, 4, Some(FoldingRangeKind::Region)),
+            ]
+        );
+    }
+}
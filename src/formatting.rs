@@ -1,77 +1,49 @@
 use log::info;
 use tower_lsp::lsp_types::*;
 
-use crate::{consts::*, lsp::Backend};
+use crate::{
+    consts::*,
+    lsp::{Backend, KeywordCase},
+    utils::CommentLine,
+};
 
 impl Backend {
+    /*
+        Removes spaces before ; , ) >
+        and duplicate spaces
+    */
     pub fn remove_leading_spaces_wildcards(&self, line: &mut String) {
-        let mut index = 0;
-        let mut met_space = false;
+        let chars: Vec<char> = line.chars().collect();
+        let mut result = String::with_capacity(line.len());
 
-        while index < line.len() {
-            if !met_space && line.chars().nth(index).unwrap_or_else(|| '_') == ' ' {
-                met_space = true;
-            }
+        for (index, c) in chars.iter().enumerate() {
+            let next = chars.get(index + 1);
 
-            if met_space && line.chars().nth(index).unwrap_or_else(|| '_') != ' ' {
-                met_space = false;
-            }
-
-            if met_space
-                && index != line.len() - 1
-                && (line.chars().nth(index + 1).unwrap_or_else(|| '_') == ' '
-                    || line.chars().nth(index + 1).unwrap_or_else(|| '_') == ';'
-                    || line.chars().nth(index + 1).unwrap_or_else(|| '_') == ','
-                    || line.chars().nth(index + 1).unwrap_or_else(|| '_') == ')'
-                    || line.chars().nth(index + 1).unwrap_or_else(|| '_') == '>')
-            {
-                line.remove(index);
-                met_space = false;
-                if index >= 2 {
-                    index -= 2;
-                } else {
-                    index -= 1;
-                }
+            if *c == ' ' && matches!(next, Some(' ' | ';' | ',' | ')' | '>')) {
+                continue;
             }
 
-            index += 1;
+            result.push(*c);
         }
+
+        *line = result;
     }
 
+    /*
+        Removes spaces after ( <
+    */
     pub fn remove_tailing_spaces_wildcards(&self, line: &mut String) {
-        let mut index = 0;
-        let mut met_wild_card = false;
-
-        while index < line.len() {
-            if !met_wild_card
-                && (line.chars().nth(index).unwrap_or_else(|| '_') == '('
-                    || line.chars().nth(index).unwrap_or_else(|| '_') == '<')
-            {
-                met_wild_card = true;
-            }
-
-            if met_wild_card
-                && line.chars().nth(index).unwrap_or_else(|| '_') != '('
-                && line.chars().nth(index).unwrap_or_else(|| '_') != '<'
-            {
-                met_wild_card = false;
-            }
+        let mut result = String::with_capacity(line.len());
 
-            if met_wild_card
-                && index != line.len() - 1
-                && line.chars().nth(index + 1).unwrap_or_else(|| '_') == ' '
-            {
-                line.remove(index + 1);
-                met_wild_card = false;
-                if index >= 2 {
-                    index -= 2;
-                } else {
-                    index -= 1;
-                }
+        for c in line.chars() {
+            if c == ' ' && matches!(result.chars().last(), Some('(' | '<')) {
+                continue;
             }
 
-            index += 1;
+            result.push(c);
         }
+
+        *line = result;
     }
 
     pub async fn align_types_inside_create_statement(
@@ -156,7 +128,7 @@ impl Backend {
                     }
                 }
 
-                if let Some(mut offset_x) = line.find(&line_type) {
+                if let Some(offset_x) = Self::find_word_offset(line, &line_type) {
                     info!("\n\nOFFSET: {}, LINE_TYPE: {}\n\n", offset_x, line_type);
                     offsets.push((*index, offset_x));
                     if max_offset_x < offset_x {
@@ -173,7 +145,7 @@ impl Backend {
              */
             for offset in offsets {
                 if offset.1 < max_offset_x {
-                    let mut working_line = String::from(&lines[offset.0]);
+                    let working_line = String::from(&lines[offset.0]);
                     let diff = max_offset_x - offset.1;
                     info!("\n\nediting line: {}, {}\n\n", working_line, offset.1);
 
@@ -184,7 +156,18 @@ impl Backend {
     }
 
     pub fn add_tabs_to_cql_types(&self, lines: &mut Vec<String>) {
+        let mut in_batch = false;
+
         for line in lines {
+            // Batches only contain DML, COUNTER/SET there aren't column types
+            if self.is_batch_begin(line) {
+                in_batch = true;
+            }
+            if in_batch {
+                in_batch = !self.is_batch_end(line);
+                continue;
+            }
+
             if line.trim().is_empty() {
                 continue;
             }
@@ -198,37 +181,37 @@ impl Backend {
                 }
             }
 
-            if let Some(typ) = found_type {
-                if let Some(offset) = line.find(typ) {
-                    if offset > 0 {
-                        if !line[..offset]
-                            .ends_with(&" ".repeat(self.formatting_config.type_alignment_offset))
-                        {
-                            line.insert_str(
-                                offset,
-                                &" ".repeat(self.formatting_config.type_alignment_offset),
-                            );
-                        }
-                    }
-                }
+            let indent = " ".repeat(self.formatting_config.type_alignment_offset);
+
+            if let Some(typ) = found_type
+                && let Some(offset) = Self::find_word_offset(line, typ)
+                && offset > 0
+                && !line[..offset].ends_with(&indent)
+            {
+                line.insert_str(offset, &indent);
             }
         }
     }
 
-    pub async fn add_tabs_to_args(&self, lines: &mut Vec<String>, document_url: &Url) {
+    pub fn add_tabs_to_args(&self, lines: &mut [String], indent: &str) {
         let mut indices = Vec::<usize>::new();
+        let comment_lines = self.block_comment_lines(lines);
 
         for line in lines.iter().enumerate() {
-            let is_inside_multiline_comment =
-                self.is_line_in_multiline_comment(line.1, line.0, lines);
+            let is_inside_multiline_comment = self.is_comment_inside(&comment_lines, line.0, lines);
             let is_arg = self.is_line_inside_init_args(line.1, line.0, lines);
             let is_selector = self.is_line_inside_selectors(line.1, line.0, lines);
-            let is_ml_comment_clause = self.is_multi_line_comment_clause(line.1);
+            let is_ml_comment_clause = self.is_comment_clause(&comment_lines, line.0, lines);
             let trimed = line.1.trim_start();
             let mut is_pk = trimed.to_lowercase().starts_with("primary");
-            let is_pk_create_table = self
-                .is_inside_create_table_no_position(line.0, document_url)
-                .await;
+
+            /*
+                Checked against working lines, not the document itself
+                since previous passes could add/remove lines
+            */
+            let is_pk_create_table = self.is_inside_create_table_no_position(line.0, lines);
+            let is_inside_create_statement =
+                is_pk_create_table || self.is_inside_create_type_no_position(line.0, lines);
 
             info!("Line: {}\nIs inside: {}", line.1, is_pk_create_table);
 
@@ -236,53 +219,25 @@ impl Backend {
                 is_pk = false;
             }
 
+            let lw = line.1.to_lowercase();
+
             if (is_inside_multiline_comment
                 || (is_arg && !is_inside_multiline_comment && !is_ml_comment_clause)
                 || (is_selector && !is_inside_multiline_comment && !is_ml_comment_clause)
                 || (is_pk && !is_inside_multiline_comment && !is_ml_comment_clause))
-                && ((!line.1.to_lowercase().starts_with("create table")
-                    || !line.1.to_lowercase().starts_with("create type"))
-                    && (self
-                        .is_inside_create_table_no_position(line.0, document_url)
-                        .await
-                        || self
-                            .is_inside_create_type_no_position(line.0, document_url)
-                            .await))
-                && (!line.1.to_lowercase().starts_with("select")
-                    && (self
-                        .is_inside_create_table_no_position(line.0, document_url)
-                        .await
-                        || self
-                            .is_inside_create_type_no_position(line.0, document_url)
-                            .await))
-                && (!line.1.to_lowercase().starts_with("as")
-                    && (self
-                        .is_inside_create_table_no_position(line.0, document_url)
-                        .await
-                        || self
-                            .is_inside_create_type_no_position(line.0, document_url)
-                            .await))
-                && (!line.1.to_lowercase().starts_with("on")
-                    && (self
-                        .is_inside_create_table_no_position(line.0, document_url)
-                        .await
-                        || self
-                            .is_inside_create_type_no_position(line.0, document_url)
-                            .await))
-                && (!line.1.to_lowercase().starts_with("where")
-                    && (self
-                        .is_inside_create_table_no_position(line.0, document_url)
-                        .await
-                        || self
-                            .is_inside_create_type_no_position(line.0, document_url)
-                            .await))
+                && is_inside_create_statement
+                && !lw.starts_with("select")
+                && !lw.starts_with("as")
+                && !lw.starts_with("on")
+                && !lw.starts_with("where")
             {
                 indices.push(line.0);
             }
         }
 
+        // Re-indent instead of prepending, so already indented lines stay the same
         for x in indices {
-            lines[x].insert_str(0, "    ");
+            lines[x] = format!("{}{}", indent, lines[x].trim_start());
         }
     }
 
@@ -332,139 +287,181 @@ impl Backend {
     Removes duplicates of ;
     */
     pub fn fix_duplicate_semi_colon(&self, line: &mut String) {
-        let mut last_colon = false;
-        let mut index = 0;
-
-        /*
-        The reason for using unwrap_or_else is
-        that when line contains Japanese (non-standart range ASCII)
-        the line.len() isn't represented correctly and will lead
-        to out of bounds access
-        */
-        while index < line.len() {
-            if !last_colon && line.chars().nth(index).unwrap_or_else(|| '_') == ';' {
-                last_colon = true;
-            } else if last_colon && line.chars().nth(index).unwrap_or_else(|| '_') == ';' {
-                line.remove(index);
-                last_colon = false;
-                if index >= 2 {
-                    index -= 2;
-                } else {
-                    index -= 1;
-                }
-            } else if line.chars().nth(index).unwrap_or_else(|| '_') != ';' {
-                last_colon = false;
-            }
-            index += 1;
-        }
+        self.remove_repeated_char(line, ';');
     }
 
     // Removes any duplicate spaces
     pub fn fix_spacing(&self, line: &mut String) {
-        let mut last_space = false;
-        let mut index = 0;
+        self.remove_repeated_char(line, ' ');
+    }
 
-        /*
-            The reason for using unwrap_or_else is
-            that when line contains Japanese (non-standart range ASCII)
-            the line.len() isn't represented correctly and will lead
-            to out of bounds access
-        */
-        while index < line.len() {
-            if !last_space && line.chars().nth(index).unwrap_or_else(|| '_') == ' ' {
-                last_space = true;
-            } else if last_space && line.chars().nth(index).unwrap_or_else(|| '_') == ' ' {
-                line.remove(index);
-                last_space = false;
-                if index >= 2 {
-                    index -= 2;
-                } else {
-                    index -= 1;
-                }
-            } else if line.chars().nth(index).unwrap_or_else(|| '_') != ' ' {
-                last_space = false;
+    /*
+        Collapses runs of the same char into a single one
+
+        Works on chars rather than byte indices,
+        so multibyte text (だよ, emoji) is handled correctly
+    */
+    fn remove_repeated_char(&self, line: &mut String, repeated: char) {
+        let mut result = String::with_capacity(line.len());
+        let mut last = None;
+
+        for c in line.chars() {
+            if c == repeated && last == Some(repeated) {
+                continue;
             }
-            index += 1;
+
+            result.push(c);
+            last = Some(c);
         }
+
+        *line = result;
     }
 
-    // Removes \n after \n or ( )
+    /*
+        Collapses blank lines
+
+        Runs of blank lines are collapsed into a single one,
+        blank line right after an opened ( is removed.
+        Blank lines next to comments are kept as they are
+        && nothing is touched inside of block comments
+    */
     pub fn fix_new_lines(&self, lines: &mut Vec<String>) {
-        let mut index = 0;
-        let mut last_new_line = false;
-        let mut last_bracket = false;
+        let comment_lines = self.block_comment_lines(lines);
 
-        while index < lines.len() {
-            if last_new_line && lines[index].len() == 0 {
-                lines.remove(index);
-                if index >= 2 {
-                    index -= 2;
-                } else if index > 0 {
-                    index -= 1;
-                }
-            }
+        let is_comment = |index: usize| {
+            let trimmed = lines[index].trim_start();
 
-            if last_bracket && lines[index].len() == 0 {
-                lines.remove(index);
-                if index >= 2 {
-                    index -= 2;
-                } else if index > 0 {
-                    index -= 1;
-                }
-            }
+            trimmed.starts_with("--")
+                || trimmed.starts_with("//")
+                || self.is_comment_inside(&comment_lines, index, lines)
+                || self.is_comment_clause(&comment_lines, index, lines)
+        };
 
-            if lines[index].len() == 0
-                && !self.is_line_in_multiline_comment(&lines[index], index, lines)
-            {
-                last_new_line = true;
-            } else {
-                last_new_line = false;
+        let mut result = Vec::<String>::with_capacity(lines.len());
+        // Index of the last kept line inside of lines
+        let mut previous: Option<usize> = None;
+
+        for index in 0..lines.len() {
+            if !lines[index].is_empty() || is_comment(index) {
+                result.push(lines[index].clone());
+                previous = Some(index);
+                continue;
             }
 
-            if lines[index].contains("(") {
-                last_bracket = true;
-            } else {
-                last_bracket = false
+            let Some(previous_index) = previous else {
+                result.push(lines[index].clone());
+                previous = Some(index);
+                continue;
+            };
+
+            let previous_line = &lines[previous_index];
+            let next_is_comment = (index + 1..lines.len())
+                .find(|&i| !lines[i].is_empty())
+                .is_some_and(is_comment);
+
+            let is_repeated = previous_line.is_empty() && !is_comment(previous_index);
+            let opens_block = !is_comment(previous_index)
+                && previous_line.matches('(').count() > previous_line.matches(')').count();
+
+            if is_repeated || (opens_block && !next_is_comment) {
+                continue;
             }
 
-            index += 1;
+            result.push(lines[index].clone());
+            previous = Some(index);
         }
+
+        *lines = result;
     }
 
     /*
         Removes all '\n' inside code_blocks
+
+        Code block is a parenthesized statement body e.g CREATE TABLE (...),
+        blank lines between statements and comments are kept
     */
     pub fn remove_new_lines_from_code_block(&self, lines: &mut Vec<String>) {
         let mut index = 0;
-        let mut inside_code_block = false;
+        let mut parenthesis_depth: usize = 0;
+        let mut comment_lines = self.block_comment_lines(lines);
 
         while index < lines.len() {
-            let line = lines[index].to_lowercase();
+            let in_comment = self.is_comment_inside(&comment_lines, index, lines);
 
-            if !inside_code_block && line.len() > 0 && !line.contains(";") {
-                inside_code_block = true;
+            if parenthesis_depth > 0 && lines[index].trim().is_empty() && !in_comment {
+                lines.remove(index);
+                if let Some(comment_lines) = comment_lines.as_mut() {
+                    comment_lines.remove(index);
+                }
+                continue;
             }
 
-            if inside_code_block && line.contains(";") {
-                inside_code_block = false;
+            if !in_comment {
+                let (opened, closed) = self.count_parenthesis(&lines[index]);
+                parenthesis_depth = (parenthesis_depth + opened).saturating_sub(closed);
             }
 
-            if inside_code_block
-                && line.len() == 0
-                && !self.is_line_in_multiline_comment(&line, index, lines)
-            {
-                lines.remove(index);
-                if index >= 2 {
-                    index -= 2;
-                } else if index > 0 {
-                    index -= 1;
+            index += 1;
+        }
+    }
+
+    /*
+        Removes blank lines inside of batches && indents contained statements
+
+        BEGIN BATCH
+            INSERT INTO ks.t (id) VALUES (1);
+            DELETE FROM ks.t WHERE id = 2;
+        APPLY BATCH;
+    */
+    pub fn format_batch_blocks(&self, lines: &mut Vec<String>, indent: &str) {
+        let mut index = 0;
+        let mut in_batch = false;
+
+        while index < lines.len() {
+            if self.is_batch_end(&lines[index]) {
+                in_batch = false;
+            }
+
+            if in_batch {
+                if lines[index].trim().is_empty() {
+                    lines.remove(index);
+                    continue;
                 }
+
+                lines[index].insert_str(0, indent);
+            }
+
+            if self.is_batch_begin(&lines[index]) {
+                in_batch = true;
             }
 
             index += 1;
         }
     }
 
+    /*
+        Counts ( and ) outside of string literals and -- // comments
+    */
+    pub fn count_parenthesis(&self, line: &str) -> (usize, usize) {
+        let mut opened = 0;
+        let mut closed = 0;
+        let mut in_string = false;
+        let mut previous = ' ';
+
+        for c in line.chars() {
+            match c {
+                '\'' => in_string = !in_string,
+                '-' | '/' if !in_string && previous == c => break,
+                '(' if !in_string => opened += 1,
+                ')' if !in_string => closed += 1,
+                _ => {}
+            }
+            previous = c;
+        }
+
+        (opened, closed)
+    }
+
     /*
         Adds missing semi colon to the and of CQL command
 
@@ -473,6 +470,7 @@ impl Backend {
     */
     pub fn apply_semi_colon(&self, lines: &mut Vec<String>) {
         let mut index = 0;
+        let comment_lines = self.block_comment_lines(lines);
 
         while index < lines.len() {
             let line = lines[index].to_lowercase();
@@ -480,14 +478,14 @@ impl Backend {
             if index + 1 != lines.len()
                 && line.len() > 0
                 && !line.contains(";")
+                && !self.is_statement_continued(&line)
                 && !line.contains("begin")
                 && !line.contains("//")
                 && !line.contains("--")
-                && !line.contains("/*")
-                && !line.contains("*/")
+                && !self.is_comment_clause(&comment_lines, index, lines)
                 && !line.ends_with("as")
                 && !line.ends_with("with")
-                && !self.is_line_in_multiline_comment(&line, index, lines)
+                && !self.is_comment_inside(&comment_lines, index, lines)
             {
                 let lw = lines[index + 1].to_lowercase();
                 let split: Vec<&str> = lw.split(' ').collect();
@@ -501,14 +499,14 @@ impl Backend {
             if index == lines.len() - 1
                 && line.len() > 0
                 && !line.contains(";")
+                && !self.is_statement_continued(&line)
                 && !line.contains("begin")
                 && !line.contains("//")
                 && !line.contains("--")
-                && !line.contains("/*")
-                && !line.contains("*/")
+                && !self.is_comment_clause(&comment_lines, index, lines)
                 && !line.ends_with("as")
                 && !line.ends_with("with")
-                && !self.is_line_in_multiline_comment(&line, index, lines)
+                && !self.is_comment_inside(&comment_lines, index, lines)
             {
                 lines[index].push(';');
             }
@@ -517,13 +515,36 @@ impl Backend {
         }
     }
 
+    /*
+        Line that can't be the end of CQL command
+
+        CREATE TABLE t (
+        name text,
+        WITH replication = {
+    */
+    fn is_statement_continued(&self, line: &str) -> bool {
+        let trimmed = line.trim_end();
+
+        trimmed.ends_with(',') || trimmed.ends_with('(') || trimmed.ends_with('{')
+    }
+
     pub fn add_spacing_new_lines(&self, lines: &mut Vec<String>) {
         let mut index = 0;
+        let mut in_batch = false;
 
         while index < lines.len() {
+            // Statements inside of BEGIN BATCH ... APPLY BATCH stay contiguous
+            if self.is_batch_begin(&lines[index]) {
+                in_batch = true;
+            }
+            if self.is_batch_end(&lines[index]) {
+                in_batch = false;
+            }
+
             if index + 1 != lines.len()
+                && !in_batch
                 && (lines[index].contains(";") || lines[index].to_lowercase().contains("begin"))
-                && lines[index + 1].len() > 0
+                && !lines[index + 1].trim().is_empty()
             {
                 lines.insert(index + 1, "".to_string());
             }
@@ -533,7 +554,7 @@ impl Backend {
     }
 
     pub fn add_new_line_before_pk(&self, lines: &mut Vec<String>) {
-        let mut index = lines.len() - 1;
+        let mut index = lines.len().saturating_sub(1);
 
         let mut indicies: Vec<usize> = Vec::new();
 
@@ -577,20 +598,35 @@ impl Backend {
         // }
     }
 
-    pub fn add_spacing_after_comma(&self, lines: &mut Vec<String>) {
-        let mut index = 0;
+    /*
+        a,b -> a, b
 
-        while index < lines.len() {
-            for idx in 0..lines[index].len() {
-                if idx + 1 != lines[index].len()
-                    && lines[index].chars().nth(idx).unwrap_or_else(|| '_') == ','
-                    && lines[index].chars().nth(idx + 1).unwrap_or_else(|| '_') != ' '
-                {
-                    lines[index].insert(idx + 1, ' ');
+        Commas inside of string literals and -- // comments are left as is
+    */
+    pub fn add_spacing_after_comma(&self, lines: &mut [String]) {
+        for line in lines.iter_mut() {
+            let mut result = String::with_capacity(line.len());
+            let mut chars = line.char_indices().peekable();
+            let mut in_string = false;
+
+            while let Some((i, c)) = chars.next() {
+                if !in_string && (line[i..].starts_with("--") || line[i..].starts_with("//")) {
+                    result.push_str(&line[i..]);
+                    break;
+                }
+
+                if c == '\'' {
+                    in_string = !in_string;
+                }
+
+                result.push(c);
+
+                if c == ',' && !in_string && chars.peek().is_some_and(|(_, next)| *next != ' ') {
+                    result.push(' ');
                 }
             }
 
-            index += 1;
+            *line = result;
         }
     }
 
@@ -599,83 +635,127 @@ impl Backend {
         Formats select statements in the following manner
 
         SELECT
-        selector1,
-        selector2,
-        selector3,
-        ...,
-        selectorN,
+            selector1,
+            selector2,
+            ...,
+            selectorN
         FROM [keyspace_name].table_name;
+
+        Function calls like count(*) or token(a, b) are kept as a single selector.
+        Statements with a single selector or without FROM are left untouched.
     */
-    pub fn format_selectors(&self, lines: &mut Vec<String>) {
+    pub fn format_selectors(&self, lines: &mut Vec<String>, indent: &str) {
         let mut index = 0;
-        let mut working_buf = Vec::<String>::new();
 
         while index < lines.len() {
-            let lw = lines[index].to_lowercase();
-            if lw.contains("select") && self.should_edit_select_statement(&lines[index], lines) {
-                working_buf.clear();
+            if !self.should_edit_select_statement(&lines[index]) {
+                index += 1;
+                continue;
+            }
 
-                let mut idx = index;
+            let mut selectors_text = String::new();
+            let mut from_clause: Option<(usize, String)> = None;
+            let mut idx = index;
 
-                while idx < lines.len() {
-                    if !lines[idx].to_lowercase().contains("from") && lines[idx].contains(";") {
-                        return;
-                    }
+            while idx < lines.len() {
+                let line = if idx == index {
+                    lines[idx].trim()[6..].to_string()
+                } else {
+                    lines[idx].trim().to_string()
+                };
 
-                    if lines[idx].to_lowercase().contains("from") {
-                        let split: Vec<&str> = lines[idx].split(' ').collect();
-                        for sp in split.into_iter() {
-                            if sp.to_lowercase() == "from" {
-                                break;
-                            }
-                            if sp.to_lowercase() != "select" {
-                                let mut retained = sp.to_string();
-                                retained.retain(|c| c != '\n' && c != '\r');
-                                retained.push('\n');
-                                working_buf.push(retained);
-                            }
-                        }
+                if line.contains("--") || line.contains("//") || line.contains("/*") {
+                    break;
+                }
 
-                        let from_pos = lines[idx].to_lowercase().rfind("from").unwrap();
-                        working_buf.push(lines[idx][from_pos..].to_string());
-                        break;
-                    }
+                if let Some(from_pos) = Self::find_word_offset(&line.to_ascii_lowercase(), "from") {
+                    selectors_text.push(' ');
+                    selectors_text.push_str(&line[..from_pos]);
+                    from_clause = Some((idx, line[from_pos..].to_string()));
+                    break;
+                }
 
-                    let split: Vec<&str> = lines[idx].split(' ').collect();
+                // Statement ended without FROM, move on to the next one
+                if line.contains(';') {
+                    break;
+                }
 
-                    for sp in split.into_iter() {
-                        if sp.to_lowercase() != "select" {
-                            working_buf.push(sp.to_string());
-                        }
-                    }
+                selectors_text.push(' ');
+                selectors_text.push_str(&line);
+                idx += 1;
+            }
 
-                    idx += 1;
-                }
+            let Some((from_index, from_line)) = from_clause else {
+                index = idx + 1;
+                continue;
+            };
 
-                let mut start_idx = index + 1;
-                for kw in working_buf.iter_mut() {
-                    kw.retain(|c| c != '\n' && c != '\r');
-                    kw.push('\n');
+            let mut header = lines[index].trim()[..6].to_string();
+            let mut selectors_text = selectors_text.trim();
 
-                    if start_idx < lines.len() {
-                        lines.insert(start_idx, kw.clone());
-                    } else {
-                        lines.push(kw.clone());
-                    }
-                    start_idx += 1;
+            for modifier in ["distinct", "json"] {
+                let lw = selectors_text.to_ascii_lowercase();
+                if lw.starts_with(modifier) && lw[modifier.len()..].starts_with(char::is_whitespace)
+                {
+                    header.push(' ');
+                    header.push_str(&selectors_text[..modifier.len()]);
+                    selectors_text = selectors_text[modifier.len()..].trim_start();
                 }
+            }
 
-                if lines[index].chars().nth(0).unwrap() == 'S' {
-                    lines[index] = "SELECT".to_string();
+            let selectors = self.split_selectors(selectors_text);
+
+            if selectors.len() < 2 {
+                index = from_index + 1;
+                continue;
+            }
+
+            let mut formatted = vec![header];
+            for (i, selector) in selectors.iter().enumerate() {
+                if i + 1 < selectors.len() {
+                    formatted.push(format!("{}{},", indent, selector));
                 } else {
-                    lines[index] = "select".to_string();
+                    formatted.push(format!("{}{}", indent, selector));
                 }
+            }
+            formatted.push(from_line);
 
-                index += working_buf.len();
-            } else {
-                index += 1;
+            let formatted_len = formatted.len();
+            lines.splice(index..=from_index, formatted);
+
+            index += formatted_len;
+        }
+    }
+
+    /*
+        Splits selectors on top level commas,
+        commas inside function calls and string literals are ignored
+    */
+    fn split_selectors(&self, text: &str) -> Vec<String> {
+        let mut selectors = Vec::<String>::new();
+        let mut current = String::new();
+        let mut depth: usize = 0;
+        let mut in_string = false;
+
+        for c in text.chars() {
+            match c {
+                '\'' => in_string = !in_string,
+                '(' if !in_string => depth += 1,
+                ')' if !in_string => depth = depth.saturating_sub(1),
+                ',' if !in_string && depth == 0 => {
+                    selectors.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
             }
+            current.push(c);
         }
+
+        selectors.push(current.trim().to_string());
+        selectors.retain(|s| !s.is_empty());
+
+        selectors
     }
 
     /*
@@ -687,11 +767,294 @@ impl Backend {
             long_name_xxxxx  type
         );
     */
-    pub fn format_table_fields(&self, lines: &mut Vec<String>) {}
+    pub fn format_table_fields(&self, lines: &mut [String], indent: &str) {
+        let mut working_blocks: Vec<Vec<usize>> = Vec::new();
+        let mut current_block: Vec<usize> = Vec::new();
+        let mut in_table = false;
+        let mut parenthesis_depth: usize = 0;
+        let comment_lines = self.block_comment_lines(lines);
 
-    pub async fn format_file(&self, lines: &Vec<&str>, document_url: &Url) -> Vec<TextEdit> {
-        let mut edits = Vec::<TextEdit>::new();
-        let mut working_vec: Vec<String> = lines.into_iter().map(|s| s.to_string()).collect();
+        for (i, line) in lines.iter().enumerate() {
+            let lw = line.trim().to_lowercase();
+
+            if !in_table {
+                if !lw.starts_with("create table") {
+                    continue;
+                }
+                in_table = true;
+            }
+
+            let depth_before = parenthesis_depth;
+            parenthesis_depth += line.matches('(').count();
+            parenthesis_depth = parenthesis_depth.saturating_sub(line.matches(')').count());
+
+            // Only direct children of CREATE TABLE (...) are fields
+            if depth_before == 1 && self.is_table_field_line(line, i, lines, &comment_lines) {
+                current_block.push(i);
+            }
+
+            // End of table definition (parenthesis balanced)
+            if (depth_before > 0 && parenthesis_depth == 0) || lw.contains(';') {
+                if !current_block.is_empty() {
+                    working_blocks.push(std::mem::take(&mut current_block));
+                }
+                in_table = false;
+                parenthesis_depth = 0;
+            }
+        }
+
+        if !current_block.is_empty() {
+            working_blocks.push(current_block);
+        }
+
+        for block in working_blocks.iter() {
+            let fields: Vec<(usize, String, String)> = block
+                .iter()
+                .filter_map(|index| {
+                    Self::split_table_field(&lines[*index])
+                        .map(|(name, rest)| (*index, name.to_string(), rest.to_string()))
+                })
+                .collect();
+
+            let max_name_len = fields
+                .iter()
+                .map(|field| field.1.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            /*
+             * 0 -> index
+             * 1 -> field name
+             * 2 -> type, modifiers && trailing comma
+             */
+            for field in fields {
+                let padding = max_name_len - field.1.chars().count() + 1;
+                lines[field.0] = format!("{}{}{}{}", indent, field.1, " ".repeat(padding), field.2);
+            }
+        }
+    }
+
+    /*
+        Field line inside of CREATE TABLE body
+
+        Skips comments, PRIMARY KEY (...) clause and closing bracket
+    */
+    fn is_table_field_line(
+        &self,
+        line: &str,
+        index: usize,
+        lines: &[String],
+        comment_lines: &Option<Vec<CommentLine>>,
+    ) -> bool {
+        let trimmed = line.trim();
+        let lw = trimmed.to_lowercase();
+
+        !trimmed.is_empty()
+            && !trimmed.starts_with(')')
+            && !trimmed.starts_with("--")
+            && !trimmed.starts_with("//")
+            && !lw.starts_with("primary")
+            && !self.is_comment_clause(comment_lines, index, lines)
+            && !self.is_comment_inside(comment_lines, index, lines)
+    }
+
+    /*
+        Splits field line into (name, rest)
+
+        user_id    uuid PRIMARY KEY,
+        ^^^^^^^    ^^^^^^^^^^^^^^^^^
+        name       rest
+    */
+    fn split_table_field(line: &str) -> Option<(&str, &str)> {
+        let trimmed = line.trim();
+
+        let name_end = if let Some(quoted) = trimmed.strip_prefix('"') {
+            quoted.find('"').map(|p| p + 2)?
+        } else {
+            trimmed.find(char::is_whitespace)?
+        };
+
+        let rest = trimmed[name_end..].trim_start();
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        Some((&trimmed[..name_end], rest))
+    }
+
+    /*
+        Rewrites keywords && types to CQL_LSP_KEYWORD_CASE
+
+        Statement keywords (CQL_KEYWORDS_LWC) are changed only at the start
+        of a statement, types (CQL_TYPES_LWC) only in a type position
+        e.g column type inside CREATE TABLE/TYPE or inside <...>,
+        so identifiers named like keywords are left as is.
+        String literals, quoted identifiers and comments are never touched.
+    */
+    pub fn normalize_keyword_case(&self, lines: &mut [String]) {
+        let case = self.formatting_config.keyword_case;
+        if case == KeywordCase::Preserve {
+            return;
+        }
+
+        let apply_case = |word: &str| match case {
+            KeywordCase::Upper => word.to_uppercase(),
+            _ => word.to_lowercase(),
+        };
+
+        let mut statement_start = true;
+        let mut in_create_body = false;
+        let mut parenthesis_depth: usize = 0;
+        let mut angle_depth: usize = 0;
+        // 0 - none, 1 - field name expected, 2 - type expected
+        let mut field_state = 0;
+
+        let comment_lines = self.block_comment_lines(lines);
+
+        for index in 0..lines.len() {
+            if self.is_comment_inside(&comment_lines, index, lines)
+                || self.is_comment_clause(&comment_lines, index, lines)
+            {
+                continue;
+            }
+
+            let line = lines[index].clone();
+            let lw_line = line.to_lowercase();
+            let mut result = String::with_capacity(line.len());
+            let mut previous_word = String::new();
+            let mut chars = line.char_indices().peekable();
+
+            if in_create_body && parenthesis_depth == 1 {
+                field_state = 1;
+            }
+
+            while let Some((i, c)) = chars.next() {
+                let in_string = Self::is_in_string_literal(&line, i as u32);
+
+                if !in_string && (line[i..].starts_with("--") || line[i..].starts_with("//")) {
+                    result.push_str(&line[i..]);
+                    break;
+                }
+
+                if !(c.is_alphanumeric() || c == '_') {
+                    // Closing quote of "Quoted" field name
+                    if c == '"'
+                        && in_string
+                        && in_create_body
+                        && parenthesis_depth == 1
+                        && field_state == 1
+                    {
+                        field_state = 2;
+                    }
+
+                    if !in_string && c != '\'' && c != '"' {
+                        match c {
+                            '(' => {
+                                parenthesis_depth += 1;
+                                if in_create_body && parenthesis_depth == 1 {
+                                    field_state = 1;
+                                }
+                            }
+                            ')' => parenthesis_depth = parenthesis_depth.saturating_sub(1),
+                            '<' => angle_depth += 1,
+                            '>' => angle_depth = angle_depth.saturating_sub(1),
+                            ',' if in_create_body && parenthesis_depth == 1 && angle_depth == 0 => {
+                                field_state = 1;
+                            }
+                            ';' => {
+                                statement_start = true;
+                                in_create_body = false;
+                                parenthesis_depth = 0;
+                                angle_depth = 0;
+                                field_state = 0;
+                            }
+                            _ => {}
+                        }
+                    }
+                    result.push(c);
+                    continue;
+                }
+
+                let mut end = i + c.len_utf8();
+                while let Some((j, next)) = chars.peek() {
+                    if !(next.is_alphanumeric() || *next == '_') {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+
+                let word = &line[i..end];
+                let lw = word.to_lowercase();
+
+                if in_string {
+                    result.push_str(word);
+                    continue;
+                }
+
+                // ks.table, table.column
+                let is_qualified = line[..i].ends_with('.') || line[end..].starts_with('.');
+
+                let is_statement_keyword =
+                    (statement_start || previous_word == "as") && CQL_KEYWORDS_LWC.contains(&lw);
+                let is_type = (angle_depth > 0 || field_state == 2) && CQL_TYPES_LWC.contains(&lw);
+
+                if !is_qualified && (is_statement_keyword || is_type) {
+                    result.push_str(&apply_case(word));
+                } else {
+                    result.push_str(word);
+                }
+
+                if statement_start {
+                    let rest = lw_line[i..].split_whitespace().take(2).collect::<Vec<_>>();
+                    in_create_body = rest.len() == 2
+                        && rest[0] == "create"
+                        && (rest[1] == "table" || rest[1] == "type");
+                }
+
+                if in_create_body && parenthesis_depth == 1 && angle_depth == 0 {
+                    field_state = match field_state {
+                        1 => 2,
+                        _ => 0,
+                    };
+                }
+
+                // BEGIN [UNLOGGED | COUNTER] BATCH is followed by statements
+                statement_start = lw == "batch" && previous_word != "apply";
+                previous_word = lw;
+            }
+
+            lines[index] = result;
+        }
+    }
+
+    /*
+        Indent unit requested by the editor
+
+        tab_size=2, insert_spaces=true -> "  "
+        insert_spaces=false            -> "\t"
+
+        Defaults to four spaces without FormattingOptions e.g on save
+    */
+    pub fn indent_unit(options: Option<&FormattingOptions>) -> String {
+        match options {
+            Some(options) if !options.insert_spaces => "\t".to_string(),
+            Some(options) if options.tab_size > 0 => " ".repeat(options.tab_size as usize),
+            _ => "    ".to_string(),
+        }
+    }
+
+    /*
+        Runs every formatting pass over the lines
+    */
+    pub async fn format_lines(
+        &self,
+        lines: &[&str],
+        document_url: &Url,
+        indent: &str,
+    ) -> Vec<String> {
+        let mut working_vec: Vec<String> = lines.iter().map(|s| s.to_string()).collect();
 
         for index in 0..working_vec.len() {
             working_vec[index] = working_vec[index].trim().to_string();
@@ -699,6 +1062,8 @@ impl Backend {
             self.fix_duplicate_semi_colon(&mut working_vec[index]);
         }
 
+        self.normalize_keyword_case(&mut working_vec);
+
         self.fix_semi_colon(&mut working_vec);
         self.fix_string_literals(&mut working_vec);
         self.fix_new_lines(&mut working_vec);
@@ -706,58 +1071,623 @@ impl Backend {
         self.apply_semi_colon(&mut working_vec);
         self.add_spacing_new_lines(&mut working_vec);
         self.add_spacing_after_comma(&mut working_vec);
-        // self.format_selectors(&mut working_vec);
-        self.add_tabs_to_args(&mut working_vec, document_url).await;
+        if self.formatting_config.format_selectors {
+            self.format_selectors(&mut working_vec, indent);
+        }
+        self.add_tabs_to_args(&mut working_vec, indent);
         self.add_new_line_before_pk(&mut working_vec);
         self.add_tabs_to_cql_types(&mut working_vec);
         self.align_types_inside_create_statement(&mut working_vec, document_url)
             .await;
+        self.format_table_fields(&mut working_vec, indent);
+        self.format_batch_blocks(&mut working_vec, indent);
 
-        let idx = working_vec.len() - 1;
+        working_vec
+    }
 
-        for (index, line) in working_vec.into_iter().enumerate() {
-            let end_char_pos: u32;
+    pub async fn format_file(
+        &self,
+        lines: &Vec<&str>,
+        document_url: &Url,
+        options: Option<&FormattingOptions>,
+    ) -> Vec<TextEdit> {
+        let indent = Self::indent_unit(options);
+        let mut edits = Vec::<TextEdit>::new();
 
-            if index >= lines.len() {
-                end_char_pos = line.len() as u32;
-            } else {
-                end_char_pos = lines[index].len() as u32;
-            }
+        /*
+            Whole document is replaced by a single edit,
+            per line edits break as soon as passes add/remove lines
+        */
+        let original = lines.join("\n");
+
+        // Empty document or blank lines only, nothing to format
+        if original.trim().is_empty() {
+            return edits;
+        }
+
+        let formatted = self
+            .format_lines(lines, document_url, &indent)
+            .await
+            .join("\n");
 
-            let text_edit = TextEdit {
+        if original != formatted {
+            edits.push(TextEdit {
                 range: Range {
                     start: Position {
-                        line: index as u32,
+                        line: 0,
                         character: 0,
                     },
-                    end: Position {
-                        line: index as u32,
-                        character: end_char_pos,
-                    },
+                    end: Self::offset_to_position(&original, original.len()),
                 },
-                new_text: line,
-            };
+                new_text: formatted,
+            });
+        }
 
-            edits.push(text_edit);
+        edits
+    }
+
+    /*
+        Formats lines overlapping the range only
+
+        Selection is widened to whole statements, so passes working
+        across lines (semi colons, blank lines between statements)
+        never cut a statement in half or reach the rest of the document
+    */
+    pub async fn format_range(
+        &self,
+        text: &str,
+        range: &Range,
+        document_url: &Url,
+        options: Option<&FormattingOptions>,
+    ) -> Vec<TextEdit> {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let last_line = lines.len() - 1;
+
+        let mut end_line = (range.end.line as usize).min(last_line);
+        // Selection ending at the start of a line doesn't include it
+        if range.end.character == 0 && range.end.line > range.start.line {
+            end_line = end_line.saturating_sub(1);
         }
+        let mut start_line = (range.start.line as usize).min(end_line);
+
+        let line_offset = |line: usize| {
+            Self::position_to_offset(
+                text,
+                &Position {
+                    line: line as u32,
+                    character: 0,
+                },
+            )
+        };
 
-        if idx < lines.len() {
-            let text_edit = TextEdit {
-                range: Range {
-                    start: Position {
-                        line: idx as u32,
-                        character: lines[idx].len() as u32,
-                    },
-                    end: Position {
-                        line: lines.len() as u32 - 1,
-                        character: lines[lines.len() - 1].len() as u32,
-                    },
+        let selection_start = line_offset(start_line);
+        let selection_end = line_offset(end_line) + lines[end_line].len();
+
+        for (start, end) in Self::statement_ranges(text) {
+            if start < selection_end && end > selection_start {
+                start_line = start_line.min(Self::offset_to_position(text, start).line as usize);
+                end_line = end_line.max(Self::offset_to_position(text, end).line as usize);
+            }
+        }
+
+        let slice = &lines[start_line..=end_line];
+        let indent = Self::indent_unit(options);
+
+        let original = slice.join("\n");
+        let formatted = self
+            .format_lines(slice, document_url, &indent)
+            .await
+            .join("\n");
+
+        if original == formatted {
+            return vec![];
+        }
+
+        vec![TextEdit {
+            range: Range {
+                start: Position {
+                    line: start_line as u32,
+                    character: 0,
                 },
-                new_text: "".to_string(),
-            };
-            edits.push(text_edit);
+                end: Self::offset_to_position(text, line_offset(end_line) + lines[end_line].len()),
+            },
+            new_text: formatted,
+        }]
+    }
+
+    /*
+        Indents the current line while typing CREATE TABLE body
+
+        CREATE TABLE ks.t (   <- ( moves the header to the line start
+            id int,           <- Enter indents one level
+        )                     <- ) dedents
+
+        Only the leading whitespace of the current line is edited
+    */
+    pub async fn format_on_type(
+        &self,
+        document_url: &Url,
+        position: &Position,
+        ch: &str,
+        options: &FormattingOptions,
+    ) -> Option<Vec<TextEdit>> {
+        let text = self.documents.read().await.get(document_url)?.clone();
+        let lines: Vec<String> = text.split('\n').map(String::from).collect();
+
+        let index = position.line as usize;
+        let line = lines.get(index)?;
+        let trimmed = line.trim_start();
+
+        let indent = match ch {
+            "\n" if trimmed.starts_with(')') => String::new(),
+            "\n" if self.is_inside_create_table_no_position(index, &lines) => {
+                Self::indent_unit(Some(options))
+            }
+            ")" if trimmed.starts_with(')')
+                && index > 0
+                && self.is_inside_create_table_no_position(index - 1, &lines) =>
+            {
+                String::new()
+            }
+            "(" if trimmed.to_lowercase().starts_with("create table") => String::new(),
+            _ => return None,
+        };
+
+        let current_indent = &line[..line.len() - trimmed.len()];
+        if current_indent == indent {
+            return None;
         }
 
-        edits
+        Some(vec![TextEdit {
+            range: Range {
+                start: Position {
+                    line: position.line,
+                    character: 0,
+                },
+                end: Position {
+                    line: position.line,
+                    character: current_indent.encode_utf16().count() as u32,
+                },
+            },
+            new_text: indent,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cqlsh::CqlSettings;
+    use crate::lsp::FormattingSettings;
+
+    fn owned(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn selectors_formatted(lines: &[&str]) -> Vec<String> {
+        let service = Backend::test_service();
+        let mut lines = owned(lines);
+        service.inner().format_selectors(&mut lines, "    ");
+        lines
+    }
+
+    fn keyword_cased(lines: &[&str], keyword_case: &str) -> Vec<String> {
+        let service = Backend::test_service_with(
+            CqlSettings::new(),
+            FormattingSettings::from_env("7", "false", keyword_case, "false"),
+        );
+        let mut lines = owned(lines);
+        service.inner().normalize_keyword_case(&mut lines);
+        lines
+    }
+
+    fn comma_spaced(line: &str) -> String {
+        let service = Backend::test_service();
+        let mut lines = vec![line.to_string()];
+        service.inner().add_spacing_after_comma(&mut lines);
+        lines.remove(0)
+    }
+
+    // Document text after the formatting edit
+    async fn format(backend: &Backend, text: &str) -> String {
+        let url = Url::parse("file:///tmp/test.cql").unwrap();
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        match backend.format_file(&lines, &url, None).await.pop() {
+            Some(edit) => edit.new_text,
+            None => text.to_string(),
+        }
+    }
+
+    #[test]
+    fn comma_spacing() {
+        assert_eq!(comma_spaced("SELECT a,b FROM t;"), "SELECT a, b FROM t;");
+        assert_eq!(comma_spaced("SELECT a, b FROM t;"), "SELECT a, b FROM t;");
+        assert_eq!(comma_spaced("VALUES ('だ',1);"), "VALUES ('だ', 1);");
+    }
+
+    #[test]
+    fn comma_spacing_skips_comments_and_literals() {
+        assert_eq!(comma_spaced("-- だ,x"), "-- だ,x");
+        assert_eq!(comma_spaced("-- だよ,x"), "-- だよ,x");
+        assert_eq!(comma_spaced("a,b // 🚀,x"), "a, b // 🚀,x");
+        assert_eq!(comma_spaced("'é,b'"), "'é,b'");
+    }
+
+    #[test]
+    fn table_fields_are_aligned() {
+        let service = Backend::test_service();
+        let mut lines: Vec<String> = [
+            "CREATE TABLE ks.user_sessions (",
+            "user_id uuid,",
+            "    session_id   timeuuid,",
+            "/* device",
+            "   name, */",
+            "last_seen timestamp STATIC,",
+            "tags set<text>,",
+            "PRIMARY KEY ((user_id), session_id)",
+            ");",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+
+        service.inner().format_table_fields(&mut lines, "    ");
+
+        assert_eq!(
+            lines,
+            [
+                "CREATE TABLE ks.user_sessions (",
+                "    user_id    uuid,",
+                "    session_id timeuuid,",
+                "/* device",
+                "   name, */",
+                "    last_seen  timestamp STATIC,",
+                "    tags       set<text>,",
+                "PRIMARY KEY ((user_id), session_id)",
+                ");",
+            ]
+        );
+    }
+
+    #[test]
+    fn single_line_selectors_are_wrapped() {
+        assert_eq!(
+            selectors_formatted(&["SELECT id, count(*), token(a, b) FROM ks.t;"]),
+            [
+                "SELECT",
+                "    id,",
+                "    count(*),",
+                "    token(a, b)",
+                "FROM ks.t;"
+            ]
+        );
+        assert_eq!(
+            selectors_formatted(&["SELECT DISTINCT id, name", "FROM t;"]),
+            ["SELECT DISTINCT", "    id,", "    name", "FROM t;"]
+        );
+    }
+
+    #[test]
+    fn multi_line_selectors_are_kept() {
+        let formatted = ["SELECT", "    id,", "    name", "FROM t;"];
+        assert_eq!(selectors_formatted(&formatted), formatted);
+
+        // Single selector && statement without FROM are left as is
+        let untouched = ["SELECT a, b;", "SELECT * FROM t;"];
+        assert_eq!(selectors_formatted(&untouched), untouched);
+    }
+
+    #[test]
+    fn blank_lines_are_removed_inside_code_blocks_only() {
+        let service = Backend::test_service();
+        let mut lines = owned(&[
+            "-- users",
+            "",
+            "CREATE TABLE t (",
+            "id int,",
+            "",
+            "name text",
+            ");",
+            "",
+            "-- seed",
+            "",
+            "INSERT INTO t (id, name)",
+            "VALUES (1, 'a');",
+        ]);
+
+        service.inner().remove_new_lines_from_code_block(&mut lines);
+
+        assert_eq!(
+            lines,
+            [
+                "-- users",
+                "",
+                "CREATE TABLE t (",
+                "id int,",
+                "name text",
+                ");",
+                "",
+                "-- seed",
+                "",
+                "INSERT INTO t (id, name)",
+                "VALUES (1, 'a');",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn blank_lines_next_to_comments_are_kept() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        let mut lines = owned(&[
+            "-- users",
+            "",
+            "SELECT * FROM t;",
+            "",
+            "",
+            "",
+            "SELECT * FROM u;",
+            "CREATE TABLE t (",
+            "",
+            "-- key",
+            "id int PRIMARY KEY",
+            ");",
+            "/* note",
+            "",
+            "*/",
+            "",
+            "SELECT * FROM v;",
+        ]);
+
+        backend.fix_new_lines(&mut lines);
+
+        assert_eq!(
+            lines,
+            [
+                "-- users",
+                "",
+                "SELECT * FROM t;",
+                "",
+                "SELECT * FROM u;",
+                "CREATE TABLE t (",
+                "",
+                "-- key",
+                "id int PRIMARY KEY",
+                ");",
+                "/* note",
+                "",
+                "*/",
+                "",
+                "SELECT * FROM v;",
+            ]
+        );
+
+        let text = "-- users\n\nSELECT * FROM t;\n\n\n-- seed\n\nINSERT INTO t (id) VALUES (1);\n";
+        let once = format(backend, text).await;
+        assert!(once.starts_with("-- users\n\n"), "{:?}", once);
+        assert!(once.contains("\n-- seed\n\n"), "{:?}", once);
+        assert_eq!(format(backend, &once).await, once);
+    }
+
+    #[tokio::test]
+    async fn edits_are_sorted_and_non_overlapping() {
+        let service = Backend::test_service();
+        let url = Url::parse("file:///tmp/test.cql").unwrap();
+        let text = "select id,name from t\nCREATE TABLE t (\nid int,\n\nname text\n);\n";
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        let edits = service.inner().format_file(&lines, &url, None).await;
+        assert_eq!(edits.len(), 1);
+
+        let end = Backend::offset_to_position(text, text.len());
+        assert_eq!(edits[0].range.start, Position::new(0, 0));
+        assert_eq!(edits[0].range.end, end);
+
+        for pair in edits.windows(2) {
+            let (previous, next) = (pair[0].range.end, pair[1].range.start);
+            assert!((previous.line, previous.character) <= (next.line, next.character));
+        }
+    }
+
+    #[tokio::test]
+    async fn blank_documents_have_no_edits() {
+        let service = Backend::test_service();
+        let url = Url::parse("file:///tmp/test.cql").unwrap();
+
+        for text in ["", "\n", "   ", "\n\n", "\t\n  \n"] {
+            let lines: Vec<&str> = text.split('\n').collect();
+            let edits = service.inner().format_file(&lines, &url, None).await;
+            assert!(edits.is_empty(), "{:?} -> {:?}", text, edits);
+        }
+    }
+
+    #[tokio::test]
+    async fn document_without_trailing_new_line() {
+        let service = Backend::test_service();
+        let url = Url::parse("file:///tmp/test.cql").unwrap();
+        let text = "select * from t";
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        let edits = service.inner().format_file(&lines, &url, None).await;
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 15))
+        );
+        assert_eq!(edits[0].new_text, "select * from t;");
+    }
+
+    #[test]
+    fn keywords_are_normalized_both_ways() {
+        // Only the statement keyword itself, not the words after it
+        let mixed = [
+            "Create table select (",
+            "Select Text, tags Set<Int>",
+            ");",
+            "select Select from t where name = 'select' -- select",
+        ];
+
+        assert_eq!(
+            keyword_cased(&mixed, "upper"),
+            [
+                "CREATE table select (",
+                "Select TEXT, tags SET<INT>",
+                ");",
+                "SELECT Select from t where name = 'select' -- select",
+            ]
+        );
+        assert_eq!(
+            keyword_cased(&mixed, "lower"),
+            [
+                "create table select (",
+                "Select text, tags set<int>",
+                ");",
+                "select Select from t where name = 'select' -- select",
+            ]
+        );
+        assert_eq!(keyword_cased(&mixed, "preserve"), mixed);
+    }
+
+    #[tokio::test]
+    async fn indent_follows_formatting_options() {
+        let service = Backend::test_service_with(
+            CqlSettings::new(),
+            FormattingSettings::from_env("7", "true", "preserve", "false"),
+        );
+        let url = Url::parse("file:///tmp/test.cql").unwrap();
+        let text = "CREATE TABLE t (\nid int,\nname text,\nPRIMARY KEY (id)\n);\n\nSELECT id, name FROM t;";
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        for (options, indent) in [
+            (
+                FormattingOptions {
+                    tab_size: 2,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+                "  ",
+            ),
+            (
+                FormattingOptions {
+                    tab_size: 4,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+                "    ",
+            ),
+            (
+                FormattingOptions {
+                    tab_size: 4,
+                    insert_spaces: false,
+                    ..Default::default()
+                },
+                "\t",
+            ),
+        ] {
+            let edits = service
+                .inner()
+                .format_file(&lines, &url, Some(&options))
+                .await;
+            let formatted = &edits[0].new_text;
+
+            for nested in ["id   int,", "name text,", "id,", "name"] {
+                let expected = format!("\n{}{}\n", indent, nested);
+                assert!(
+                    formatted.contains(&expected),
+                    "{:?} in {:?}",
+                    expected,
+                    formatted
+                );
+            }
+        }
+
+        assert_eq!(Backend::indent_unit(None), "    ");
+    }
+
+    /*
+        Every entry has to come out of the second format unchanged,
+        format_file(format_file(x)) == format_file(x)
+    */
+    const CORPUS: &[&str] = &[
+        "",
+        "\n",
+        "SELECT * FROM t",
+        "select id,name from ks.users where id=1;\n",
+        "SELECT id, name, email FROM ks.users WHERE id = 1 LIMIT 10;\n",
+        "USE ks;\nSELECT * FROM users;\nSELECT * FROM t;;\n",
+        "CREATE TABLE ks.user_sessions (\nuser_id uuid,\nsession_id timeuuid,\nstarted_at timestamp,\ndevice text,\nPRIMARY KEY ((user_id), session_id)\n) WITH CLUSTERING ORDER BY (session_id DESC);\n",
+        "CREATE TABLE t (id int PRIMARY KEY, tags set<text>, attrs map<text, frozen<address>>);\n",
+        "CREATE TYPE ks.address (\nstreet text,\ncity text\n);\n",
+        "INSERT INTO t (id,name) VALUES (1,'a, b');\n",
+        "UPDATE t SET views = views + 1 WHERE id = 1;\n",
+        "BEGIN BATCH\nINSERT INTO t (id) VALUES (1);\nUPDATE t SET a = 2 WHERE id = 1;\nAPPLY BATCH;\n",
+        "/* block\ncomment, with commas */\nSELECT * FROM t;\n",
+        "-- line comment\nSELECT a,b FROM t; // trailing,comment\n",
+        "\n\nSELECT * FROM t;\n\n\n",
+        // Blank lines around comments, see fix_new_lines
+        "-- users\n\nSELECT * FROM t;\n\n-- seed\n\nSELECT * FROM u;\n",
+        "CREATE TABLE t (\n\n-- key\nid int PRIMARY KEY\n);\n",
+        "SELECT * FROM t; -- a\n\n\nSELECT * FROM u;\n",
+        // Multibyte text, see add_spacing_after_comma
+        "-- だ,x\nSELECT a,b FROM t;\n",
+        "-- だよ,x\n",
+        "INSERT INTO t (id,name) VALUES (1,'é,b');\n",
+        "SELECT 'だよ',a FROM t; -- 🚀,x\n",
+        "INSERT INTO t (id,emoji) VALUES (1,'👍🏽,🚀');\n",
+    ];
+
+    #[tokio::test]
+    async fn format_file_is_idempotent() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+
+        for text in CORPUS {
+            let once = format(backend, text).await;
+            let twice = format(backend, &once).await;
+            assert_eq!(once, twice, "{:?}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn counter_batch_stays_contiguous() {
+        let service = Backend::test_service();
+        let text = "BEGIN COUNTER BATCH\nUPDATE stats SET views = views + 1 WHERE id = 1;\n\nUPDATE stats SET likes = likes + 1 WHERE id = 1\nAPPLY BATCH;\nSELECT * FROM stats;\n";
+
+        let formatted = format(service.inner(), text).await;
+
+        assert_eq!(
+            formatted,
+            "BEGIN COUNTER BATCH\n    UPDATE stats SET views = views + 1 WHERE id = 1;\n    UPDATE stats SET likes = likes + 1 WHERE id = 1;\nAPPLY BATCH;\n\nSELECT * FROM stats;\n"
+        );
+        assert_eq!(format(service.inner(), &formatted).await, formatted);
+    }
+
+    #[tokio::test]
+    async fn collection_and_tuple_columns_are_indented() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        let lines = owned(&[
+            "CREATE TABLE ks.places (",
+            "id int,",
+            "tags set<text>,",
+            "coords tuple<int,int>,",
+            "bounds frozen<tuple<int, int>>,",
+            "PRIMARY KEY ((id), tags)",
+            ");",
+        ]);
+
+        let inside: Vec<bool> = (0..lines.len())
+            .map(|index| backend.is_line_inside_init_args(&lines[index], index, &lines))
+            .collect();
+        assert_eq!(inside, [false, true, true, true, true, true, false]);
+
+        let formatted = format(backend, &(lines.join("\n") + "\n")).await;
+        assert_eq!(
+            formatted,
+            "CREATE TABLE ks.places (\n    id     int,\n    tags   set<text>,\n    coords tuple<int, int>,\n    bounds frozen<tuple<int, int>>,\n\n    PRIMARY KEY ((id), tags)\n);\n"
+        );
+        assert_eq!(format(backend, &formatted).await, formatted);
     }
 }
@@ -5,6 +5,132 @@ use crate::cqlsh::*;
 use crate::lsp::Backend;
 
 impl Backend {
+    /*
+        CQL_LSP_OFFLINE routing
+
+        Only completions that don't need the cluster are served,
+        schema-aware ones (keyspaces, tables, columns, ...) are skipped
+    */
+    pub async fn handle_offline_completion(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+        document_url: &Url,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let in_string = Self::is_in_string_literal(line, position.character);
+
+        if self.should_suggest_batch(text, line, position) && !in_string {
+            return self.handle_batch_completion(text, line, position);
+        }
+
+        if self.should_suggest_permissions(line, position) && !in_string {
+            return self.handle_permissions();
+        }
+
+        if self.should_suggest_consistency(line, position) {
+            return self.handle_consistency_completion();
+        }
+
+        if self.should_suggest_index_class(line, position) {
+            return self.handle_index_class_completion(line, position);
+        }
+
+        if self.should_suggest_using_options(line, position) && !in_string {
+            return self.handle_using_options_completion();
+        }
+
+        if self.should_suggest_using_values(line, position) && !in_string {
+            return self.handle_using_values_completion(line, position);
+        }
+
+        if self.should_suggest_create_keywords(line, position) {
+            return self.handle_create_keywords();
+        }
+
+        if self.should_suggest_alter_keywords(line, position) {
+            return self.handle_alter_keywords();
+        }
+
+        if self.should_suggest_drop_keywords(line, position) {
+            return self.handle_drop_keywords();
+        }
+
+        if self.get_describe_clause(line, position) == Some("objects") && !in_string {
+            return self.handle_describe_completion(line, position).await;
+        }
+
+        if self
+            .should_suggest_primary_key_columns(position, text)
+            .await
+            && !in_string
+        {
+            return self
+                .handle_primary_key_columns_completion(position, text)
+                .await;
+        }
+
+        if self
+            .should_suggest_collection_inner_type(line, position, text)
+            .await
+            && !in_string
+        {
+            return self
+                .handle_collection_inner_type_completion(line, position)
+                .await;
+        }
+
+        if self
+            .should_suggest_table_with_options(line, position, text)
+            .await
+            && !in_string
+        {
+            return self.handle_table_with_options();
+        }
+
+        if self
+            .should_suggest_types_completions(line, position, text)
+            .await
+        {
+            return self.handle_types_completion(position, text).await;
+        }
+
+        if self
+            .should_suggest_type_modifiers(line, position, text)
+            .await
+        {
+            return self
+                .handle_type_modifiers_completion(line, position, text)
+                .await;
+        }
+
+        if self.should_suggest_from(line, position) {
+            return self.handle_from_completion();
+        }
+
+        if self.should_suggest_if_not_exists(line, position) {
+            return self.handle_if_not_exists();
+        }
+
+        if self.should_suggest_select_tail_keywords(line, position) && !in_string {
+            return self.handle_select_tail_keywords();
+        }
+
+        if self.should_suggest_limit_value(line, position) && !in_string {
+            return self.handle_limit_value_completion();
+        }
+
+        if self
+            .should_suggest_keywords(text, line, position, document_url)
+            .await
+            && !in_string
+        {
+            return self.handle_keywords_completion();
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
     pub async fn handle_in_string_keyspace_completion(
         &self,
         line: &str,
@@ -85,29 +211,57 @@ impl Backend {
         line: &str,
         position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        self.handle_bare_keyspace_completion(line, position, true)
+            .await
+    }
+
+    pub async fn handle_alter_keyspace_completions(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        self.handle_bare_keyspace_completion(line, position, false)
+            .await
+    }
+
+    /*
+        Keyspace names without the quotes USE needs,
+        only names that require quoting are quoted
+
+        DROP KEYSPACE my|       -> my_keyspace;  (rest of the line is replaced)
+        ALTER KEYSPACE my|      -> my_keyspace   (typed name is replaced)
+    */
+    async fn handle_bare_keyspace_completion(
+        &self,
+        line: &str,
+        position: &Position,
+        terminated: bool,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let start = self.get_start_offset(line, position);
+        let end = match terminated {
+            true => line.len() as u32,
+            false => position.character,
+        };
+
         let mut items = Vec::new();
         for keyspace in self.get_keyspaces().await {
-            let mut index = position.character as usize;
-            while index > 0 {
-                if line.chars().nth(index).unwrap_or_else(|| '_') == ' ' {
-                    index += 1;
-                    break;
-                }
-                index -= 1;
-            }
+            let name = Self::quote_identifier(&keyspace);
 
             let text_edit = TextEdit {
                 range: Range {
                     start: Position {
                         line: position.line,
-                        character: index as u32,
+                        character: start,
                     },
                     end: Position {
                         line: position.line,
-                        character: line.len() as u32,
+                        character: end,
                     },
                 },
-                new_text: format!("{};", keyspace),
+                new_text: match terminated {
+                    true => format!("{};", name),
+                    false => name,
+                },
             };
 
             items.push(CompletionItem {
@@ -118,10 +272,10 @@ impl Backend {
             });
         }
 
-        if !items.is_empty() {
-            return Ok(Some(CompletionResponse::Array(items)));
-        }
-        Ok(Some(CompletionResponse::Array(vec![])))
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
     pub async fn handle_out_of_string_keyspace_completion(
@@ -162,144 +316,872 @@ impl Backend {
             });
         }
 
-        if !items.is_empty() {
-            return Ok(Some(CompletionResponse::Array(items)));
-        }
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
+    }
+
+    pub fn handle_keywords_completion(
+        &self,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        return Ok(Some(CompletionResponse::Array(
+            KEYWORDS.iter().cloned().collect(),
+        )));
+    }
+
+    /*
+        Native types for collection parameters
+
+        counter can't be used inside of collections, bare collection
+        keywords are replaced by their <> snippets
+
+        frozen<| also suggests user defined types of the current keyspace
+    */
+    pub async fn handle_collection_inner_type_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let mut items: Vec<CompletionItem> = TYPES
+            .iter()
+            .filter(|item| {
+                !matches!(
+                    item.label.to_lowercase().as_str(),
+                    "counter" | "list" | "map" | "set" | "tuple"
+                )
+            })
+            .cloned()
+            .collect();
+
+        let Some((collection, index)) = self.get_collection_type_slot(prefix) else {
+            return Ok(Some(CompletionResponse::Array(items)));
+        };
+
+        if collection == "frozen" {
+            let keyspace = self.latest_keyspace(position).await;
+            let types = self.unwrap_query(query_types(&self.config())).await;
+
+            for item in types {
+                let label = match keyspace {
+                    Some(ref keyspace) if item.keyspace_name == *keyspace => item.type_name.clone(),
+                    Some(_) => continue,
+                    None => format!("{}.{}", item.keyspace_name, item.type_name),
+                };
+
+                items.insert(
+                    0,
+                    CompletionItem {
+                        label: label.clone(),
+                        kind: Some(CompletionItemKind::STRUCT),
+                        detail: Some(format!(
+                            "User defined type | {}.{}",
+                            item.keyspace_name, item.type_name
+                        )),
+                        insert_text: Some(label),
+                        insert_text_format: Some(InsertTextFormat::SNIPPET),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        let is_fresh = prefix.trim_end().ends_with('<');
+
+        if collection == "map" && index == 0 && is_fresh {
+            items.insert(
+                0,
+                CompletionItem {
+                    label: "map<key, value>".to_string(),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some("Key && value types of the map".to_string()),
+                    insert_text: Some(r#"${1:text}, ${2:text}"#.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                },
+            );
+        }
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    /*
+        Native types followed by user defined types of the table keyspace
+
+        UDT columns are frozen in most cases, so frozen<udt>
+        is suggested along with the plain type name
+    */
+    pub async fn handle_types_completion(
+        &self,
+        position: &Position,
+        text: &str,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let mut items: Vec<CompletionItem> = TYPES.iter().cloned().collect();
+
+        let Some(keyspace) = self.create_table_keyspace(position, text).await else {
+            return Ok(Some(CompletionResponse::Array(items)));
+        };
+
+        let types = self.unwrap_query(query_types(&self.config())).await;
+
+        for item in types {
+            if item.keyspace_name != keyspace
+                || items
+                    .iter()
+                    .any(|native| native.label.eq_ignore_ascii_case(&item.type_name))
+            {
+                continue;
+            }
+
+            for label in [
+                item.type_name.clone(),
+                format!("frozen<{}>", item.type_name),
+            ] {
+                items.push(CompletionItem {
+                    label: label.clone(),
+                    kind: Some(CompletionItemKind::STRUCT),
+                    detail: Some(format!(
+                        "User defined type | {}.{}",
+                        item.keyspace_name, item.type_name
+                    )),
+                    insert_text: Some(label),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    /*
+        PRIMARY KEY isn't offered once another column of the
+        buffer's CREATE TABLE was declared as the key
+    */
+    pub async fn handle_type_modifiers_completion(
+        &self,
+        line: &str,
+        position: &Position,
+        text: &str,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if line.to_lowercase().contains("primary") {
+            return Ok(Some(CompletionResponse::Array(vec![
+                CompletionItem {
+                    label: "KEY".to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some("Upper case KEY type modifier".to_string()),
+                    documentation: Some(Documentation::String("KEY type modifier".to_string())),
+                    insert_text: Some(r#"KEY"#.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                },
+                CompletionItem {
+                    label: "key".to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some("Lower case key type modifier".to_string()),
+                    documentation: Some(Documentation::String("key type modifier".to_string())),
+                    insert_text: Some(r#"key"#.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                },
+            ])));
+        }
+
+        let mut items = vec![
+            CompletionItem {
+                label: "PRIMARY KEY".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Upper case PRIMARY KEY type modifier".to_string()),
+                documentation: Some(Documentation::String(
+                    "PRIMARY KEY type modifier".to_string(),
+                )),
+                insert_text: Some(r#"PRIMARY KEY"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "primary key".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Lower case primary key type modifier".to_string()),
+                documentation: Some(Documentation::String(
+                    "primary key type modifier".to_string(),
+                )),
+                insert_text: Some(r#"primary key"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "STATIC".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Upper case STATIC type modifier".to_string()),
+                documentation: Some(Documentation::String("STATIC type modifier".to_string())),
+                insert_text: Some(r#"STATIC"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "static".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("Lower case static type modifier".to_string()),
+                documentation: Some(Documentation::String("static type modifier".to_string())),
+                insert_text: Some(r#"static"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+        ];
+
+        if self.has_inline_primary_key(text, position).await {
+            items.retain(|item| !item.label.eq_ignore_ascii_case("primary key"));
+        }
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    /*
+        Documentation of the highlighted item, see resolve_data
+
+        Column documentation is built from the payload itself,
+        CREATE TABLE of a table is queried from the cluster
+    */
+    pub async fn handle_completion_resolve(&self, mut item: CompletionItem) -> CompletionItem {
+        let Some(data) = item.data.as_ref() else {
+            return item;
+        };
+
+        let (Some(keyspace), Some(table)) = (data["keyspace"].as_str(), data["table"].as_str())
+        else {
+            return item;
+        };
+
+        let documentation = match data["column"].as_str() {
+            Some(column) => format!(
+                "```cql\n{} {}\n```\n{} column of {}.{}",
+                Self::quote_identifier(column),
+                data["type"].as_str().unwrap_or_default(),
+                data["kind"].as_str().unwrap_or_default().replace('_', " "),
+                keyspace,
+                table
+            ),
+            None => {
+                if self.config().offline {
+                    return item;
+                }
+
+                let statement = self
+                    .unwrap_query(async {
+                        describe_table(&self.config(), keyspace, table)
+                            .await
+                            .map(|statement| vec![statement])
+                    })
+                    .await;
+
+                let Some(statement) = statement.first() else {
+                    return item;
+                };
+
+                format!("```cql\n{}\n```", statement)
+            }
+        };
+
+        item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: documentation,
+        }));
+
+        item
+    }
+
+    pub async fn handle_fields_completion(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let mut items = match self.get_fields(text, line, position).await {
+            Ok(Some(CompletionResponse::Array(items))) => items,
+            _ => vec![],
+        };
+
+        items.extend(self.get_udf_completions(text, position).await);
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub async fn handle_where_columns_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_where_columns(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_allow_filtering_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_allow_filtering(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub fn handle_limit_value_completion(
+        &self,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        Ok(Some(CompletionResponse::Array(vec![CompletionItem {
+            label: "row_count".to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some("Maximum number of returned rows".to_string()),
+            insert_text: Some(r#"${1:100}$0"#.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        }])))
+    }
+
+    pub fn handle_consistency_completion(
+        &self,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        Ok(Some(CompletionResponse::Array(
+            CONSISTENCY_LEVELS.iter().cloned().collect(),
+        )))
+    }
+
+    pub fn handle_using_options_completion(
+        &self,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        Ok(Some(CompletionResponse::Array(
+            USING_OPTIONS.iter().cloned().collect(),
+        )))
+    }
+
+    pub fn handle_using_values_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let items = match self.get_using_option(prefix) {
+            Some("ttl") => USING_TTL_VALUES.iter().cloned().collect(),
+            Some("timestamp") => USING_TIMESTAMP_VALUES.iter().cloned().collect(),
+            _ => vec![],
+        };
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub fn handle_value_template_completion(
+        &self,
+        column: &Column,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        Ok(Some(CompletionResponse::Array(
+            self.get_value_templates(column),
+        )))
+    }
+
+    /*
+        Plain keywords stay available next to the batch ones,
+        DML statements are what goes inside of a batch
+    */
+    pub fn handle_batch_completion(
+        &self,
+        text: &str,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let mut items: Vec<CompletionItem> = match self.get_batch_clause(text, line, position) {
+            Some("begin") => BATCH_BEGIN_VARIANTS.iter().cloned().collect(),
+            Some("apply") => BATCH_APPLY.iter().cloned().collect(),
+            _ => vec![],
+        };
+
+        items.extend(KEYWORDS.iter().cloned());
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub fn handle_permissions(&self) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        Ok(Some(CompletionResponse::Array(
+            PERMISSIONS.iter().cloned().collect(),
+        )))
+    }
+
+    pub async fn handle_roles_completion(
+        &self,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let r = self.unwrap_query(query_roles(&self.config())).await;
+
+        let mut items = Vec::<CompletionItem>::new();
+
+        for item in r {
+            items.push(CompletionItem {
+                label: item.name.clone(),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: Some("Role".to_string()),
+                insert_text: Some(item.name),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub async fn handle_udt_fields_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_udt_fields(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_insert_columns_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_insert_columns(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_update_set_columns_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_update_set_columns(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    /*
+        Inside of the quotes only the typed part of the class is replaced
+
+        USING |         -> 'sai'
+        USING 'org.|    -> 'org.apache.cassandra.index.sasi.SASIIndex
+    */
+    pub fn handle_index_class_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if self.get_index_class_clause(line, position) == Some("options") {
+            return Ok(Some(CompletionResponse::Array(
+                INDEX_OPTIONS.iter().cloned().collect(),
+            )));
+        }
+
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+        let quote = prefix
+            .rfind('\'')
+            .filter(|_| Self::is_in_string_literal(line, position.character));
+
+        let items = INDEX_CLASSES
+            .iter()
+            .map(|(class, detail)| {
+                let (start, new_text) = match quote {
+                    Some(quote) => (quote as u32 + 1, class.to_string()),
+                    None => (
+                        self.get_start_offset(line, position),
+                        format!("'{}'", class),
+                    ),
+                };
+
+                CompletionItem {
+                    label: class.to_string(),
+                    kind: Some(CompletionItemKind::CLASS),
+                    detail: Some(detail.to_string()),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                        range: Range {
+                            start: Position {
+                                line: position.line,
+                                character: start,
+                            },
+                            end: *position,
+                        },
+                        new_text,
+                    })),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub async fn handle_index_columns_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_index_columns(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_alter_table_ops_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_alter_table_ops(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_order_by_columns_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(response) = self
+            .get_order_by_columns(line, position)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(response));
+        }
+
         Ok(Some(CompletionResponse::Array(vec![])))
     }
 
-    pub fn handle_keywords_completion(
+    pub fn handle_table_with_options(
         &self,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        return Ok(Some(CompletionResponse::Array(
-            KEYWORDS.iter().cloned().collect(),
-        )));
+        Ok(Some(CompletionResponse::Array(
+            TABLE_WITH_OPTIONS.iter().cloned().collect(),
+        )))
     }
 
-    pub fn handle_types_completion(
+    /*
+        ALTER TABLE ks.t WITH |
+
+        Options are prefilled with current values of the table,
+        CREATE TABLE && unknown tables get the default snippets
+    */
+    pub async fn handle_alter_table_with_options(
         &self,
+        position: &Position,
+        text: &str,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        return Ok(Some(CompletionResponse::Array(
-            TYPES.iter().cloned().collect(),
-        )));
+        let Some((keyspace, table)) = self.get_alter_table_target(position, text) else {
+            return self.handle_table_with_options();
+        };
+
+        let Some(keyspace) = keyspace.or(self.latest_keyspace(position).await) else {
+            return self.handle_table_with_options();
+        };
+
+        let options = self
+            .unwrap_query(async {
+                query_tables_with_options(&self.config(), &keyspace, &table)
+                    .await
+                    .map(|options| options.into_iter().collect())
+            })
+            .await;
+
+        let Some(options) = options.first() else {
+            return self.handle_table_with_options();
+        };
+
+        let literals = self.table_option_literals(options);
+
+        let items = TABLE_WITH_OPTIONS
+            .iter()
+            .map(|item| {
+                let Some((name, literal)) = literals.iter().find(|(name, _)| *name == item.label)
+                else {
+                    return item.clone();
+                };
+
+                // Snippet placeholder, $ } && \ have to be escaped
+                let placeholder = literal
+                    .replace('\\', "\\\\")
+                    .replace('$', "\\$")
+                    .replace('}', "\\}");
+
+                CompletionItem {
+                    detail: Some(format!("Current value: {}", literal)),
+                    insert_text: Some(format!("{} = ${{1:{}}}", name, placeholder)),
+                    ..item.clone()
+                }
+            })
+            .collect();
+
+        Ok(Some(CompletionResponse::Array(items)))
     }
 
-    pub fn handle_type_modifiers_completion(
+    /*
+        Clauses allowed after SELECT ... FROM table,
+        listed in the order they appear in a statement
+
+        BYPASS CACHE && USING TIMEOUT are Scylla only,
+        offered with CQL_LSP_DIALECT=scylla
+    */
+    pub fn handle_select_tail_keywords(
         &self,
-        line: &str,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        if line.to_lowercase().contains("primary") {
-            return Ok(Some(CompletionResponse::Array(vec![
-                CompletionItem {
-                    label: "KEY".to_string(),
-                    kind: Some(CompletionItemKind::KEYWORD),
-                    detail: Some("Upper case KEY type modifier".to_string()),
-                    documentation: Some(Documentation::String("KEY type modifier".to_string())),
-                    insert_text: Some(r#"KEY"#.to_string()),
-                    insert_text_format: Some(InsertTextFormat::SNIPPET),
-                    ..Default::default()
-                },
-                CompletionItem {
-                    label: "key".to_string(),
+        let mut items = Vec::<CompletionItem>::new();
+
+        let mut clauses = vec![
+            ("WHERE", "Restricts selected rows"),
+            ("GROUP BY", "Groups rows by primary key columns"),
+            ("ORDER BY", "Orders rows by clustering columns"),
+            ("PER PARTITION LIMIT", "Limits rows returned per partition"),
+            ("LIMIT", "Limits rows returned by the query"),
+            ("ALLOW FILTERING", "Allows queries that require filtering"),
+        ];
+
+        if self.config().dialect == Dialect::Scylla {
+            clauses.extend([
+                ("BYPASS CACHE", "Reads without populating the row cache"),
+                (
+                    "USING TIMEOUT",
+                    "Server side timeout of the query e.g 200ms",
+                ),
+            ]);
+        }
+
+        for (index, (keyword, detail)) in clauses.iter().enumerate() {
+            for keyword in [keyword.to_string(), keyword.to_lowercase()] {
+                items.push(CompletionItem {
+                    label: keyword.clone(),
                     kind: Some(CompletionItemKind::KEYWORD),
-                    detail: Some("Lower case key type modifier".to_string()),
-                    documentation: Some(Documentation::String("key type modifier".to_string())),
-                    insert_text: Some(r#"key"#.to_string()),
+                    detail: Some(detail.to_string()),
+                    insert_text: Some(format!("{} $0", keyword)),
                     insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    sort_text: Some(format!("{}_{}", index, keyword)),
                     ..Default::default()
-                },
-            ])));
+                });
+            }
         }
 
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    pub fn handle_from_completion(&self) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
         return Ok(Some(CompletionResponse::Array(vec![
             CompletionItem {
-                label: "PRIMARY KEY".to_string(),
-                kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Upper case PRIMARY KEY type modifier".to_string()),
-                documentation: Some(Documentation::String(
-                    "PRIMARY KEY type modifier".to_string(),
-                )),
-                insert_text: Some(r#"PRIMARY KEY"#.to_string()),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            },
-            CompletionItem {
-                label: "primary key".to_string(),
-                kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Lower case primary key type modifier".to_string()),
-                documentation: Some(Documentation::String(
-                    "primary key type modifier".to_string(),
-                )),
-                insert_text: Some(r#"primary key"#.to_string()),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            },
-            CompletionItem {
-                label: "STATIC".to_string(),
+                label: "FROM".to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Upper case STATIC type modifier".to_string()),
-                documentation: Some(Documentation::String("STATIC type modifier".to_string())),
-                insert_text: Some(r#"STATIC"#.to_string()),
+                detail: Some("Upper case FROM keyword".to_string()),
+                documentation: Some(Documentation::String("FROM keyword".to_string())),
+                insert_text: Some(r#"FROM $0"#.to_string()),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             },
             CompletionItem {
-                label: "static".to_string(),
+                label: "from".to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Lower case static type modifier".to_string()),
-                documentation: Some(Documentation::String("static type modifier".to_string())),
-                insert_text: Some(r#"static"#.to_string()),
+                detail: Some("Lower case from keyword".to_string()),
+                documentation: Some(Documentation::String("FROM keyword".to_string())),
+                insert_text: Some(r#"from $0"#.to_string()),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             },
         ])));
     }
 
-    pub async fn handle_fields_completion(
+    pub async fn handle_table_completion(
         &self,
         line: &str,
         position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        if let Some(response) = self
-            .get_fields(line, position)
+        if let Some(tables) = self
+            .get_table_completions(line, position)
             .await
             .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
         {
-            return Ok(Some(response));
+            return Ok(Some(tables));
         }
 
-        return Ok(Some(CompletionResponse::Array(vec![])));
+        Ok(Some(CompletionResponse::Array(vec![])))
     }
 
-    pub fn handle_from_completion(&self) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        return Ok(Some(CompletionResponse::Array(vec![
+    /*
+        TABLE keyword is optional, TRUNCATE ks.users is valid as well
+    */
+    pub async fn handle_truncate_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let mut items = vec![
             CompletionItem {
-                label: "FROM".to_string(),
+                label: "TABLE".to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Upper case FROM keyword".to_string()),
-                documentation: Some(Documentation::String("FROM keyword".to_string())),
-                insert_text: Some(r#"FROM $0"#.to_string()),
+                insert_text: Some("TABLE $0".to_string()),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             },
             CompletionItem {
-                label: "from".to_string(),
+                label: "table".to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
-                detail: Some("Lower case from keyword".to_string()),
-                documentation: Some(Documentation::String("FROM keyword".to_string())),
-                insert_text: Some(r#"from $0"#.to_string()),
+                insert_text: Some("table $0".to_string()),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             },
-        ])));
+        ];
+
+        if let Ok(Some(CompletionResponse::Array(mut tables))) =
+            self.get_table_completions(line, position).await
+        {
+            items.append(&mut tables);
+        }
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
-    pub async fn handle_table_completion(
+    /*
+        DESCRIBE |              -> object keywords
+        DESCRIBE KEYSPACE |     -> keyspaces
+        DESCRIBE TABLE |        -> tables
+        DESCRIBE TYPE |         -> types
+    */
+    pub async fn handle_describe_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        match self.get_describe_clause(line, position) {
+            Some("keyspace") => {
+                self.handle_bare_keyspace_completion(line, position, true)
+                    .await
+            }
+            Some("table") => self.handle_table_completion(line, position).await,
+            Some("type") => self.handle_describe_type_completion(line, position).await,
+            _ => Ok(Some(CompletionResponse::Array(
+                DESCRIBE_OBJECTS.iter().cloned().collect(),
+            ))),
+        }
+    }
+
+    /*
+        Types of the latest USE keyspace are inserted without keyspace
+    */
+    async fn handle_describe_type_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let keyspace = self.latest_keyspace(position).await;
+        let types = self.unwrap_query(query_types(&self.config())).await;
+
+        let items = types
+            .iter()
+            .map(|item| {
+                let scoped = keyspace.as_deref() == Some(item.keyspace_name.as_str());
+
+                CompletionItem {
+                    label: match scoped {
+                        true => item.type_name.clone(),
+                        false => format!("{}.{}", item.keyspace_name, item.type_name),
+                    },
+                    kind: Some(CompletionItemKind::STRUCT),
+                    insert_text: Some(match scoped {
+                        true => Self::quote_identifier(&item.type_name),
+                        false => Self::quote_qualified_name(&item.keyspace_name, &item.type_name),
+                    }),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
+    }
+
+    pub async fn handle_primary_key_columns_completion(
+        &self,
+        position: &Position,
+        text: &str,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(columns) = self
+            .get_primary_key_columns(position, text)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(columns));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_view_columns_completion(
+        &self,
+        position: &Position,
+        text: &str,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        if let Some(columns) = self
+            .get_view_columns(position, text)
+            .await
+            .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
+        {
+            return Ok(Some(columns));
+        }
+
+        Ok(Some(CompletionResponse::Array(vec![])))
+    }
+
+    pub async fn handle_dot_scoped_tables_completion(
         &self,
+        line: &str,
         position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
         if let Some(tables) = self
-            .get_table_completions(position)
+            .get_dot_scoped_tables(line, position)
             .await
             .unwrap_or_else(|_| Some(CompletionResponse::Array(vec![])))
         {
@@ -309,6 +1191,119 @@ impl Backend {
         Ok(Some(CompletionResponse::Array(vec![])))
     }
 
+    pub async fn handle_replication_options_completion(
+        &self,
+        line: &str,
+        position: &Position,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        if self.is_replication_class_value(prefix) {
+            let quoted = prefix.trim_end().ends_with('\'');
+            let mut items = Vec::<CompletionItem>::new();
+
+            for strategy in ["SimpleStrategy", "NetworkTopologyStrategy"] {
+                items.push(CompletionItem {
+                    label: strategy.to_string(),
+                    kind: Some(CompletionItemKind::VALUE),
+                    detail: Some("Replication strategy".to_string()),
+                    insert_text: Some(match quoted {
+                        true => format!("{}'", strategy),
+                        false => format!("'{}'", strategy),
+                    }),
+                    ..Default::default()
+                });
+            }
+
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        let datacenters = self.get_datacenters().await;
+
+        // Placeholder datacenter is used if cluster isn't reachable
+        let network_topology = match datacenters.is_empty() {
+            true => r#"'class': 'NetworkTopologyStrategy', '${1:datacenter1}': ${2:3}"#.to_string(),
+            false => {
+                let dcs: Vec<String> = datacenters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, dc)| format!("'{}': ${{{}:3}}", dc, i + 1))
+                    .collect();
+                format!("'class': 'NetworkTopologyStrategy', {}", dcs.join(", "))
+            }
+        };
+
+        let mut items = vec![
+            CompletionItem {
+                label: "SimpleStrategy".to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some("SimpleStrategy replication map".to_string()),
+                documentation: Some(Documentation::String(
+                    "Single datacenter replication, not recommended for production".to_string(),
+                )),
+                insert_text: Some(
+                    r#"'class': 'SimpleStrategy', 'replication_factor': ${1:3}"#.to_string(),
+                ),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "NetworkTopologyStrategy".to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some("NetworkTopologyStrategy replication map".to_string()),
+                documentation: Some(Documentation::String(
+                    "Replication factor per datacenter".to_string(),
+                )),
+                insert_text: Some(network_topology),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "'class'".to_string(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                insert_text: Some(r#"'class': '$0'"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "'replication_factor'".to_string(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                insert_text: Some(r#"'replication_factor': ${1:3}"#.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            },
+        ];
+
+        // Opening quote of the key is already typed, {'class': '...', '|
+        let quoted = prefix.trim_end().ends_with('\'');
+
+        let detail = match datacenters.is_empty() {
+            true => "Datacenter placeholder, cluster isn't reachable",
+            false => "Datacenter",
+        };
+
+        let datacenters = match datacenters.is_empty() {
+            true => vec!["datacenter1".to_string()],
+            false => datacenters,
+        };
+
+        for dc in datacenters {
+            items.push(CompletionItem {
+                label: format!("'{}'", dc),
+                kind: Some(CompletionItemKind::PROPERTY),
+                detail: Some(detail.to_string()),
+                insert_text: Some(match quoted {
+                    true => format!("{}': ${{1:3}}", dc),
+                    false => format!("'{}': ${{1:3}}", dc),
+                }),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+        }
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
     pub async fn handle_out_of_string_graph_engine_completion(
         &self,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
@@ -1032,136 +2027,212 @@ impl Backend {
         Ok(Some(CompletionResponse::Array(items)))
     }
 
+    /*
+        keyspace.name of the dropped object
+
+        Objects of the latest USE keyspace are inserted without
+        keyspace unless CQL_LSP_QUALIFIED_DROP is enabled
+    */
+    pub fn drop_object_completion(
+        &self,
+        keyspace_name: &str,
+        name: &str,
+        scoped: bool,
+    ) -> CompletionItem {
+        let qualified = format!("{}.{}", keyspace_name, name);
+
+        let insert_text = if scoped && !self.config().qualified_drop {
+            Self::quote_identifier(name)
+        } else {
+            Self::quote_qualified_name(keyspace_name, name)
+        };
+
+        CompletionItem {
+            label: qualified,
+            kind: Some(CompletionItemKind::VALUE),
+            insert_text: Some(insert_text),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        }
+    }
+
+    /*
+        Aggregates of the latest USE keyspace only,
+        every aggregate of the cluster without USE
+    */
     pub async fn handle_drop_aggregate_completions(
         &self,
+        line: &str,
+        position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        let rq = query_aggregates(&self.config).await;
-
-        match rq {
-            Ok(r) => {
-                let mut items = Vec::<CompletionItem>::new();
-
-                for item in r {
-                    items.push(CompletionItem {
-                        label: format!("{}.{}", item.keyspace_name, item.aggregate_name),
-                        kind: Some(CompletionItemKind::VALUE),
-                        insert_text: Some(format!(
-                            "{}.{}",
-                            item.keyspace_name, item.aggregate_name
-                        )),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
+        let keyspace = self.latest_keyspace(position).await;
 
-                return Ok(Some(CompletionResponse::Array(items)));
+        let r = match &keyspace {
+            Some(keyspace) => {
+                self.unwrap_query(query_keyspace_scoped_aggregates(&self.config(), keyspace))
+                    .await
             }
-
-            Err(_) => return Ok(Some(CompletionResponse::Array(vec![]))),
-        }
+            None => self.unwrap_query(query_aggregates(&self.config())).await,
+        };
+
+        let items = r
+            .iter()
+            .map(|item| {
+                self.drop_object_completion(
+                    &item.keyspace_name,
+                    &item.aggregate_name,
+                    keyspace.is_some(),
+                )
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
+    /*
+        Functions of the latest USE keyspace only,
+        every function of the cluster without USE
+    */
     pub async fn handle_drop_function_completions(
         &self,
+        line: &str,
+        position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        let rq = query_functions(&self.config).await;
-
-        match rq {
-            Ok(r) => {
-                let mut items = Vec::<CompletionItem>::new();
-
-                for item in r {
-                    items.push(CompletionItem {
-                        label: format!("{}.{}", item.keyspace_name, item.function_name),
-                        kind: Some(CompletionItemKind::VALUE),
-                        insert_text: Some(format!("{}.{}", item.keyspace_name, item.function_name)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
+        let keyspace = self.latest_keyspace(position).await;
 
-                return Ok(Some(CompletionResponse::Array(items)));
+        let r = match &keyspace {
+            Some(keyspace) => {
+                self.unwrap_query(query_keyspace_scoped_functions(&self.config(), keyspace))
+                    .await
             }
-
-            Err(_) => return Ok(Some(CompletionResponse::Array(vec![]))),
-        }
+            None => self.unwrap_query(query_functions(&self.config())).await,
+        };
+
+        let items = r
+            .iter()
+            .map(|item| {
+                self.drop_object_completion(
+                    &item.keyspace_name,
+                    &item.function_name,
+                    keyspace.is_some(),
+                )
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
+    /*
+        Indexes of the latest USE keyspace only,
+        every index of the cluster without USE
+    */
     pub async fn handle_drop_index_completions(
         &self,
+        line: &str,
+        position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        let rq = query_indexes(&self.config).await;
-
-        match rq {
-            Ok(r) => {
-                let mut items = Vec::<CompletionItem>::new();
+        let keyspace = self.latest_keyspace(position).await;
 
-                for item in r {
-                    items.push(CompletionItem {
-                        label: format!("{}.{}", item.keyspace_name, item.index_name),
-                        kind: Some(CompletionItemKind::VALUE),
-                        insert_text: Some(format!("{}.{}", item.keyspace_name, item.index_name)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
-
-                return Ok(Some(CompletionResponse::Array(items)));
+        let r = match &keyspace {
+            Some(keyspace) => {
+                self.unwrap_query(query_keyspace_scoped_indexes(&self.config(), keyspace))
+                    .await
             }
-
-            Err(_) => return Ok(Some(CompletionResponse::Array(vec![]))),
-        }
+            None => self.unwrap_query(query_indexes(&self.config())).await,
+        };
+
+        let items = r
+            .iter()
+            .map(|item| {
+                self.drop_object_completion(
+                    &item.keyspace_name,
+                    &item.index_name,
+                    keyspace.is_some(),
+                )
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
+    /*
+        Types of the latest USE keyspace only,
+        every type of the cluster without USE
+    */
     pub async fn handle_drop_type_completions(
         &self,
+        line: &str,
+        position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        let rq = query_types(&self.config).await;
+        let keyspace = self.latest_keyspace(position).await;
 
-        match rq {
-            Ok(r) => {
-                let mut items = Vec::<CompletionItem>::new();
-
-                for item in r {
-                    items.push(CompletionItem {
-                        label: format!("{}.{}", item.keyspace_name, item.type_name),
-                        kind: Some(CompletionItemKind::VALUE),
-                        insert_text: Some(format!("{}.{}", item.keyspace_name, item.type_name)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
-
-                return Ok(Some(CompletionResponse::Array(items)));
+        let r = match &keyspace {
+            Some(keyspace) => {
+                self.unwrap_query(query_keyspace_scoped_types(&self.config(), keyspace))
+                    .await
             }
-
-            Err(_) => return Ok(Some(CompletionResponse::Array(vec![]))),
-        }
+            None => self.unwrap_query(query_types(&self.config())).await,
+        };
+
+        let items = r
+            .iter()
+            .map(|item| {
+                self.drop_object_completion(
+                    &item.keyspace_name,
+                    &item.type_name,
+                    keyspace.is_some(),
+                )
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 
+    /*
+        Views of the latest USE keyspace only,
+        every view of the cluster without USE
+    */
     pub async fn handle_drop_view_completions(
         &self,
+        line: &str,
+        position: &Position,
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        let rq = query_views(&self.config).await;
-
-        match rq {
-            Ok(r) => {
-                let mut items = Vec::<CompletionItem>::new();
-
-                for item in r {
-                    items.push(CompletionItem {
-                        label: format!("{}.{}", item.keyspace_name, item.view_name),
-                        kind: Some(CompletionItemKind::VALUE),
-                        insert_text: Some(format!("{}.{}", item.keyspace_name, item.view_name)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
+        let keyspace = self.latest_keyspace(position).await;
 
-                return Ok(Some(CompletionResponse::Array(items)));
+        let r = match &keyspace {
+            Some(keyspace) => {
+                self.unwrap_query(query_keyspace_scoped_views(&self.config(), keyspace))
+                    .await
             }
-
-            Err(_) => return Ok(Some(CompletionResponse::Array(vec![]))),
-        }
+            None => self.unwrap_query(query_views(&self.config())).await,
+        };
+
+        let items = r
+            .iter()
+            .map(|item| {
+                self.drop_object_completion(
+                    &item.keyspace_name,
+                    &item.view_name,
+                    keyspace.is_some(),
+                )
+            })
+            .collect();
+
+        let typed = Self::typed_identifier(line, position);
+        Ok(Some(CompletionResponse::Array(Self::filter_by_typed(
+            items, &typed,
+        ))))
     }
 }
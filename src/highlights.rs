@@ -0,0 +1,262 @@
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::lsp::Backend;
+
+/*
+    Document highlights for tables and columns
+
+    Tables are matched across every statement of the file,
+    columns only across statements of the same table
+
+    SELECT name FROM ks.users;           -> name READ
+    UPDATE users SET name = 'x' ...;     -> name WRITE
+*/
+impl Backend {
+    pub async fn get_document_highlights(
+        &self,
+        uri: &Url,
+        position: &Position,
+    ) -> Vec<DocumentHighlight> {
+        let Some(text) = self.documents.read().await.get(uri).cloned() else {
+            return vec![];
+        };
+        let Some(tree) = self.tree_for(uri).await else {
+            return vec![];
+        };
+
+        let root = tree.root_node();
+        let offset = Self::position_to_offset(&text, position);
+
+        // Cursor right after the identifier e.g users|
+        let Some(node) = [offset, offset.saturating_sub(1)]
+            .into_iter()
+            .filter_map(|offset| root.descendant_for_byte_range(offset, offset))
+            .find(|node| node.kind() == "identifier")
+        else {
+            return vec![];
+        };
+
+        if Self::is_in_comment_or_string(node) {
+            return vec![];
+        }
+
+        let mut cursor = root.walk();
+        let commands: Vec<Node> = root
+            .children(&mut cursor)
+            .filter(|command| command.kind() == "cql_commands")
+            .collect();
+
+        let occurrences = match Self::is_table_reference(node) {
+            true => Self::table_occurrences(node, &commands, &text),
+            false => Self::column_occurrences(node, &commands, &text),
+        };
+
+        occurrences
+            .into_iter()
+            .map(|(node, kind)| DocumentHighlight {
+                range: Range {
+                    start: Self::offset_to_position(&text, node.start_byte()),
+                    end: Self::offset_to_position(&text, node.end_byte()),
+                },
+                kind: Some(kind),
+            })
+            .collect()
+    }
+
+    fn table_occurrences<'a>(
+        node: Node,
+        commands: &[Node<'a>],
+        text: &str,
+    ) -> Vec<(Node<'a>, DocumentHighlightKind)> {
+        let Some(name) = Self::referenced_name(node, text) else {
+            return vec![];
+        };
+
+        let mut occurrences = Vec::new();
+
+        for command in commands {
+            let kind = Self::table_highlight_kind(*command, text);
+
+            let mut references = Vec::<Node>::new();
+            Self::collect_table_references(*command, &mut references);
+
+            for reference in references {
+                if Self::names_match(&name, reference.utf8_text(text.as_bytes()).unwrap_or("")) {
+                    occurrences.extend(Self::last_identifier(reference).map(|id| (id, kind)));
+                }
+            }
+        }
+
+        occurrences
+    }
+
+    fn column_occurrences<'a>(
+        node: Node,
+        commands: &[Node<'a>],
+        text: &str,
+    ) -> Vec<(Node<'a>, DocumentHighlightKind)> {
+        let normalize =
+            |node: Node| Self::parse_identifier(node.utf8_text(text.as_bytes()).unwrap_or(""));
+
+        let column = normalize(node);
+
+        let Some(statement) = Self::command_of(node) else {
+            return vec![];
+        };
+        let table = Self::command_table(statement, text);
+
+        let mut occurrences = Vec::new();
+
+        for command in commands {
+            let same_scope = match (&table, Self::command_table(*command, text)) {
+                (Some(table), Some(other)) => Self::names_match(table, &other),
+                _ => command.id() == statement.id(),
+            };
+
+            if !same_scope {
+                continue;
+            }
+
+            let mut identifiers = Vec::<Node>::new();
+            Self::collect_identifiers(*command, &mut identifiers);
+
+            for identifier in identifiers {
+                if Self::is_table_reference(identifier)
+                    || Self::is_type_reference(identifier)
+                    || Self::is_in_comment_or_string(identifier)
+                    || normalize(identifier) != column
+                {
+                    continue;
+                }
+
+                let kind = Self::column_highlight_kind(*command, identifier, text);
+                occurrences.push((identifier, kind));
+            }
+        }
+
+        occurrences
+    }
+
+    /*
+        CREATE, INSERT, UPDATE, DELETE, ALTER && TRUNCATE write the table,
+        the rest of the commands only read it
+    */
+    fn table_highlight_kind(command: Node, text: &str) -> DocumentHighlightKind {
+        let keyword = Self::command_keyword(command, text).unwrap_or_default();
+
+        match keyword.as_str() {
+            "create" | "insert" | "update" | "delete" | "alter" | "truncate" => {
+                DocumentHighlightKind::WRITE
+            }
+            _ => DocumentHighlightKind::READ,
+        }
+    }
+
+    /*
+        CREATE TABLE users (name text, ...)            -> WRITE
+        INSERT INTO users (name) VALUES (...)          -> WRITE
+        UPDATE users SET name = 'x' WHERE name = 'y'   -> WRITE, READ
+    */
+    fn column_highlight_kind(command: Node, identifier: Node, text: &str) -> DocumentHighlightKind {
+        let prefix = text
+            .get(command.start_byte()..identifier.start_byte())
+            .unwrap_or("")
+            .to_lowercase();
+        let words: Vec<&str> = prefix.split_whitespace().collect();
+
+        let keyword = Self::command_keyword(command, text).unwrap_or_default();
+
+        let is_write = match keyword.as_str() {
+            "create" => true,
+            "insert" => !words.contains(&"values"),
+            "update" => words.contains(&"set") && !words.contains(&"where"),
+            _ => false,
+        };
+
+        match is_write {
+            true => DocumentHighlightKind::WRITE,
+            false => DocumentHighlightKind::READ,
+        }
+    }
+
+    /*
+        Table of the statement e.g ks.users of SELECT * FROM ks.users
+    */
+    fn command_table(command: Node, text: &str) -> Option<String> {
+        let mut cursor = command.walk();
+        let table = command
+            .children(&mut cursor)
+            .find(|child| child.kind() == "table_keyspace_name")?;
+
+        table.utf8_text(text.as_bytes()).ok().map(|s| s.to_string())
+    }
+
+    fn command_of(node: Node) -> Option<Node> {
+        let mut current = Some(node);
+
+        while let Some(n) = current {
+            if n.kind() == "cql_commands" {
+                return Some(n);
+            }
+            current = n.parent();
+        }
+
+        None
+    }
+
+    /*
+        WHERE columns are parsed as table_keyspace_name
+        inside of selector_conditions, those aren't tables
+    */
+    fn is_table_reference(node: Node) -> bool {
+        let mut current = Some(node);
+
+        while let Some(n) = current {
+            if n.kind() == "table_keyspace_name" {
+                return n
+                    .parent()
+                    .is_none_or(|parent| parent.kind() != "selector_conditions");
+            }
+            if n.kind() == "cql_commands" {
+                break;
+            }
+            current = n.parent();
+        }
+
+        false
+    }
+
+    fn collect_table_references<'a>(node: Node<'a>, references: &mut Vec<Node<'a>>) {
+        if node.kind() == "table_keyspace_name" {
+            if node
+                .parent()
+                .is_none_or(|parent| parent.kind() != "selector_conditions")
+            {
+                references.push(node);
+            }
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            Self::collect_table_references(child, references);
+        }
+    }
+
+    fn is_in_comment_or_string(node: Node) -> bool {
+        let mut current = Some(node);
+
+        while let Some(n) = current {
+            if matches!(
+                n.kind(),
+                "comment" | "line_comment" | "block_comment" | "string_literal"
+            ) {
+                return true;
+            }
+            current = n.parent();
+        }
+
+        false
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use crate::cqlsh::Column;
+use crate::lexer::{self, Token};
+use crate::lsp::Backend;
+
+/*
+    Inlay hints with types of selected columns
+
+    SELECT id, name FROM ks.users;  -> SELECT id: uuid, name: text FROM ks.users;
+
+    Only plain column selectors are hinted, functions, aliases
+    && columns missing in the table are skipped
+*/
+impl Backend {
+    pub async fn get_inlay_hints(&self, uri: &Url, range: &Range) -> Vec<InlayHint> {
+        if self.config().offline {
+            return vec![];
+        }
+
+        let Some(text) = self.documents.read().await.get(uri).cloned() else {
+            return vec![];
+        };
+
+        let range_start = Self::position_to_offset(&text, &range.start);
+        let range_end = Self::position_to_offset(&text, &range.end);
+
+        let mut hints = Vec::<InlayHint>::new();
+        let mut keyspace: Option<String> = None;
+        let mut tables = HashMap::<(String, String), Vec<Column>>::new();
+
+        for (start, end) in Self::statement_ranges(&text) {
+            let statement = &text[start..end];
+
+            if let Some(use_keyspace) = self.get_use_keyspace(statement) {
+                keyspace = Some(use_keyspace);
+                continue;
+            }
+
+            if end < range_start || start > range_end {
+                continue;
+            }
+
+            let tokens = lexer::code_tokens(statement);
+            let Some((selectors, table_keyspace, table)) = Self::selected_columns(&tokens) else {
+                continue;
+            };
+
+            let Some(table_keyspace) = table_keyspace.or(keyspace.clone()) else {
+                continue;
+            };
+
+            // Same table is usually selected by several statements
+            let key = (table_keyspace, table);
+            if !tables.contains_key(&key) {
+                let columns = self.schema_hard_scoped_fields(&key.0, &key.1).await;
+                tables.insert(key.clone(), columns);
+            }
+            let columns = &tables[&key];
+
+            for selector in selectors {
+                let name = Self::parse_identifier(selector.text);
+                let offset = start + selector.end;
+
+                if offset < range_start || offset > range_end {
+                    continue;
+                }
+
+                let Some(column) = columns.iter().find(|c| c.column_name == name) else {
+                    continue;
+                };
+
+                hints.push(InlayHint {
+                    position: Self::offset_to_position(&text, offset),
+                    label: InlayHintLabel::String(format!(": {}", column.column_type)),
+                    kind: Some(InlayHintKind::TYPE),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(false),
+                    padding_right: Some(false),
+                    data: None,
+                });
+            }
+        }
+
+        hints
+    }
+
+    /*
+        Plain column selectors && the FROM table of SELECT statement
+
+        SELECT DISTINCT id, count(*), name AS n FROM ks.t -> ([id], Some(ks), t)
+    */
+    pub fn selected_columns<'a>(
+        tokens: &[Token<'a>],
+    ) -> Option<(Vec<Token<'a>>, Option<String>, String)> {
+        if !tokens.first()?.is_keyword("select") {
+            return None;
+        }
+
+        let from = tokens.iter().position(|t| t.is_keyword("from"))?;
+
+        // FROM ks.t, FROM t
+        let (keyspace, table) = match tokens.get(from + 1..from + 4) {
+            Some([keyspace, dot, table]) if dot.is_punct(".") && keyspace.end == dot.start => (
+                Some(Self::parse_identifier(keyspace.text)),
+                Self::parse_identifier(table.text),
+            ),
+            _ => {
+                let table = tokens.get(from + 1).filter(|t| t.is_word())?;
+                (None, Self::parse_identifier(table.text))
+            }
+        };
+
+        let mut selectors = Vec::<Token>::new();
+
+        for selector in tokens[1..from].split(|t| t.is_punct(",")) {
+            let selector = match selector {
+                [distinct, rest @ ..] if distinct.text.eq_ignore_ascii_case("distinct") => rest,
+                _ => selector,
+            };
+
+            if let [column] = selector
+                && column.is_word()
+            {
+                selectors.push(*column);
+            }
+        }
+
+        Some((selectors, keyspace, table))
+    }
+}
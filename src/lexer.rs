@@ -0,0 +1,333 @@
+use crate::consts::KEYWORDS_STRINGS_LWC;
+
+/*
+    Line lexer used by completion predicates
+
+    SELECT "first name", age FROM ks.users; -- comment
+
+    select              Keyword
+    "first name"        Identifier
+    ,                   Punct
+    age                 Identifier
+    from                Keyword
+    ks . users          Identifier Punct Identifier
+    ;                   Punct
+    -- comment          Comment
+
+    Unlike split(' ') it doesn't care about tabs, repeated spaces,
+    punctuation glued to words && spaces inside of quotes.
+    Unterminated strings && comments run until the end of the input
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    String,
+    Number,
+    Punct,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    // Byte span inside of the input
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token<'_> {
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_punct(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+
+    pub fn is_word(&self) -> bool {
+        matches!(self.kind, TokenKind::Keyword | TokenKind::Identifier)
+    }
+}
+
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::<Token>::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let c = bytes[index];
+        let start = index;
+
+        if c.is_ascii_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let next = bytes.get(index + 1).copied();
+
+        let kind = match (c, next) {
+            (b'-', Some(b'-')) | (b'/', Some(b'/')) => {
+                index = input[index..].find('\n').map_or(bytes.len(), |p| p + index);
+                TokenKind::Comment
+            }
+            (b'/', Some(b'*')) => {
+                index = input[index + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |p| p + index + 4);
+                TokenKind::Comment
+            }
+            (b'$', Some(b'$')) => {
+                index = input[index + 2..]
+                    .find("$$")
+                    .map_or(bytes.len(), |p| p + index + 4);
+                TokenKind::String
+            }
+            (b'\'', _) => {
+                index = quoted_end(bytes, index, b'\'');
+                TokenKind::String
+            }
+            (b'"', _) => {
+                index = quoted_end(bytes, index, b'"');
+                TokenKind::Identifier
+            }
+            _ if c.is_ascii_digit() => {
+                // 1.5, 1e3, 0xff, 1h30m, uuid parts
+                while index < bytes.len()
+                    && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'.')
+                {
+                    index += 1;
+                }
+                TokenKind::Number
+            }
+            _ if c.is_ascii_alphabetic() || c == b'_' || !c.is_ascii() => {
+                while index < bytes.len()
+                    && (bytes[index].is_ascii_alphanumeric()
+                        || bytes[index] == b'_'
+                        || !bytes[index].is_ascii())
+                {
+                    index += 1;
+                }
+
+                if KEYWORDS_STRINGS_LWC.contains(&input[start..index].to_lowercase()) {
+                    TokenKind::Keyword
+                } else {
+                    TokenKind::Identifier
+                }
+            }
+            (b'>' | b'<' | b'!', Some(b'=')) => {
+                index += 2;
+                TokenKind::Punct
+            }
+            _ => {
+                index += 1;
+                TokenKind::Punct
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &input[start..index],
+            start,
+            end: index,
+        });
+    }
+
+    tokens
+}
+
+/*
+    Tokens except comments, what most predicates care about
+*/
+pub fn code_tokens(input: &str) -> Vec<Token<'_>> {
+    tokenize(input)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect()
+}
+
+/*
+    Tokens before the word that is still being typed
+
+    SELECT a, na|   -> SELECT a ,
+    SELECT a, |     -> SELECT a ,
+*/
+pub fn completed_tokens<'a, 'b>(tokens: &'b [Token<'a>], input: &str) -> &'b [Token<'a>] {
+    match tokens.last() {
+        Some(last) if last.is_word() && last.end == input.len() => &tokens[..tokens.len() - 1],
+        _ => tokens,
+    }
+}
+
+/*
+    Lowercase text of the tokens, qualified names are kept
+    as a single word
+
+    FROM ks.users; -> [from, ks.users, ;]
+*/
+pub fn words(tokens: &[Token]) -> Vec<String> {
+    let mut words = Vec::<String>::new();
+    let mut previous: Option<&Token> = None;
+
+    for token in tokens {
+        let joined = previous.is_some_and(|previous| {
+            previous.end == token.start && (previous.is_punct(".") || token.is_punct("."))
+        });
+
+        match words.last_mut() {
+            Some(word) if joined => word.push_str(&token.text.to_lowercase()),
+            _ => words.push(token.text.to_lowercase()),
+        }
+
+        previous = Some(token);
+    }
+
+    words
+}
+
+/*
+    End of the literal opened at start, doubled quote is an escaped quote
+
+    'it''s'  "say ""hi"""
+*/
+fn quoted_end(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let mut index = start + 1;
+
+    while index < bytes.len() {
+        if bytes[index] == quote {
+            if bytes.get(index + 1) == Some(&quote) {
+                index += 2;
+                continue;
+            }
+            return index + 1;
+        }
+        index += 1;
+    }
+
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .into_iter()
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn whitespace_and_punctuation() {
+        assert_eq!(
+            kinds("SELECT\ta,b  FROM\tks.t;"),
+            [
+                (Keyword, "SELECT"),
+                (Identifier, "a"),
+                (Punct, ","),
+                (Identifier, "b"),
+                (Keyword, "FROM"),
+                (Identifier, "ks"),
+                (Punct, "."),
+                (Identifier, "t"),
+                (Punct, ";"),
+            ]
+        );
+        assert_eq!(
+            kinds("a>=1 AND b!=2"),
+            [
+                (Identifier, "a"),
+                (Punct, ">="),
+                (Number, "1"),
+                (Keyword, "AND"),
+                (Identifier, "b"),
+                (Punct, "!="),
+                (Number, "2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_and_quoted_identifiers() {
+        assert_eq!(
+            kinds(r#"WHERE "first name" = 'it''s, FROM x' AND b = $$a;b$$"#),
+            [
+                (Keyword, "WHERE"),
+                (Identifier, r#""first name""#),
+                (Punct, "="),
+                (String, "'it''s, FROM x'"),
+                (Keyword, "AND"),
+                (Identifier, "b"),
+                (Punct, "="),
+                (String, "$$a;b$$"),
+            ]
+        );
+
+        // Unterminated literal runs until the end of the input
+        assert_eq!(
+            kinds("VALUES ('ab"),
+            [(Keyword, "VALUES"), (Punct, "("), (String, "'ab")]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            kinds("a -- b, c\n/* d */ e // f"),
+            [
+                (Identifier, "a"),
+                (Comment, "-- b, c"),
+                (Comment, "/* d */"),
+                (Identifier, "e"),
+                (Comment, "// f"),
+            ]
+        );
+        assert_eq!(kinds("/* open"), [(Comment, "/* open")]);
+
+        let code: Vec<&str> = code_tokens("a -- b\nc").iter().map(|t| t.text).collect();
+        assert_eq!(code, ["a", "c"]);
+    }
+
+    #[test]
+    fn numbers_and_multibyte_identifiers() {
+        assert_eq!(
+            kinds("1.5 1h30m だよ_1 🚀"),
+            [
+                (Number, "1.5"),
+                (Number, "1h30m"),
+                (Identifier, "だよ_1"),
+                (Identifier, "🚀"),
+            ]
+        );
+
+        let tokens = tokenize("'だ' x");
+        assert_eq!((tokens[1].start, tokens[1].end), (6, 7));
+    }
+
+    #[test]
+    fn completed_tokens_drop_the_typed_word() {
+        let input = "SELECT a, na";
+        let tokens = code_tokens(input);
+        let texts: Vec<&str> = completed_tokens(&tokens, input)
+            .iter()
+            .map(|t| t.text)
+            .collect();
+        assert_eq!(texts, ["SELECT", "a", ","]);
+
+        let input = "SELECT a, ";
+        let tokens = code_tokens(input);
+        assert_eq!(completed_tokens(&tokens, input).len(), 3);
+    }
+
+    #[test]
+    fn words_keep_qualified_names() {
+        let tokens = code_tokens("FROM Ks.Users;");
+        assert_eq!(words(&tokens), ["from", "ks.users", ";"]);
+
+        let tokens = code_tokens("a . b");
+        assert_eq!(words(&tokens), ["a", ".", "b"]);
+    }
+}
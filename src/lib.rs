@@ -1,9 +1,21 @@
+pub mod code_actions;
+pub mod commands;
 pub mod completions;
 pub mod consts;
 pub mod cqlsh;
+pub mod debounce;
+pub mod definitions;
+pub mod diagnostics;
+pub mod folding;
 pub mod formatting;
 pub mod handlers;
+pub mod highlights;
+pub mod inlay_hints;
+pub mod lexer;
 pub mod lsp;
+pub mod schema;
+pub mod semantic_tokens;
 pub mod setup;
+pub mod symbols;
 pub mod tree_sitter;
 pub mod utils;
@@ -2,9 +2,17 @@ use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
 use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock as StdRwLock};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::AbortHandle;
+use tree_sitter::Tree;
 
+use crate::commands::{DESCRIBE_TABLE_COMMAND, RELOAD_CONFIG_COMMAND, RUN_STATEMENT_COMMAND};
 use crate::cqlsh::CqlSettings;
+use crate::debounce::Debouncer;
+use crate::schema::SchemaSnapshot;
+use crate::semantic_tokens::semantic_tokens_legend;
 
 /*
     Based on DataStax HCD && CQL versions 3.4+
@@ -19,15 +27,43 @@ use crate::cqlsh::CqlSettings;
     Some of the default CQL functions will be different because of DataStax HCD extensions
 */
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordCase {
+    Upper,
+    Lower,
+    Preserve,
+}
+
+impl KeywordCase {
+    pub fn from_env(keyword_case: &str) -> Self {
+        match keyword_case.to_lowercase().as_str() {
+            "upper" => Self::Upper,
+            "lower" => Self::Lower,
+            _ => Self::Preserve,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FormattingSettings {
     pub type_alignment_offset: usize,
+    pub format_selectors: bool,
+    pub keyword_case: KeywordCase,
+    pub format_on_save: bool,
 }
 
 impl FormattingSettings {
-    pub fn from_env(type_alignment_offset: &str) -> Self {
+    pub fn from_env(
+        type_alignment_offset: &str,
+        format_selectors: &str,
+        keyword_case: &str,
+        format_on_save: &str,
+    ) -> Self {
         Self {
             type_alignment_offset: type_alignment_offset.parse().unwrap(),
+            format_selectors: format_selectors == "true",
+            keyword_case: KeywordCase::from_env(keyword_case),
+            format_on_save: format_on_save == "true",
         }
     }
 }
@@ -36,9 +72,22 @@ impl FormattingSettings {
 pub struct Backend {
     pub client: Client,
     pub documents: RwLock<HashMap<Url, String>>,
+    pub parsed_trees: RwLock<HashMap<Url, Tree>>,
+    // Pauses did_change driven work until typing stops
+    pub debouncer: Debouncer,
     pub current_document: RwLock<Option<RwLock<Document>>>,
-    pub config: CqlSettings,
+    // Swapped by cql.reloadConfig, read through config()
+    pub config: StdRwLock<Arc<CqlSettings>>,
     pub formatting_config: FormattingSettings,
+    // Set once unreachable cluster was reported to the client
+    pub db_unreachable: AtomicBool,
+    // Serves completions on cold start, dropped once the cluster answers.
+    // Shared with the background refresh
+    pub schema_snapshot: Arc<RwLock<Option<SchemaSnapshot>>>,
+    // Datacenter names of the cluster, queried once
+    pub datacenters: RwLock<Option<Vec<String>>>,
+    // Background schema refreshes, aborted on shutdown
+    pub refresh_tasks: Mutex<Vec<AbortHandle>>,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +107,43 @@ impl Document {
     }
 }
 
+/*
+    Offline backend for unit tests, the cluster is never contacted.
+    Service owns the backend, service.inner() borrows it
+*/
+#[cfg(test)]
+impl Backend {
+    pub fn test_service() -> tower_lsp::LspService<Backend> {
+        Self::test_service_with(
+            CqlSettings::new(),
+            FormattingSettings::from_env("7", "false", "preserve", "false"),
+        )
+    }
+
+    pub fn test_service_with(
+        mut settings: CqlSettings,
+        formatting_config: FormattingSettings,
+    ) -> tower_lsp::LspService<Backend> {
+        settings.offline = true;
+
+        let (service, _) = tower_lsp::LspService::new(|client| Backend {
+            client,
+            documents: RwLock::new(HashMap::new()),
+            parsed_trees: RwLock::new(HashMap::new()),
+            debouncer: Debouncer::new(300),
+            current_document: RwLock::new(None),
+            config: StdRwLock::new(Arc::new(settings)),
+            formatting_config,
+            db_unreachable: AtomicBool::new(false),
+            schema_snapshot: Arc::new(RwLock::new(None)),
+            datacenters: RwLock::new(None),
+            refresh_tasks: Mutex::new(Vec::new()),
+        });
+
+        service
+    }
+}
+
 impl Backend {
     // -----------------------------[Helper Functions]-----------------------------
 
@@ -74,6 +160,30 @@ impl Backend {
     // -----------------------------[Handlers]-----------------------------
 
     // handlers.rs
+
+    // -----------------------------[Definitions]-----------------------------
+
+    // definitions.rs
+
+    // -----------------------------[Code Actions]-----------------------------
+
+    // code_actions.rs
+
+    // -----------------------------[Folding]-----------------------------
+
+    // folding.rs
+
+    // -----------------------------[Semantic Tokens]-----------------------------
+
+    // semantic_tokens.rs
+
+    // -----------------------------[Commands]-----------------------------
+
+    // commands.rs
+
+    // -----------------------------[Diagnostics]-----------------------------
+
+    // diagnostics.rs
 }
 
 #[tower_lsp::async_trait]
@@ -84,11 +194,16 @@ impl LanguageServer for Backend {
     ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
+                    resolve_provider: Some(true),
                     trigger_characters: Some(vec![
                         ".".to_string(),
                         "\"".to_string(),
@@ -98,6 +213,41 @@ impl LanguageServer for Backend {
                     ..Default::default()
                 }),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: Some(vec!["(".to_string(), ")".to_string()]),
+                }),
+                definition_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens_legend(),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        },
+                    ),
+                ),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: match self.config().enable_exec {
+                        true => vec![
+                            DESCRIBE_TABLE_COMMAND.to_string(),
+                            RELOAD_CONFIG_COMMAND.to_string(),
+                            RUN_STATEMENT_COMMAND.to_string(),
+                        ],
+                        false => vec![
+                            DESCRIBE_TABLE_COMMAND.to_string(),
+                            RELOAD_CONFIG_COMMAND.to_string(),
+                        ],
+                    },
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             ..Default::default()
@@ -113,19 +263,174 @@ impl LanguageServer for Backend {
         if let Some(current_doc) = self.documents.read().await.get(&document) {
             let lines: Vec<&str> = current_doc.split('\n').collect();
 
-            return Ok(Some(self.format_file(&lines, &document).await));
+            return Ok(Some(
+                self.format_file(&lines, &document, Some(&params.options))
+                    .await,
+            ));
         } else {
             return Ok(Some(vec![]));
         }
     }
 
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let document = params.text_document.uri;
+
+        let Some(text) = self.documents.read().await.get(&document).cloned() else {
+            return Ok(Some(vec![]));
+        };
+
+        Ok(Some(
+            self.format_range(&text, &params.range, &document, Some(&params.options))
+                .await,
+        ))
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let document = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        Ok(self
+            .format_on_type(&document, &position, &params.ch, &params.options)
+            .await)
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        Ok(self
+            .find_definition(&uri, &position)
+            .await
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn rename(
+        &self,
+        params: RenameParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        self.rename_symbol(&uri, &position, &params.new_name).await
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        Ok(Some(self.get_document_highlights(&uri, &position).await))
+    }
+
+    async fn inlay_hint(
+        &self,
+        params: InlayHintParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri;
+
+        Ok(Some(self.get_inlay_hints(&uri, &params.range).await))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(self.get_workspace_symbols(&params.query).await))
+    }
+
+    async fn folding_range(
+        &self,
+        params: FoldingRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<FoldingRange>>> {
+        let Some(text) = self
+            .documents
+            .read()
+            .await
+            .get(&params.text_document.uri)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.get_folding_ranges(&text)))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensResult>> {
+        let Some(tokens) = self.get_semantic_tokens(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: tokens,
+        })))
+    }
+
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CodeActionResponse>> {
+        let actions = self
+            .get_code_actions(&params.text_document.uri, &params.range)
+            .await;
+
+        Ok(Some(actions))
+    }
+
+    async fn completion_resolve(
+        &self,
+        item: CompletionItem,
+    ) -> tower_lsp::jsonrpc::Result<CompletionItem> {
+        Ok(self.handle_completion_resolve(item).await)
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<LSPAny>> {
+        match params.command.as_str() {
+            RUN_STATEMENT_COMMAND => self.run_statement(&params.arguments).await,
+            DESCRIBE_TABLE_COMMAND => self.describe_table(&params.arguments).await,
+            RELOAD_CONFIG_COMMAND => self.reload_config().await,
+            _ => Err(tower_lsp::jsonrpc::Error::method_not_found()),
+        }
+    }
+
     async fn initialized(&self, _: InitializedParams) {
         self.client
             .log_message(MessageType::INFO, "LSP initialized!")
             .await;
+
+        self.check_cluster_connection().await;
     }
 
+    /*
+        Sessions are opened per query && closed once it's done,
+        only debounced work && schema refreshes can outlive the client.
+        Pending pauses are aborted so no validation starts after shutdown,
+        refreshes are aborted so no snapshot is written after it
+    */
     async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
+        self.debouncer.cancel_all().await;
+
+        for refresh in self.refresh_tasks.lock().await.drain(..) {
+            refresh.abort();
+        }
+
         Ok(())
     }
 
@@ -133,20 +438,86 @@ impl LanguageServer for Backend {
         let uri = params.text_document.uri;
         let changes = &params.content_changes;
 
-        if let Some(change) = changes.first() {
-            self.documents
-                .write()
-                .await
-                .insert(uri.clone(), change.text.clone());
-
-            let mut current = self.current_document.write().await;
-            if let Some(ref mut document_lock) = *current {
-                let mut document = document_lock.write().await;
-                if document.uri == uri {
-                    document.change(uri.clone(), change.text.clone());
+        if changes.is_empty() {
+            return;
+        }
+
+        let mut documents = self.documents.write().await;
+        let text = documents.entry(uri.clone()).or_default();
+        let mut old_tree = self.parsed_trees.write().await.remove(&uri);
+
+        /*
+            Changes are applied in order,
+            each range refers to the document after the previous change
+
+            Cached tree is edited along with the text, so the document
+            is re-parsed incrementally once all changes are applied
+        */
+        for change in changes {
+            match (Self::content_change_edit(text, change), old_tree.as_mut()) {
+                (Some(edit), Some(tree)) => tree.edit(&edit),
+                (None, _) => old_tree = None,
+                _ => {}
+            }
+
+            Self::apply_content_change(text, change);
+        }
+
+        let text = text.clone();
+        drop(documents);
+
+        self.update_tree(&uri, &text, old_tree).await;
+        self.publish_diagnostics(&uri, &text).await;
+
+        let mut current = self.current_document.write().await;
+        if let Some(ref mut document_lock) = *current {
+            let mut document = document_lock.write().await;
+            if document.uri == uri {
+                document.change(uri.clone(), text);
+            }
+        }
+        drop(current);
+
+        if self.config().live_validation {
+            self.validate_after_pause(&uri, params.text_document.version)
+                .await;
+        }
+    }
+
+    /*
+        Formatting edits are sent to the client, validation
+        runs on the document the client sent back
+    */
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+
+        if self.formatting_config.format_on_save {
+            let text = self.documents.read().await.get(&uri).cloned();
+
+            if let Some(text) = text {
+                let lines: Vec<&str> = text.split('\n').collect();
+                let edits = self.format_file(&lines, &uri, None).await;
+
+                let edit = WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), edits)])),
+                    ..Default::default()
+                };
+
+                if let Err(e) = self.client.apply_edit(edit).await {
+                    self.client
+                        .log_message(MessageType::ERROR, format!("Format on save failed: {}", e))
+                        .await;
                 }
             }
         }
+
+        if self.config().validate_on_save {
+            let text = self.documents.read().await.get(&uri).cloned();
+
+            if let Some(text) = text {
+                self.publish_validated_diagnostics(&uri, &text).await;
+            }
+        }
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -168,6 +539,9 @@ impl LanguageServer for Backend {
             .await
             .insert(uri.clone(), text.clone());
 
+        self.update_tree(&uri, &text, None).await;
+        self.publish_diagnostics(&uri, &text).await;
+
         self.client
             .log_message(MessageType::INFO, format!("Opened: {}", uri))
             .await;
@@ -180,17 +554,30 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
 
-        let documents = self.documents.read().await;
-        let text = match documents.get(&uri) {
-            Some(text) => text,
-            None => return Ok(None),
+        /*
+            Guard isn't held while completing, did_change waiting for
+            the write lock would block every later read of documents
+        */
+        let Some(text) = self.documents.read().await.get(&uri).cloned() else {
+            return Ok(None);
         };
+        let text = text.as_str();
 
         let line = match text.lines().nth(position.line as usize) {
             Some(line) => line,
             None => return Ok(None),
         };
 
+        if self.is_idle_space_trigger(line, &position, params.context.as_ref()) {
+            return Ok(None);
+        }
+
+        if self.config().offline {
+            return self
+                .handle_offline_completion(text, line, &position, &uri)
+                .await;
+        }
+
         // --------------------------------[EXPERIMENTAL] --------------------------------
 
         /*
@@ -198,8 +585,6 @@ impl LanguageServer for Backend {
             For more information, see https://github.com/Akzestia/cql-lsp
         */
 
-        // let ssh_command_sequence = self.should_suggest_command_sequence(line, &position);
-
         // --------------------------------[EXPERIMENTAL] --------------------------------
 
         // --------------------------------[STABLE] --------------------------------
@@ -211,12 +596,34 @@ impl LanguageServer for Backend {
 
         // General
         let in_string = Self::is_in_string_literal(line, position.character);
+        let ssh_command_sequence = self.should_suggest_command_sequence(text, line, &position);
+        let ssh_batch = self.should_suggest_batch(text, line, &position);
         let ssh_keyspaces = self.should_suggest_keyspaces(line, &position);
         let ssh_graph_types = self.should_suggest_graph_engine_types(line, &position);
-        let ssh_keywords = self.should_suggest_keywords(line, &position).await;
-        let ssh_fields = self.should_suggest_fields(line, &position);
+        let ssh_replication_options = self.should_suggest_replication_options(line, &position);
+        let ssh_keywords = self
+            .should_suggest_keywords(text, line, &position, &uri)
+            .await;
+        let ssh_fields = self.should_suggest_fields(text, line, &position);
+        let ssh_where_columns = self.should_suggest_where_columns(line, &position);
+        let ssh_order_by_columns = self.should_suggest_order_by_columns(line, &position);
+        let ssh_select_tail_keywords = self.should_suggest_select_tail_keywords(line, &position);
+        let ssh_limit_value = self.should_suggest_limit_value(line, &position);
+        let ssh_allow_filtering = self.should_suggest_allow_filtering(line, &position);
+        let ssh_udt_fields = self.should_suggest_udt_fields(line, &position);
+        let ssh_insert_columns = self.should_suggest_insert_columns(line, &position);
+        let ssh_update_set_columns = self.should_suggest_update_set_columns(line, &position);
+        let ssh_alter_table_ops = self.should_suggest_alter_table_ops(line, &position);
+        let ssh_index_columns = self.should_suggest_index_columns(line, &position);
+        let ssh_index_class = self.should_suggest_index_class(line, &position);
+        let ssh_consistency = self.should_suggest_consistency(line, &position);
+        let ssh_using_options = self.should_suggest_using_options(line, &position);
+        let ssh_using_values = self.should_suggest_using_values(line, &position);
+        let ssh_roles = self.should_suggest_roles(line, &position);
+        let ssh_permissions = self.should_suggest_permissions(line, &position);
         let ssh_from = self.should_suggest_from(line, &position);
         let ssh_table_completions = self.should_suggest_table_completions(line, &position);
+        let ssh_dot_scoped_tables = self.should_suggest_dot_scoped_tables(line, &position);
         let ssh_if_not_exists = self.should_suggest_if_not_exists(line, &position);
         let ssh_create_keywords = self.should_suggest_create_keywords(line, &position);
         let ssh_alter_keywords = self.should_suggest_alter_keywords(line, &position);
@@ -224,6 +631,7 @@ impl LanguageServer for Backend {
         // DROP kw
         let ssh_drop_keywords = self.should_suggest_drop_keywords(line, &position);
         let ssh_drop_keyspaces = self.should_suggest_drop_keyspaces(line, &position);
+        let ssh_alter_keyspaces = self.should_suggest_alter_keyspaces(line, &position);
         let ssh_drop_tables = self.should_suggest_drop_tables(line, &position);
         // DROP Queries
         let ssh_drop_aggregate = self.should_suggest_drop_aggregate(line, &position);
@@ -232,16 +640,39 @@ impl LanguageServer for Backend {
         let ssh_drop_type = self.should_suggest_drop_types(line, &position);
         let ssh_drop_view = self.should_suggest_drop_views(line, &position);
 
+        // TRUNCATE
+        let ssh_truncate = self.should_suggest_truncate(line, &position);
+        let ssh_truncate_tables = self.should_suggest_truncate_tables(line, &position);
+        let ssh_describe = self.should_suggest_describe(line, &position);
+
         // Types
         let ssh_types = self
-            .should_suggest_types_completions(line, &position, &uri)
+            .should_suggest_types_completions(line, &position, text)
             .await;
         let ssh_type_modifiers = self
-            .should_suggest_type_modifiers(line, &position, &uri)
+            .should_suggest_type_modifiers(line, &position, text)
+            .await;
+        let ssh_table_with_options = self
+            .should_suggest_table_with_options(line, &position, text)
+            .await;
+        let ssh_collection_inner_type = self
+            .should_suggest_collection_inner_type(line, &position, text)
+            .await;
+        let ssh_view_columns = self.should_suggest_view_columns(&position, text).await;
+        let ssh_primary_key_columns = self
+            .should_suggest_primary_key_columns(&position, text)
             .await;
 
         // --------------------------------[STABLE] --------------------------------
 
+        if ssh_command_sequence {
+            return self.get_available_command_sequences();
+        }
+
+        if ssh_batch && !in_string {
+            return self.handle_batch_completion(text, line, &position);
+        }
+
         if ssh_keyspaces {
             return if in_string {
                 self.handle_in_string_keyspace_completion(line, &position)
@@ -252,6 +683,74 @@ impl LanguageServer for Backend {
             };
         }
 
+        // Checked early, REVOKE ... FROM would trigger table completions
+        if ssh_roles && !in_string {
+            return self.handle_roles_completion().await;
+        }
+
+        if ssh_permissions && !in_string {
+            return self.handle_permissions();
+        }
+
+        // Resolved only when reached, the column is looked up in the schema
+        if let Some(column) = self.should_suggest_value_template(line, &position).await {
+            return self.handle_value_template_completion(&column);
+        }
+
+        if ssh_udt_fields {
+            return self.handle_udt_fields_completion(line, &position).await;
+        }
+
+        // Checked before fields, view SELECT list has no FROM on the same line
+        if ssh_view_columns && !in_string {
+            return self.handle_view_columns_completion(&position, text).await;
+        }
+
+        if ssh_dot_scoped_tables && !in_string {
+            return self
+                .handle_dot_scoped_tables_completion(line, &position)
+                .await;
+        }
+
+        // Checked before tables, INSERT INTO t( would trigger table completions
+        if ssh_insert_columns && !in_string {
+            return self.handle_insert_columns_completion(line, &position).await;
+        }
+
+        if ssh_update_set_columns && !in_string {
+            return self
+                .handle_update_set_columns_completion(line, &position)
+                .await;
+        }
+
+        // Checked before USING options, USING of CREATE CUSTOM INDEX takes a class
+        if ssh_index_class {
+            return self.handle_index_class_completion(line, &position);
+        }
+
+        if ssh_index_columns && !in_string {
+            return self.handle_index_columns_completion(line, &position).await;
+        }
+
+        if ssh_alter_table_ops && !in_string {
+            return self
+                .handle_alter_table_ops_completion(line, &position)
+                .await;
+        }
+
+        // Levels are also completed inside quotes, CONSISTENCY 'QUORUM'
+        if ssh_consistency {
+            return self.handle_consistency_completion();
+        }
+
+        if ssh_using_options && !in_string {
+            return self.handle_using_options_completion();
+        }
+
+        if ssh_using_values && !in_string {
+            return self.handle_using_values_completion(line, &position);
+        }
+
         if ssh_create_keywords {
             return self.handle_create_keywords();
         }
@@ -268,36 +767,78 @@ impl LanguageServer for Backend {
             return self.handle_drop_keyspace_completions(line, &position).await;
         }
 
+        if ssh_alter_keyspaces && !in_string {
+            return self
+                .handle_alter_keyspace_completions(line, &position)
+                .await;
+        }
+
         if ssh_drop_tables {
-            return self.handle_table_completion(&position).await;
+            return self.handle_table_completion(line, &position).await;
         }
 
         if ssh_drop_aggregate {
-            return self.handle_drop_aggregate_completions().await;
+            return self
+                .handle_drop_aggregate_completions(line, &position)
+                .await;
         }
 
         if ssh_drop_function {
-            return self.handle_drop_function_completions().await;
+            return self.handle_drop_function_completions(line, &position).await;
         }
 
         if ssh_drop_index {
-            return self.handle_drop_index_completions().await;
+            return self.handle_drop_index_completions(line, &position).await;
         }
 
         if ssh_drop_type {
-            return self.handle_drop_type_completions().await;
+            return self.handle_drop_type_completions(line, &position).await;
         }
 
         if ssh_drop_view {
-            return self.handle_drop_view_completions().await;
+            return self.handle_drop_view_completions(line, &position).await;
+        }
+
+        if ssh_truncate && !in_string {
+            return self.handle_truncate_completion(line, &position).await;
+        }
+
+        if ssh_truncate_tables && !in_string {
+            return self.handle_table_completion(line, &position).await;
+        }
+
+        if ssh_describe && !in_string {
+            return self.handle_describe_completion(line, &position).await;
+        }
+
+        // Checked before types, PRIMARY KEY ( is inside of the column body
+        if ssh_primary_key_columns && !in_string {
+            return self
+                .handle_primary_key_columns_completion(&position, text)
+                .await;
+        }
+
+        // Checked before types, map<| is a column type position as well
+
+        if ssh_collection_inner_type && !in_string {
+            return self
+                .handle_collection_inner_type_completion(line, &position)
+                .await;
+        }
+
+        // Checked before types, column body detection doesn't stop at ) WITH
+        if ssh_table_with_options && !in_string {
+            return self.handle_alter_table_with_options(&position, text).await;
         }
 
         if ssh_types {
-            return self.handle_types_completion();
+            return self.handle_types_completion(&position, text).await;
         }
 
         if ssh_type_modifiers {
-            return self.handle_type_modifiers_completion(line);
+            return self
+                .handle_type_modifiers_completion(line, &position, text)
+                .await;
         }
 
         if ssh_from {
@@ -309,11 +850,41 @@ impl LanguageServer for Backend {
         }
 
         if ssh_fields {
-            return self.handle_fields_completion(line, &position).await;
+            return self.handle_fields_completion(text, line, &position).await;
+        }
+
+        if ssh_select_tail_keywords && !in_string {
+            return self.handle_select_tail_keywords();
+        }
+
+        if ssh_order_by_columns && !in_string {
+            return self
+                .handle_order_by_columns_completion(line, &position)
+                .await;
+        }
+
+        if ssh_limit_value && !in_string {
+            return self.handle_limit_value_completion();
+        }
+
+        if ssh_allow_filtering && !in_string {
+            return self
+                .handle_allow_filtering_completion(line, &position)
+                .await;
+        }
+
+        if ssh_where_columns && !in_string {
+            return self.handle_where_columns_completion(line, &position).await;
         }
 
         if ssh_table_completions {
-            return self.handle_table_completion(&position).await;
+            return self.handle_table_completion(line, &position).await;
+        }
+
+        if ssh_replication_options {
+            return self
+                .handle_replication_options_completion(line, &position)
+                .await;
         }
 
         if ssh_graph_types {
@@ -1,10 +1,14 @@
-use cql_lsp::cqlsh::CqlSettings;
+use cql_lsp::debounce::Debouncer;
 use cql_lsp::lsp::{Backend, FormattingSettings};
-use cql_lsp::setup::setup_logger;
+use cql_lsp::schema::SchemaSnapshot;
+use cql_lsp::setup::{load_config, load_settings, parse_listen_addr, setup_logger};
 use log::info;
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock as StdRwLock};
 use tokio::io::{stdin, stdout};
-use tokio::sync::RwLock;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, RwLock};
 use tower_lsp::{LspService, Server};
 
 /*
@@ -25,10 +29,39 @@ use tower_lsp::{LspService, Server};
 
 
     [LocalHost]
-    CQL_LSP_DB_URL = "127.0.0.1:9042"
+    CQL_LSP_DB_URL = "127.0.0.1:9042" | Comma separated for multiple nodes e.g "10.0.0.1:9042,10.0.0.2:9042"
     CQL_LSP_DB_PASSWD = "cassandra"
     CQL_LSP_DB_USER = "cassandra"
     CQL_LSP_ENABLE_LOGGING = false | Used for development
+    CQL_LSP_LOG_LEVEL = info | error, warn, info, debug, trace or off
+    CQL_LSP_LOG_PATH = "" | Defaults to <data_dir>/cql_lsp/output.log
+    CQL_LSP_DB_TIMEOUT = 3 | Connection timeout in seconds
+    CQL_LSP_DB_RETRIES = 2 | Connection retries before cluster is reported unreachable
+    CQL_LSP_OFFLINE = false | Never connects, schema-aware completions && validation are disabled
+    CQL_LSP_SCHEMA_SNAPSHOT = false | Caches schema in <data_dir>/cql_lsp/schema.json for faster cold starts
+    CQL_LSP_FORMAT_SELECTORS = false | Breaks SELECT lists one selector per line
+    CQL_LSP_CONTEXT_BASED_SELECT = false | SELECT field completions insert FROM keyspace.table
+    CQL_LSP_QUALIFIED_DROP = false | DROP completions insert keyspace.name even for the USE keyspace
+    CQL_LSP_COMMAND_SNIPPETS = false | [Experimental] Statement templates on empty lines
+    CQL_LSP_MAX_COMPLETION_ITEMS = 5000 | Columns read per completion query, 0 for no limit
+    CQL_LSP_QUERY_TIMEOUT_MS = 2000 | Completion queries slower than this yield no items, 0 for no limit
+    CQL_LSP_DIALECT = cassandra | scylla, cassandra or hcd, Scylla only syntax is offered for scylla
+    CQL_LSP_KEYWORD_CASE = preserve | upper, lower or preserve
+    CQL_LSP_FORMAT_ON_SAVE = false | Formats document when it's saved
+    CQL_LSP_VALIDATE_ON_SAVE = false | Prepares statements on save && reports rejected ones
+    CQL_LSP_LIVE_VALIDATION = false | Prepares statements shortly after typing stops
+    CQL_LSP_DEBOUNCE_MS = 300 | Pause in typing before live validation runs
+    CQL_LSP_LISTEN = "" | Serves LSP over TCP e.g "tcp://127.0.0.1:9257", defaults to stdio
+
+    [Execution]
+    CQL_LSP_ENABLE_EXEC = false | Enables cql.runStatement command
+    CQL_LSP_ALLOW_MUTATIONS = false | Allows running statements other than SELECT
+
+    [TLS]
+    CQL_LSP_TLS_ENABLED = false
+    CQL_LSP_TLS_CA_PATH = "/path/to/ca.pem" | Required when TLS is enabled
+    CQL_LSP_TLS_CLIENT_CERT = "/path/to/client.pem" | Optional, requires key
+    CQL_LSP_TLS_CLIENT_KEY = "/path/to/client.key" | Optional, requires cert
 
     [Dockerults]
     CQL_LSP_DB_URL = "172.17.0.2:9042"
@@ -54,46 +87,104 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "false".to_string()
     });
 
-    // Enabel logging if env variable was set to true
-    if enable_logging == "true" {
-        setup_logger().map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    let log_level = std::env::var("CQL_LSP_LOG_LEVEL").unwrap_or_else(|_| "info".to_string());
+    let log_path = std::env::var("CQL_LSP_LOG_PATH").unwrap_or_default();
+
+    /*
+        Enabel logging if env variable was set to true
+
+        Logger never writes to stdout, it's used by tower_lsp
+        for JSON-RPC messages. Broken log file doesn't stop the
+        server, error is reported on stderr instead
+    */
+    if enable_logging == "true"
+        && let Err(e) = setup_logger(&log_level, &log_path)
+    {
+        eprintln!("cql_lsp: logging disabled, failed to open log file: {}", e);
     }
 
-    // Set missing env variables to default ones
-    let url = std::env::var("CQL_LSP_DB_URL").unwrap_or_else(|_| {
-        // Defaults to localhost and NOT docker
-        info!("Db url wasn't provided. Setting url to default(127.0.0.1)");
-        "127.0.0.1".to_string()
-    });
-    let pswd = std::env::var("CQL_LSP_DB_PASSWD").unwrap_or_else(|_| {
-        info!("Db pswd wasn't provided.\nSetting pswd to default(cassandra)");
-        "cassandra".to_string()
-    });
-    let user = std::env::var("CQL_LSP_DB_USER").unwrap_or_else(|_| {
-        info!("Db user wasn't provided.\nSetting user to default(cassandra)");
-        "cassandra".to_string()
-    });
+    // Env variables take precedence over config.lsp
+    let file_config = load_config();
+    // Set missing env variables to config/default ones
     let type_alignment_offset = std::env::var("CQL_LSP_TYPE_ALIGNMENT_OFFSET").unwrap_or_else(|_| {
        info!("Type alignment offset wasn't provided.\n Setting type alignment offset to default 7");
        "7".to_string()
     });
+    let keyword_case = std::env::var("CQL_LSP_KEYWORD_CASE").unwrap_or_else(|_| {
+        info!("Keyword case wasn't provided.\nSetting keyword case to default(preserve)");
+        "preserve".to_string()
+    });
+    let format_selectors = std::env::var("CQL_LSP_FORMAT_SELECTORS").unwrap_or_else(|_| {
+        info!(
+            "Format selectors mode wasn't provided.\nSetting format selectors to config value({})",
+            file_config.context_based_select
+        );
+        file_config.context_based_select.to_string()
+    });
 
-    // Init CqlSettings settings
-    let settings = CqlSettings::from_env(&url, &pswd, &user);
-    let formatting_settings = FormattingSettings::from_env(&type_alignment_offset);
+    let format_on_save = std::env::var("CQL_LSP_FORMAT_ON_SAVE").unwrap_or_else(|_| {
+        info!("Format on save mode wasn't provided.\nSetting format on save to default(false)");
+        "false".to_string()
+    });
+    let debounce_ms = std::env::var("CQL_LSP_DEBOUNCE_MS").unwrap_or_else(|_| {
+        info!("Debounce wasn't provided.\nSetting debounce to default(300)");
+        "300".to_string()
+    });
+    let listen = std::env::var("CQL_LSP_LISTEN").unwrap_or_else(|_| {
+        info!("Listen address wasn't provided.\nSetting transport to default(stdio)");
+        String::new()
+    });
+    let listen_addr = parse_listen_addr(&listen)?;
+
+    let settings = load_settings(&file_config);
+    let formatting_settings = FormattingSettings::from_env(
+        &type_alignment_offset,
+        &format_selectors,
+        &keyword_case,
+        &format_on_save,
+    );
+
+    let debouncer = Debouncer::from_env(&debounce_ms);
+
+    // Loaded before connecting, the first completions don't wait for the cluster
+    let schema_snapshot = if settings.schema_snapshot && !settings.offline {
+        SchemaSnapshot::load()
+    } else {
+        None
+    };
 
-    // Start LSP
-    let stdin = stdin();
-    let stdout = stdout();
     let (service, socket) = LspService::new(|client| Backend {
         client,
         documents: RwLock::new(HashMap::new()),
+        parsed_trees: RwLock::new(HashMap::new()),
+        debouncer,
         current_document: RwLock::new(None),
-        config: settings,
+        config: StdRwLock::new(Arc::new(settings)),
         formatting_config: formatting_settings,
+        db_unreachable: AtomicBool::new(false),
+        schema_snapshot: Arc::new(RwLock::new(schema_snapshot)),
+        datacenters: RwLock::new(None),
+        refresh_tasks: Mutex::new(Vec::new()),
     });
 
-    Server::new(stdin, stdout, socket).serve(service).await;
+    /*
+        Single client per server, same as with stdio.
+        The listener stops accepting once the client connects
+    */
+    match listen_addr {
+        Some(addr) => {
+            let listener = TcpListener::bind(&addr).await?;
+            info!("Listening on tcp://{}", listener.local_addr()?);
+
+            let (stream, peer) = listener.accept().await?;
+            info!("Client connected from {}", peer);
+
+            let (read, write) = tokio::io::split(stream);
+            Server::new(read, write, socket).serve(service).await;
+        }
+        // Nothing else may write to stdout from here on
+        None => Server::new(stdin(), stdout(), socket).serve(service).await,
+    }
 
     Ok(())
 }
@@ -0,0 +1,306 @@
+use dirs::data_dir;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+use crate::cqlsh::{self, Column, CqlError, CqlSettings, Table};
+use crate::lsp::Backend;
+
+/*
+    Schema snapshot
+
+    Keyspaces, tables && columns of the cluster stored as JSON
+    next to the log, <data_dir>/cql_lsp/schema.json
+
+    Snapshot is loaded on startup && serves completions until
+    the cluster answers, afterwards it's rewritten from the live
+    schema && every completion queries the cluster again
+
+    Corrupt snapshot is ignored, it's overwritten on the next refresh
+*/
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    pub keyspaces: Vec<String>,
+    pub tables: Vec<Table>,
+    pub columns: Vec<Column>,
+}
+
+impl SchemaSnapshot {
+    pub fn path() -> PathBuf {
+        let mut path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("cql_lsp");
+        path.push("schema.json");
+        path
+    }
+
+    pub fn load() -> Option<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    // Missing && corrupt files yield None
+    pub fn load_from(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+
+        match serde_json::from_str::<Self>(&content) {
+            Ok(snapshot) => {
+                info!(
+                    "Loaded schema snapshot {:?}: {} keyspaces, {} tables",
+                    path,
+                    snapshot.keyspaces.len(),
+                    snapshot.tables.len()
+                );
+                Some(snapshot)
+            }
+            Err(e) => {
+                warn!("Ignoring corrupt schema snapshot {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(&Self::path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        info!("Saved schema snapshot {:?}", path);
+
+        Ok(())
+    }
+
+    pub async fn query(config: &CqlSettings) -> Result<Self, CqlError> {
+        let keyspaces = cqlsh::query_keyspaces(config).await?;
+        let tables = cqlsh::query_g_tables(config).await?;
+        // Snapshot keeps the whole schema, completion cap doesn't apply
+        let columns = cqlsh::query_g_fields(config, 0).await?;
+
+        Ok(Self {
+            keyspaces: keyspaces.into_iter().collect(),
+            tables,
+            columns,
+        })
+    }
+}
+
+impl Backend {
+    /*
+        Replaces snapshot on disk with the live schema,
+        completions stop using the snapshot once it succeeds
+
+        Reading the whole schema of a big cluster takes a while,
+        it runs in the background && the snapshot keeps serving meanwhile.
+        Refresh is tracked in refresh_tasks, shutdown aborts it
+    */
+    pub async fn spawn_schema_snapshot_refresh(&self) -> JoinHandle<()> {
+        let config = self.config();
+        let schema_snapshot = Arc::clone(&self.schema_snapshot);
+
+        let refresh = tokio::spawn(async move {
+            match SchemaSnapshot::query(&config).await {
+                Ok(snapshot) => {
+                    if let Err(e) = snapshot.save() {
+                        warn!("Failed to save schema snapshot: {}", e);
+                    }
+                    *schema_snapshot.write().await = None;
+                    info!("Schema snapshot reconciled with the cluster");
+                }
+                Err(e) => warn!("Failed to refresh schema snapshot: {}", e),
+            }
+        });
+
+        // Finished refreshes are dropped, every reload would add one
+        let mut refresh_tasks = self.refresh_tasks.lock().await;
+        refresh_tasks.retain(|task| !task.is_finished());
+        refresh_tasks.push(refresh.abort_handle());
+
+        refresh
+    }
+
+    pub async fn schema_keyspaces(&self) -> Vec<String> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot.keyspaces.clone();
+        }
+
+        self.unwrap_query(cqlsh::query_keyspaces(&self.config()))
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    pub async fn schema_g_tables(&self) -> Vec<Table> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot.tables.clone();
+        }
+
+        self.unwrap_query(cqlsh::query_g_tables(&self.config()))
+            .await
+    }
+
+    pub async fn schema_keyspace_scoped_tables(&self, keyspace: &str) -> Vec<Table> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot
+                .tables
+                .iter()
+                .filter(|table| table.keyspace_name == keyspace)
+                .cloned()
+                .collect();
+        }
+
+        self.unwrap_query(cqlsh::query_keyspace_scoped_tables(
+            &self.config(),
+            keyspace,
+        ))
+        .await
+    }
+
+    pub async fn schema_g_fields(&self) -> Vec<Column> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot.columns.clone();
+        }
+
+        self.unwrap_query(cqlsh::query_g_fields(
+            &self.config(),
+            self.config().max_completion_items,
+        ))
+        .await
+    }
+
+    pub async fn schema_keyspace_scoped_fields(&self, keyspace: &str) -> Vec<Column> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot
+                .columns
+                .iter()
+                .filter(|column| column.keyspace_name == keyspace)
+                .cloned()
+                .collect();
+        }
+
+        let config = self.config();
+        self.unwrap_query(cqlsh::query_keyspace_scoped_fields(
+            &config,
+            keyspace,
+            config.max_completion_items,
+        ))
+        .await
+    }
+
+    pub async fn schema_hard_scoped_fields(&self, keyspace: &str, table: &str) -> Vec<Column> {
+        if let Some(snapshot) = self.schema_snapshot.read().await.as_ref() {
+            return snapshot
+                .columns
+                .iter()
+                .filter(|column| column.keyspace_name == keyspace && column.table_name == table)
+                .cloned()
+                .collect();
+        }
+
+        self.unwrap_query(cqlsh::query_hard_scoped_fields(
+            &self.config(),
+            keyspace,
+            table,
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_the_snapshot() {
+        let service = Backend::test_service();
+        let backend = service.inner();
+        *backend.schema_snapshot.write().await = Some(SchemaSnapshot {
+            keyspaces: vec!["ks".to_string()],
+            ..Default::default()
+        });
+
+        // Offline backend, the query fails without touching the network
+        backend.spawn_schema_snapshot_refresh().await.await.unwrap();
+
+        assert_eq!(backend.schema_keyspaces().await, ["ks"]);
+    }
+
+    #[test]
+    fn corrupt_snapshot_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("cql_lsp_schema_{}", std::process::id()));
+        let path = dir.join("schema.json");
+
+        assert!(SchemaSnapshot::load_from(&path).is_none());
+
+        SchemaSnapshot {
+            keyspaces: vec!["ks".to_string()],
+            ..Default::default()
+        }
+        .save_to(&path)
+        .unwrap();
+        assert_eq!(SchemaSnapshot::load_from(&path).unwrap().keyspaces, ["ks"]);
+
+        for garbage in [
+            "",
+            "not json",
+            "{\"keyspaces\": [",
+            "{\"keyspaces\": 1}",
+            "[]",
+        ] {
+            std::fs::write(&path, garbage).unwrap();
+            assert!(SchemaSnapshot::load_from(&path).is_none(), "{:?}", garbage);
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_running_refreshes() {
+        use tower_lsp::LanguageServer;
+
+        let service = Backend::test_service();
+        let backend = service.inner();
+
+        // Accepts the connection but never answers, the refresh hangs
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut settings = CqlSettings::new();
+        settings.urls = vec![listener.local_addr().unwrap().to_string()];
+        settings.connection_timeout_secs = 60;
+        *backend.config.write().unwrap() = Arc::new(settings);
+
+        let tasks = || {
+            tokio::runtime::Handle::current()
+                .metrics()
+                .num_alive_tasks()
+        };
+        let idle = tasks();
+
+        let first = backend.spawn_schema_snapshot_refresh().await;
+        let second = backend.spawn_schema_snapshot_refresh().await;
+        assert_eq!(tasks(), idle + 2);
+        assert_eq!(backend.refresh_tasks.lock().await.len(), 2);
+
+        // Let them reach the connection attempt
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!first.is_finished() && !second.is_finished());
+
+        backend.shutdown().await.unwrap();
+
+        assert!(first.await.unwrap_err().is_cancelled());
+        assert!(second.await.unwrap_err().is_cancelled());
+        assert!(backend.refresh_tasks.lock().await.is_empty());
+
+        // Connection tasks of the aborted sessions wind down on their own
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while tasks() > idle {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::consts::*;
+use crate::lsp::Backend;
+
+/*
+    Semantic tokens legend, index of the type is sent to the client
+*/
+pub const SEMANTIC_TOKEN_TYPES: [SemanticTokenType; 8] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::NAMESPACE,
+];
+
+const KEYWORD: u32 = 0;
+const TYPE: u32 = 1;
+const FUNCTION: u32 = 2;
+const VARIABLE: u32 = 3;
+const STRING: u32 = 4;
+const NUMBER: u32 = 5;
+const COMMENT: u32 = 6;
+const NAMESPACE: u32 = 7;
+
+pub fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: SEMANTIC_TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![],
+    }
+}
+
+/*
+    Semantic tokens from the cached parse tree
+
+    Table names aren't highlighted, keyspaces are namespaces
+    and remaining identifiers are treated as columns
+*/
+impl Backend {
+    pub async fn get_semantic_tokens(&self, uri: &Url) -> Option<Vec<SemanticToken>> {
+        let text = self.documents.read().await.get(uri)?.clone();
+        let tree = self.tree_for(uri).await?;
+
+        let mut tokens = Vec::<(usize, usize, u32)>::new();
+        Self::collect_semantic_tokens(tree.root_node(), &text, &mut tokens);
+
+        Some(Self::encode_semantic_tokens(&text, &tokens))
+    }
+
+    fn collect_semantic_tokens(node: Node, text: &str, tokens: &mut Vec<(usize, usize, u32)>) {
+        if let Some(token_type) = Self::semantic_token_type(node, text) {
+            tokens.push((node.start_byte(), node.end_byte(), token_type));
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            Self::collect_semantic_tokens(child, text, tokens);
+        }
+    }
+
+    fn semantic_token_type(node: Node, text: &str) -> Option<u32> {
+        match node.kind() {
+            "line_comment" | "block_comment" => return Some(COMMENT),
+            "string_literal" => return Some(STRING),
+            "integer" | "float" => return Some(NUMBER),
+            _ => {}
+        }
+
+        if node.child_count() != 0 {
+            return None;
+        }
+
+        let value = node.utf8_text(text.as_bytes()).ok()?;
+        let lw_value = value.to_lowercase();
+        let parent_kind = node.parent().map(|parent| parent.kind()).unwrap_or("");
+
+        // Parentheses of calls aren't always siblings e.g. VALUES (uuid(), ...)
+        let is_call = text[node.end_byte()..].trim_start().starts_with('(');
+        let is_function = parent_kind == "func_definition"
+            || CQL_NATIVE_FUNCTIONS
+                .iter()
+                .any(|function| function.label == value);
+
+        // SET n = 1 parses numbers as list_identifier
+        if node.kind() == "list_identifier" {
+            return match value.parse::<f64>().is_ok() {
+                true => Some(NUMBER),
+                false => Some(VARIABLE),
+            };
+        }
+
+        if node.kind() == "identifier" {
+            // ks.users
+            if parent_kind == "key_space_name" {
+                return match node.next_sibling().is_some_and(|next| next.kind() == ".") {
+                    true => Some(NAMESPACE),
+                    false => None,
+                };
+            }
+
+            // WHERE id = 1 columns are wrapped into table_keyspace_name too
+            if parent_kind == "table_keyspace_name" {
+                let is_column = node
+                    .parent()
+                    .and_then(|parent| parent.parent())
+                    .is_some_and(|grand_parent| grand_parent.kind() == "selector_conditions");
+
+                return is_column.then_some(VARIABLE);
+            }
+
+            if is_call {
+                return Some(FUNCTION);
+            }
+
+            let command_keyword = Self::command_keyword(node, text);
+            if command_keyword.as_deref() == Some("use") {
+                return Some(NAMESPACE);
+            }
+
+            // CREATE KEYSPACE [IF NOT EXISTS] ks; DROP KEYSPACE [IF EXISTS] ks;
+            let previous = node
+                .prev_sibling()
+                .and_then(|previous| previous.utf8_text(text.as_bytes()).ok())
+                .map(|previous| previous.to_lowercase());
+
+            if matches!(previous.as_deref(), Some("keyspace") | Some("exists"))
+                && matches!(
+                    command_keyword.as_deref(),
+                    Some("create" | "drop" | "alter")
+                )
+            {
+                return Some(NAMESPACE);
+            }
+
+            // LIMIT / ALLOW FILTERING etc. end up as identifiers
+            if value.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+                && KEYWORDS_STRINGS_LWC.contains(&lw_value)
+            {
+                return Some(KEYWORD);
+            }
+
+            return Some(VARIABLE);
+        }
+
+        // Anonymous tokens, only words are highlighted
+        if node.is_named() || !value.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        // VALUES ( / IN ( / PRIMARY KEY ( aren't calls
+        if is_call && (is_function || !KEYWORDS_STRINGS_LWC.contains(&lw_value)) {
+            return Some(FUNCTION);
+        }
+
+        if parent_kind.starts_with("cql_types") && CQL_TYPES_LWC.contains(&lw_value) {
+            return Some(TYPE);
+        }
+
+        Some(KEYWORD)
+    }
+
+    /*
+        First keyword of the command the node belongs to
+    */
+    pub fn command_keyword(node: Node, text: &str) -> Option<String> {
+        let mut current = Some(node);
+
+        while let Some(n) = current {
+            if n.kind() == "cql_commands" {
+                let command = n.utf8_text(text.as_bytes()).ok()?;
+                return command
+                    .split_whitespace()
+                    .next()
+                    .map(|kw| kw.to_lowercase());
+            }
+            current = n.parent();
+        }
+
+        None
+    }
+
+    /*
+        Delta encoding required by the spec
+
+        Positions && lengths are in UTF-16 code units,
+        multi-line tokens e.g. /* ... */ are split per line
+    */
+    pub fn encode_semantic_tokens(
+        text: &str,
+        tokens: &[(usize, usize, u32)],
+    ) -> Vec<SemanticToken> {
+        let mut result = Vec::<SemanticToken>::new();
+        let mut previous = Position {
+            line: 0,
+            character: 0,
+        };
+
+        for (start, end, token_type) in tokens {
+            let mut line_start = *start;
+
+            for segment in text[*start..*end].split('\n') {
+                let segment = segment.trim_end_matches('\r');
+                let length = segment.encode_utf16().count() as u32;
+                let position = Self::offset_to_position(text, line_start);
+                line_start += segment.len() + 1;
+
+                if length == 0 {
+                    continue;
+                }
+
+                let delta_line = position.line - previous.line;
+                let delta_start = match delta_line {
+                    0 => position.character - previous.character,
+                    _ => position.character,
+                };
+
+                result.push(SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length,
+                    token_type: *token_type,
+                    token_modifiers_bitset: 0,
+                });
+
+                previous = position;
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(delta_line: u32, delta_start: u32, length: u32, token_type: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn non_ascii_offsets_are_utf16() {
+        let text = "SELECT 'だよ🚀', id FROM t;";
+        let string_start = text.find('\'').unwrap();
+        let string_end = text.rfind('\'').unwrap() + 1;
+        let column_start = text.find("id").unwrap();
+
+        let tokens = [
+            (0, 6, KEYWORD),
+            (string_start, string_end, STRING),
+            (column_start, column_start + 2, VARIABLE),
+        ];
+
+        assert_eq!(
+            Backend::encode_semantic_tokens(text, &tokens),
+            [
+                token(0, 0, 6, KEYWORD),
+                // ' だ よ 🚀 ' -> 1 + 1 + 1 + 2 + 1
+                token(0, 7, 6, STRING),
+                token(0, 8, 2, VARIABLE),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_tokens_are_split_per_line() {
+        let text = "/* だよ\n🚀 */ SELECT";
+        let comment_end = text.find(" SELECT").unwrap();
+
+        let tokens = [
+            (0, comment_end, COMMENT),
+            (comment_end + 1, text.len(), KEYWORD),
+        ];
+
+        assert_eq!(
+            Backend::encode_semantic_tokens(text, &tokens),
+            [
+                token(0, 0, 5, COMMENT),
+                token(1, 0, 5, COMMENT),
+                token(0, 6, 6, KEYWORD),
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use crate::cqlsh::{CqlSettings, TlsSettings};
 use dirs::data_dir;
+use log::{info, warn};
 use std::{fs::File, io::Write, path::PathBuf};
 
 #[derive(Debug, Clone)]
@@ -12,11 +14,44 @@ pub struct SetupConfig {
     pub context_based_select: bool,
 }
 
-pub fn setup_logger() -> Result<(), fern::InitError> {
-    let mut log_path = data_dir().unwrap_or_else(|| PathBuf::from("."));
-    log_path.push("cql_lsp");
-    std::fs::create_dir_all(&log_path).expect("Failed to create log directory");
-    log_path.push("output.log");
+impl Default for SetupConfig {
+    fn default() -> Self {
+        Self {
+            password: String::from("cassandra"),
+            user_name: String::from("cassandra"),
+            ip_addr: String::from("127.0.0.1"),
+            context_based_select: false,
+        }
+    }
+}
+
+/*
+    Logs go to a file only, stdout is the LSP
+    JSON-RPC channel && must stay clean
+
+    log_level  -> error, warn, info, debug, trace, off
+    log_path   -> empty for <data_dir>/cql_lsp/output.log
+*/
+pub fn setup_logger(log_level: &str, log_path: &str) -> Result<(), fern::InitError> {
+    let level = log_level
+        .parse::<log::LevelFilter>()
+        .unwrap_or(log::LevelFilter::Info);
+
+    let log_path = match log_path.trim() {
+        "" => {
+            let mut log_path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+            log_path.push("cql_lsp");
+            log_path.push("output.log");
+            log_path
+        }
+        path => PathBuf::from(path),
+    };
+
+    if let Some(parent) = log_path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
 
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -28,27 +63,35 @@ pub fn setup_logger() -> Result<(), fern::InitError> {
                 message
             ))
         })
-        .level(log::LevelFilter::Info)
-        .chain(std::io::stdout())
-        .chain(fern::log_file(log_path)?)
+        .level(level)
+        .chain(fern::log_file(&log_path)?)
         .apply()?;
 
+    info!("Logging to {:?} with level {}", log_path, level);
+
     Ok(())
 }
 
-pub fn setup_config() -> Result<(), Box<dyn std::error::Error>> {
+fn config_path() -> PathBuf {
     let mut config_path = data_dir().unwrap_or_else(|| PathBuf::from("."));
     config_path.push("cql_lsp/config.lsp");
-    println!("Config: {:?}", config_path.to_str());
+    config_path
+}
+
+pub fn setup_config() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = config_path();
+    info!("Config: {:?}", config_path.to_str());
 
     if !config_path.exists() {
         let mut file =
             File::create_new(config_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
+        let defaults = SetupConfig::default();
+
         write!(
             file,
-            "[db_context]\npassword = \"{}\"\nuser = \"{}\"\nip = \"{}\"\n",
-            "cassandara", "cassandara", "127.0.0.1"
+            "[db_context]\npassword = \"{}\"\nuser = \"{}\"\nip = \"{}\"\n\n[formatting]\ncontext_based_select = {}\n",
+            defaults.password, defaults.user_name, defaults.ip_addr, defaults.context_based_select
         )?;
 
         return Ok(());
@@ -56,3 +99,316 @@ pub fn setup_config() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/*
+    CqlSettings from env variables, missing ones are
+    taken from config.lsp or defaults
+
+    Called on startup && by cql.reloadConfig
+*/
+pub fn load_settings(file_config: &SetupConfig) -> CqlSettings {
+    let url = std::env::var("CQL_LSP_DB_URL").unwrap_or_else(|_| {
+        // Defaults to localhost and NOT docker
+        info!(
+            "Db url wasn't provided. Setting url to config value({})",
+            file_config.ip_addr
+        );
+        file_config.ip_addr.clone()
+    });
+    let pswd = std::env::var("CQL_LSP_DB_PASSWD").unwrap_or_else(|_| {
+        info!("Db pswd wasn't provided.\nSetting pswd to config value");
+        file_config.password.clone()
+    });
+    let user = std::env::var("CQL_LSP_DB_USER").unwrap_or_else(|_| {
+        info!(
+            "Db user wasn't provided.\nSetting user to config value({})",
+            file_config.user_name
+        );
+        file_config.user_name.clone()
+    });
+    let timeout = std::env::var("CQL_LSP_DB_TIMEOUT").unwrap_or_else(|_| {
+        info!("Db timeout wasn't provided.\nSetting timeout to default(3)");
+        "3".to_string()
+    });
+    let db_retries = std::env::var("CQL_LSP_DB_RETRIES").unwrap_or_else(|_| {
+        info!("Db retries weren't provided.\nSetting retries to default(2)");
+        "2".to_string()
+    });
+    let tls_enabled = std::env::var("CQL_LSP_TLS_ENABLED").unwrap_or_else(|_| {
+        info!("TLS mode wasn't provided.\nSetting TLS mode to default(false)");
+        "false".to_string()
+    });
+    let tls_ca_path = std::env::var("CQL_LSP_TLS_CA_PATH").unwrap_or_default();
+    let tls_client_cert = std::env::var("CQL_LSP_TLS_CLIENT_CERT").unwrap_or_default();
+    let tls_client_key = std::env::var("CQL_LSP_TLS_CLIENT_KEY").unwrap_or_default();
+    let context_based_select = std::env::var("CQL_LSP_CONTEXT_BASED_SELECT").unwrap_or_else(|_| {
+        info!(
+            "Context based select mode wasn't provided.\nSetting context based select to config value({})",
+            file_config.context_based_select
+        );
+        file_config.context_based_select.to_string()
+    });
+    let qualified_drop = std::env::var("CQL_LSP_QUALIFIED_DROP").unwrap_or_else(|_| {
+        info!("Qualified drop mode wasn't provided.\nSetting qualified drop to default(false)");
+        "false".to_string()
+    });
+    let command_snippets = std::env::var("CQL_LSP_COMMAND_SNIPPETS").unwrap_or_else(|_| {
+        info!("Command snippets mode wasn't provided.\nSetting command snippets to default(false)");
+        "false".to_string()
+    });
+    let validate_on_save = std::env::var("CQL_LSP_VALIDATE_ON_SAVE").unwrap_or_else(|_| {
+        info!("Validate on save mode wasn't provided.\nSetting validate on save to default(false)");
+        "false".to_string()
+    });
+    let live_validation = std::env::var("CQL_LSP_LIVE_VALIDATION").unwrap_or_else(|_| {
+        info!("Live validation mode wasn't provided.\nSetting live validation to default(false)");
+        "false".to_string()
+    });
+    let offline = std::env::var("CQL_LSP_OFFLINE").unwrap_or_else(|_| {
+        info!("Offline mode wasn't provided.\nSetting offline mode to default(false)");
+        "false".to_string()
+    });
+    let schema_snapshot = std::env::var("CQL_LSP_SCHEMA_SNAPSHOT").unwrap_or_else(|_| {
+        info!("Schema snapshot mode wasn't provided.\nSetting schema snapshot to default(false)");
+        "false".to_string()
+    });
+    let enable_exec = std::env::var("CQL_LSP_ENABLE_EXEC").unwrap_or_else(|_| {
+        info!("Exec mode wasn't provided.\nSetting exec mode to default(false)");
+        "false".to_string()
+    });
+    let allow_mutations = std::env::var("CQL_LSP_ALLOW_MUTATIONS").unwrap_or_else(|_| {
+        info!("Mutations mode wasn't provided.\nSetting mutations mode to default(false)");
+        "false".to_string()
+    });
+
+    let max_completion_items = std::env::var("CQL_LSP_MAX_COMPLETION_ITEMS").unwrap_or_else(|_| {
+        info!(
+            "Max completion items weren't provided.\nSetting max completion items to default(5000)"
+        );
+        "5000".to_string()
+    });
+
+    let query_timeout_ms = std::env::var("CQL_LSP_QUERY_TIMEOUT_MS").unwrap_or_else(|_| {
+        info!("Query timeout wasn't provided.\nSetting query timeout to default(2000)");
+        "2000".to_string()
+    });
+
+    let dialect = std::env::var("CQL_LSP_DIALECT").unwrap_or_else(|_| {
+        info!("Dialect wasn't provided.\nSetting dialect to default(cassandra)");
+        "cassandra".to_string()
+    });
+
+    let tls_settings = TlsSettings::from_env(
+        &tls_enabled,
+        &tls_ca_path,
+        &tls_client_cert,
+        &tls_client_key,
+    );
+
+    CqlSettings::from_env(
+        &url,
+        &pswd,
+        &user,
+        &timeout,
+        tls_settings,
+        &enable_exec,
+        &allow_mutations,
+        &db_retries,
+        &validate_on_save,
+        &live_validation,
+        &context_based_select,
+        &offline,
+        &schema_snapshot,
+        &qualified_drop,
+        &command_snippets,
+        &max_completion_items,
+        &query_timeout_ms,
+        &dialect,
+    )
+}
+
+/*
+    Reads config.lsp written by setup_config
+
+    Missing file yields defaults, malformed file is
+    reported && ignored as a whole
+*/
+pub fn load_config() -> SetupConfig {
+    let config_path = config_path();
+
+    let Ok(content) = std::fs::read_to_string(&config_path) else {
+        info!(
+            "Config {:?} wasn't found. Using default config",
+            config_path
+        );
+        return SetupConfig::default();
+    };
+
+    parse_config(&content).unwrap_or_else(|e| {
+        warn!(
+            "Invalid config {:?}: {}. Using default config",
+            config_path, e
+        );
+        SetupConfig::default()
+    })
+}
+
+/*
+    [db_context]
+    password = "cassandra"
+    user = "cassandra"
+    ip = "127.0.0.1"
+
+    [formatting]
+    context_based_select = false
+*/
+pub fn parse_config(content: &str) -> Result<SetupConfig, String> {
+    let mut config = SetupConfig::default();
+    let mut section = String::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let line_number = index + 1;
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            section = name
+                .strip_suffix(']')
+                .ok_or(format!("line {}: unclosed section {:?}", line_number, line))?
+                .trim()
+                .to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(format!("line {}: expected key = value", line_number))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        let string_value = || -> Result<String, String> {
+            value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .map(String::from)
+                .ok_or(format!(
+                    "line {}: {} must be a quoted string",
+                    line_number, key
+                ))
+        };
+
+        match (section.as_str(), key) {
+            ("db_context", "password") => config.password = string_value()?,
+            ("db_context", "user") => config.user_name = string_value()?,
+            ("db_context", "ip") => config.ip_addr = string_value()?,
+            ("formatting", "context_based_select") => {
+                config.context_based_select = value
+                    .parse()
+                    .map_err(|_| format!("line {}: {} must be true or false", line_number, key))?
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: unknown key {:?} in section [{}]",
+                    line_number, key, section
+                ));
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+/*
+    CQL_LSP_LISTEN
+
+    ""                      -> None, stdio
+    tcp://127.0.0.1:9257    -> Some(127.0.0.1:9257)
+*/
+pub fn parse_listen_addr(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+
+    if value.is_empty() || value == "stdio" {
+        return Ok(None);
+    }
+
+    let addr = value.strip_prefix("tcp://").ok_or(format!(
+        "unsupported transport {:?}, expected tcp://host:port",
+        value
+    ))?;
+
+    if addr
+        .rsplit_once(':')
+        .is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())
+    {
+        return Err(format!(
+            "invalid listen address {:?}, expected tcp://host:port",
+            value
+        ));
+    }
+
+    Ok(Some(addr.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_parsed() {
+        let config = parse_config(
+            "# written by setup_config\n[db_context]\npassword = \"secret\"\nuser = \"admin\"\nip = \"10.0.0.1\"\n\n[formatting]\ncontext_based_select = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.password, "secret");
+        assert_eq!(config.user_name, "admin");
+        assert_eq!(config.ip_addr, "10.0.0.1");
+        assert!(config.context_based_select);
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        let error = parse_config("[db_context]\nport = \"9042\"\n").unwrap_err();
+        assert!(error.contains("unknown key \"port\""), "{}", error);
+
+        // Known key in the wrong section
+        assert!(parse_config("[formatting]\nuser = \"admin\"\n").is_err());
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        let error = parse_config("[db_context]\nuser = admin\n").unwrap_err();
+        assert!(error.contains("must be a quoted string"), "{}", error);
+
+        assert!(parse_config("[formatting]\ncontext_based_select = yes\n").is_err());
+        assert!(parse_config("[db_context\n").is_err());
+        assert!(parse_config("[db_context]\nuser\n").is_err());
+    }
+
+    #[test]
+    fn listen_addr_is_parsed() {
+        assert_eq!(parse_listen_addr(""), Ok(None));
+        assert_eq!(parse_listen_addr("stdio"), Ok(None));
+        assert_eq!(
+            parse_listen_addr(" tcp://127.0.0.1:9257 "),
+            Ok(Some("127.0.0.1:9257".to_string()))
+        );
+        assert_eq!(
+            parse_listen_addr("tcp://localhost:0"),
+            Ok(Some("localhost:0".to_string()))
+        );
+
+        for invalid in [
+            "127.0.0.1:9257",
+            "udp://127.0.0.1:9257",
+            "tcp://127.0.0.1",
+            "tcp://:9257",
+            "tcp://127.0.0.1:port",
+            "tcp://127.0.0.1:70000",
+        ] {
+            assert!(parse_listen_addr(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
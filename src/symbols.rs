@@ -0,0 +1,115 @@
+use tower_lsp::lsp_types::*;
+
+use crate::lsp::Backend;
+
+/*
+    Workspace symbols
+
+    Keyspaces, tables, types, functions and aggregates
+    defined by CREATE statements of open documents
+
+    CREATE TABLE IF NOT EXISTS ks.users (...)  -> users | ks
+    CREATE OR REPLACE FUNCTION ks.f (...)      -> f | ks
+*/
+impl Backend {
+    pub async fn get_workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        let documents: Vec<(Url, String)> = self
+            .documents
+            .read()
+            .await
+            .iter()
+            .map(|(uri, text)| (uri.clone(), text.clone()))
+            .collect();
+
+        let query = query.to_lowercase();
+        let mut symbols = Vec::<SymbolInformation>::new();
+
+        for (uri, text) in documents {
+            let mut keyspace: Option<String> = None;
+
+            for (start, end) in Self::statement_ranges(&text) {
+                let statement = &text[start..end];
+
+                if let Some(use_keyspace) = self.get_use_keyspace(statement) {
+                    keyspace = Some(use_keyspace);
+                    continue;
+                }
+
+                let Some((kind, name)) = Self::defined_symbol(statement) else {
+                    continue;
+                };
+
+                let (container, name) = match name.split_once('.') {
+                    Some((ksp, object)) => (Some(ksp.to_string()), object.to_string()),
+                    None if kind == SymbolKind::NAMESPACE => (None, name),
+                    None => (keyspace.clone(), name),
+                };
+
+                if !name.to_lowercase().contains(&query) {
+                    continue;
+                }
+
+                #[allow(deprecated)]
+                symbols.push(SymbolInformation {
+                    name,
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: Range {
+                            start: Self::offset_to_position(&text, start),
+                            end: Self::offset_to_position(&text, end),
+                        },
+                    },
+                    container_name: container,
+                });
+            }
+        }
+
+        symbols
+    }
+
+    /*
+        Kind && name of the object created by the statement,
+        quotes are stripped from the name
+    */
+    fn defined_symbol(statement: &str) -> Option<(SymbolKind, String)> {
+        let words: Vec<&str> = statement.split_whitespace().collect();
+        let lw_words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+
+        if lw_words.first().map(String::as_str) != Some("create") {
+            return None;
+        }
+
+        let mut index = 1;
+        if lw_words.get(1).map(String::as_str) == Some("or")
+            && lw_words.get(2).map(String::as_str) == Some("replace")
+        {
+            index = 3;
+        }
+
+        let kind = match lw_words.get(index)?.as_str() {
+            "keyspace" => SymbolKind::NAMESPACE,
+            "table" => SymbolKind::CLASS,
+            "type" => SymbolKind::STRUCT,
+            "function" => SymbolKind::FUNCTION,
+            "aggregate" => SymbolKind::FUNCTION,
+            _ => return None,
+        };
+        index += 1;
+
+        if lw_words.get(index).map(String::as_str) == Some("if") {
+            index += 3;
+        }
+
+        let name = words.get(index)?;
+        let name = name
+            .split(['(', ';'])
+            .next()
+            .unwrap_or(name)
+            .replace('"', "");
+
+        (!name.is_empty()).then_some((kind, name))
+    }
+}
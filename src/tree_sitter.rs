@@ -1,6 +1,6 @@
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex;
-use tree_sitter::{Node, Parser, TreeCursor};
+use tree_sitter::{Parser, Tree};
 
 pub static TS_CQL: Lazy<Mutex<Parser>> = Lazy::new(|| {
     let mut parser = Parser::new();
@@ -9,3 +9,15 @@ pub static TS_CQL: Lazy<Mutex<Parser>> = Lazy::new(|| {
         .expect("Error loading CQL grammar");
     Mutex::new(parser)
 });
+
+/*
+    Blocking parse for sync code paths e.g. formatting passes,
+    uses its own parser instead of locking TS_CQL
+*/
+pub fn parse_cql(text: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tttx_tree_sitter_cql::LANGUAGE.into())
+        .ok()?;
+    parser.parse(text, None)
+}
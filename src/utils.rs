@@ -1,8 +1,118 @@
 use crate::consts::*;
+use crate::cqlsh::{CqlError, CqlSettings};
 use crate::lsp::Backend;
+use crate::tree_sitter::{TS_CQL, parse_cql};
 use log::info;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tower_lsp::lsp_types::{
+    CompletionItem, MessageType, Position, TextDocumentContentChangeEvent, Url,
+};
+use tree_sitter::{InputEdit, Node, Point, Tree};
+
+/*
+    Relation of a line to /* ... */ comments
+
+    Clause  - line contains start or end of the comment
+    Inside  - line is fully inside of the comment, excluding /* && */
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentLine {
+    Code,
+    Clause,
+    Inside,
+}
 
 impl Backend {
+    /*
+        Current CqlSettings
+
+        Arc is cloned so the lock is never held across an await,
+        queries that already started keep the settings they began with
+    */
+    pub fn config(&self) -> Arc<CqlSettings> {
+        self.config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /*
+        Unwraps result of cqlsh query, failed queries yield no items
+
+        Connection && auth errors are reported to the client, otherwise it's
+        hard to tell an unreachable cluster from an empty schema. They're shown
+        once until the next successful query. Missing objects stay quiet,
+        they're expected while the statement is still being typed
+
+        Query is bounded by CQL_LSP_QUERY_TIMEOUT_MS, opening the session
+        included. Slow query yields no items instead of stalling the completion
+    */
+    pub async fn unwrap_query<T>(
+        &self,
+        query: impl Future<Output = Result<Vec<T>, CqlError>>,
+    ) -> Vec<T> {
+        let timeout_ms = self.config().query_timeout_ms;
+
+        let result = match timeout_ms {
+            0 => query.await,
+            _ => match tokio::time::timeout(Duration::from_millis(timeout_ms), query).await {
+                Ok(result) => result,
+                Err(_) => {
+                    info!("Query timed out after {}ms", timeout_ms);
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Query timed out after {}ms", timeout_ms),
+                        )
+                        .await;
+                    return vec![];
+                }
+            },
+        };
+
+        let e = match result {
+            Ok(items) => {
+                self.db_unreachable.store(false, Ordering::Relaxed);
+                return items;
+            }
+            Err(e) => e,
+        };
+
+        info!("Query failed: {}", e);
+
+        let message = match &e {
+            CqlError::Connection(_) => format!(
+                "Database is unreachable, schema completions are unavailable. {}",
+                e
+            ),
+            CqlError::Auth(_) => format!(
+                "{}. Check CQL_LSP_DB_USER && CQL_LSP_DB_PASSWD, schema completions are unavailable",
+                e
+            ),
+            CqlError::Query(_) | CqlError::Deserialize(_) => {
+                self.client
+                    .log_message(MessageType::WARNING, e.to_string())
+                    .await;
+                return vec![];
+            }
+            CqlError::NotFound(_) | CqlError::Offline => return vec![],
+        };
+
+        self.client
+            .log_message(MessageType::WARNING, format!("Completion failed: {}", e))
+            .await;
+
+        if !self.db_unreachable.swap(true, Ordering::Relaxed) {
+            self.client
+                .show_message(MessageType::WARNING, message)
+                .await;
+        }
+
+        vec![]
+    }
+
     pub fn is_in_string_literal(line: &str, position: u32) -> bool {
         let prefix = match line.get(..position as usize) {
             Some(p) => p,
@@ -30,6 +140,542 @@ impl Backend {
         in_double_quotes || in_single_quotes
     }
 
+    /*
+        Upper case variant sorts above the lower case one
+
+        SELECT -> select_0
+        select -> select_1
+    */
+    pub fn case_sort_text(label: &str) -> String {
+        let variant = match label.chars().any(|c| c.is_lowercase()) {
+            true => 1,
+            false => 0,
+        };
+
+        format!("{}_{}", label.to_lowercase(), variant)
+    }
+
+    /*
+        Identifier typed right before the cursor, lowercased
+        && without the opening quote
+
+        FROM us|        -> us
+        USE "My|        -> my
+        DROP TABLE |    ->
+    */
+    pub fn typed_identifier(line: &str, position: &Position) -> String {
+        let prefix = line.get(..position.character as usize).unwrap_or(line);
+
+        let start = prefix
+            .rfind(|c: char| c.is_whitespace() || matches!(c, '(' | ',' | ';' | '='))
+            .map_or(0, |i| i + 1);
+
+        prefix[start..]
+            .trim_start_matches(['"', '\''])
+            .to_lowercase()
+    }
+
+    /*
+        Schema object items starting with the typed identifier
+
+        filter_text is set to the bare identifier, label && insert text
+        may be quoted or qualified. ks.users is matched by both ks && us
+    */
+    pub fn filter_by_typed(items: Vec<CompletionItem>, typed: &str) -> Vec<CompletionItem> {
+        items
+            .into_iter()
+            .filter_map(|mut item| {
+                let identifier = item.filter_text.clone().unwrap_or(item.label.clone());
+                let lw = identifier.to_lowercase();
+
+                let name = lw.rsplit('.').next().unwrap_or(&lw);
+                if !lw.starts_with(typed) && !name.starts_with(typed) {
+                    return None;
+                }
+
+                item.filter_text = Some(identifier);
+                Some(item)
+            })
+            .collect()
+    }
+
+    /*
+        Applies a single didChange event to the stored text
+
+        Events without range carry the whole document
+    */
+    pub fn apply_content_change(text: &mut String, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let start = Self::position_to_offset(text, &range.start);
+                let end = Self::position_to_offset(text, &range.end).max(start);
+                text.replace_range(start..end, &change.text);
+            }
+            None => *text = change.text.clone(),
+        }
+    }
+
+    /*
+        Tree-sitter edit matching a didChange event,
+        has to be computed before the change is applied to the text
+
+        Events without range replace the whole document,
+        the tree is re-parsed from scratch in that case
+    */
+    pub fn content_change_edit(
+        text: &str,
+        change: &TextDocumentContentChangeEvent,
+    ) -> Option<InputEdit> {
+        let range = change.range?;
+
+        let start_byte = Self::position_to_offset(text, &range.start);
+        let old_end_byte = Self::position_to_offset(text, &range.end).max(start_byte);
+        let new_end_byte = start_byte + change.text.len();
+
+        let start_position = Self::offset_to_point(text, start_byte);
+        let old_end_position = Self::offset_to_point(text, old_end_byte);
+
+        // Point right after the inserted text
+        let new_end_position = match change.text.rfind('\n') {
+            Some(i) => Point {
+                row: start_position.row + change.text.matches('\n').count(),
+                column: change.text.len() - i - 1,
+            },
+            None => Point {
+                row: start_position.row,
+                column: start_position.column + change.text.len(),
+            },
+        };
+
+        Some(InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position,
+        })
+    }
+
+    /*
+        Tree-sitter point of a byte offset, columns are counted in bytes
+    */
+    pub fn offset_to_point(text: &str, offset: usize) -> Point {
+        let offset = offset.min(text.len());
+        let prefix = &text[..offset];
+        let line_start = prefix.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        Point {
+            row: prefix.matches('\n').count(),
+            column: offset - line_start,
+        }
+    }
+
+    /*
+        Re-parses document and caches the tree,
+        old_tree has to be edited to match the new text
+    */
+    pub async fn update_tree(&self, uri: &Url, text: &str, old_tree: Option<Tree>) {
+        let mut parser = TS_CQL.lock().await;
+        let tree = parser.parse(text, old_tree.as_ref());
+        drop(parser);
+
+        let mut trees = self.parsed_trees.write().await;
+        match tree {
+            Some(tree) => trees.insert(uri.clone(), tree),
+            None => trees.remove(uri),
+        };
+    }
+
+    /*
+        Cached parse tree of the document
+
+        Documents that weren't parsed yet are parsed on demand
+    */
+    pub async fn tree_for(&self, uri: &Url) -> Option<Tree> {
+        if let Some(tree) = self.parsed_trees.read().await.get(uri) {
+            return Some(tree.clone());
+        }
+
+        let text = self.documents.read().await.get(uri)?.clone();
+        self.update_tree(uri, &text, None).await;
+
+        self.parsed_trees.read().await.get(uri).cloned()
+    }
+
+    /*
+        Whether the position is inside of a comment node
+
+        Only cached trees are used, None means there is no tree
+        and line based helpers have to be used instead
+    */
+    pub async fn is_position_in_comment(
+        &self,
+        uri: &Url,
+        text: &str,
+        position: &Position,
+    ) -> Option<bool> {
+        let tree = self.parsed_trees.read().await.get(uri)?.clone();
+        let offset = Self::position_to_offset(text, position);
+        let root = tree.root_node();
+
+        let comment_kind = |offset: usize| -> Option<&'static str> {
+            let mut current = root.descendant_for_byte_range(offset, offset);
+            while let Some(node) = current {
+                if node.kind() == "line_comment" || node.kind() == "block_comment" {
+                    return Some(node.kind());
+                }
+                current = node.parent();
+            }
+            None
+        };
+
+        // Cursor at the end of -- comment is still inside of it
+        let in_comment = comment_kind(offset).is_some()
+            || (offset > 0 && comment_kind(offset - 1) == Some("line_comment"));
+
+        Some(in_comment)
+    }
+
+    /*
+        Block comment lines of working lines
+
+        Parsed from lines themselves, since formatting passes
+        add/remove lines. None if lines couldn't be parsed
+    */
+    pub fn block_comment_lines(&self, lines: &[String]) -> Option<Vec<CommentLine>> {
+        let tree = parse_cql(&lines.join("\n"))?;
+        let mut comment_lines = vec![CommentLine::Code; lines.len()];
+
+        fn collect(node: Node, comment_lines: &mut [CommentLine]) {
+            if node.kind() == "block_comment" {
+                let start = node.start_position().row;
+                let end = node.end_position().row;
+
+                if end > start {
+                    comment_lines[start + 1..end].fill(CommentLine::Inside);
+                }
+                comment_lines[start] = CommentLine::Clause;
+                comment_lines[end] = CommentLine::Clause;
+                return;
+            }
+
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                collect(child, comment_lines);
+            }
+        }
+
+        collect(tree.root_node(), &mut comment_lines);
+
+        Some(comment_lines)
+    }
+
+    /*
+        Tree based is_line_in_multiline_comment,
+        falls back to the line scan if lines couldn't be parsed
+    */
+    pub fn is_comment_inside(
+        &self,
+        comment_lines: &Option<Vec<CommentLine>>,
+        index: usize,
+        lines: &[String],
+    ) -> bool {
+        match comment_lines {
+            Some(comment_lines) => comment_lines.get(index) == Some(&CommentLine::Inside),
+            None => self.is_line_in_multiline_comment(&lines[index], index, lines),
+        }
+    }
+
+    /*
+        Tree based is_multi_line_comment_clause,
+        falls back to the line scan if lines couldn't be parsed
+    */
+    pub fn is_comment_clause(
+        &self,
+        comment_lines: &Option<Vec<CommentLine>>,
+        index: usize,
+        lines: &[String],
+    ) -> bool {
+        match comment_lines {
+            Some(comment_lines) => comment_lines.get(index) == Some(&CommentLine::Clause),
+            None => self.is_multi_line_comment_clause(&lines[index]),
+        }
+    }
+
+    /*
+        Byte offset of an LSP position
+
+        Characters are counted in UTF-16 code units as the spec requires,
+        positions past the end of a line/document are clamped
+    */
+    pub fn position_to_offset(text: &str, position: &Position) -> usize {
+        let mut offset = 0;
+
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            if index == position.line as usize {
+                let content = line.trim_end_matches(['\n', '\r']);
+                let mut units = 0;
+
+                for (byte_index, c) in content.char_indices() {
+                    if units >= position.character as usize {
+                        return offset + byte_index;
+                    }
+                    units += c.len_utf16();
+                }
+
+                return offset + content.len();
+            }
+
+            offset += line.len();
+        }
+
+        text.len()
+    }
+
+    /*
+        LSP position of a byte offset, inverse of position_to_offset
+    */
+    pub fn offset_to_position(text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let prefix = &text[..offset];
+        let line_start = prefix.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        Position {
+            line: prefix.matches('\n').count() as u32,
+            character: prefix[line_start..].encode_utf16().count() as u32,
+        }
+    }
+
+    /*
+        Statement containing the given byte offset
+
+        Statements are split on ; outside of strings && comments,
+        comments are dropped from the returned statement
+    */
+    pub fn statement_at(text: &str, offset: usize) -> Option<String> {
+        let mut statement = String::new();
+        let mut chars = text.char_indices().peekable();
+        let mut in_single_quotes = false;
+        let mut in_double_quotes = false;
+
+        while let Some((i, c)) = chars.next() {
+            let next = chars.peek().map(|(_, n)| *n);
+
+            if !in_single_quotes && !in_double_quotes {
+                // -- and // comments run until the end of the line
+                if (c == '-' && next == Some('-')) || (c == '/' && next == Some('/')) {
+                    while chars.next_if(|(_, n)| *n != '\n').is_some() {}
+                    continue;
+                }
+
+                if c == '/' && next == Some('*') {
+                    chars.next();
+                    while let Some((_, n)) = chars.next() {
+                        if n == '*' && chars.next_if(|(_, n)| *n == '/').is_some() {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+
+                if c == ';' {
+                    if offset <= i {
+                        break;
+                    }
+                    statement.clear();
+                    continue;
+                }
+            }
+
+            match c {
+                '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+                '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+                _ => {}
+            }
+
+            statement.push(c);
+        }
+
+        let statement = statement.trim();
+        (!statement.is_empty()).then(|| statement.to_string())
+    }
+
+    /*
+        Byte ranges of statements, ; is included
+
+        Leading comments aren't part of the statement,
+        BEGIN BATCH ... APPLY BATCH; is a single statement
+    */
+    pub fn statement_ranges(text: &str) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::<(usize, usize)>::new();
+        let mut chars = text.char_indices().peekable();
+        let mut start: Option<usize> = None;
+        let mut end = 0;
+        let mut in_single_quotes = false;
+        let mut in_double_quotes = false;
+
+        while let Some((i, c)) = chars.next() {
+            let next = chars.peek().map(|(_, n)| *n);
+
+            if !in_single_quotes && !in_double_quotes {
+                if (c == '-' && next == Some('-')) || (c == '/' && next == Some('/')) {
+                    while chars.next_if(|(_, n)| *n != '\n').is_some() {}
+                    continue;
+                }
+
+                if c == '/' && next == Some('*') {
+                    chars.next();
+                    while let Some((_, n)) = chars.next() {
+                        if n == '*' && chars.next_if(|(_, n)| *n == '/').is_some() {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+
+                if c == ';' {
+                    if let Some(s) = start {
+                        let statement = text[s..i].to_lowercase();
+                        let words: Vec<&str> = statement.split_whitespace().collect();
+                        let is_open_batch = words.first() == Some(&"begin")
+                            && !words.ends_with(&["apply", "batch"]);
+
+                        if !is_open_batch {
+                            ranges.push((s, i + 1));
+                            start = None;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            if c.is_whitespace() {
+                continue;
+            }
+
+            match c {
+                '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+                '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+                _ => {}
+            }
+
+            start.get_or_insert(i);
+            end = i + c.len_utf8();
+        }
+
+        if let Some(s) = start {
+            ranges.push((s, end));
+        }
+
+        ranges
+    }
+
+    /*
+        Byte offset of the first whitespace separated occurrence of word
+
+        Unlike line.find(word) it won't match inside of other words
+        e.g timestamp inside last_seen_timestamp
+    */
+    pub fn find_word_offset(line: &str, word: &str) -> Option<usize> {
+        let mut position = 0;
+
+        while let Some(found) = line[position..].find(word).map(|p| p + position) {
+            let end = found + word.len();
+            let starts_word = line[..found].ends_with(char::is_whitespace) || found == 0;
+            let ends_word = end == line.len() || line[end..].starts_with(char::is_whitespace);
+
+            if starts_word && ends_word {
+                return Some(found);
+            }
+
+            position = end;
+        }
+
+        None
+    }
+
+    /*
+        Identifier as it's stored in system_schema
+
+        users        -> users
+        Users        -> users
+        "Users"      -> Users
+        "say ""hi""" -> say "hi"
+
+        Unquoted identifiers are case insensitive && lowercased,
+        quoted ones are kept as is
+    */
+    pub fn parse_identifier(raw: &str) -> String {
+        let raw = raw.trim();
+
+        match raw
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.strip_suffix('"'))
+        {
+            Some(quoted) if raw.len() > 1 => quoted.replace("\"\"", "\""),
+            _ => raw.to_lowercase(),
+        }
+    }
+
+    /*
+        ks.users         -> (Some(ks), users)
+        "Ks"."My.Table"  -> (Some(Ks), My.Table)
+        users            -> (None, users)
+
+        Dots inside of quotes don't split the name
+    */
+    pub fn parse_qualified_name(raw: &str) -> (Option<String>, String) {
+        let mut in_quotes = false;
+
+        for (index, c) in raw.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                '.' if !in_quotes => {
+                    return (
+                        Some(Self::parse_identifier(&raw[..index])),
+                        Self::parse_identifier(&raw[index + 1..]),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        (None, Self::parse_identifier(raw))
+    }
+
+    /*
+        Identifier as it has to be written in a statement
+
+        users        -> users
+        Users        -> "Users"
+        first name   -> "first name"
+
+        Anything but lowercase letters, digits && _ needs quotes,
+        otherwise the name would be lowercased by the cluster
+    */
+    pub fn quote_identifier(name: &str) -> String {
+        let is_plain = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+        if is_plain {
+            return name.to_string();
+        }
+
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    // ks.users, "Ks"."Users"
+    pub fn quote_qualified_name(keyspace: &str, name: &str) -> String {
+        format!(
+            "{}.{}",
+            Self::quote_identifier(keyspace),
+            Self::quote_identifier(name)
+        )
+    }
+
     pub fn line_contains_cql_type(&self, line: &str) -> bool {
         let split: Vec<&str> = line.split_whitespace().collect();
 
@@ -66,7 +712,7 @@ impl Backend {
         false
     }
 
-    pub fn is_line_inside_selectors(&self, line: &str, index: usize, lines: &Vec<String>) -> bool {
+    pub fn is_line_inside_selectors(&self, line: &str, index: usize, lines: &[String]) -> bool {
         if self.line_contains_cql_kw(line) || line.contains(";") || line.len() == 0 {
             return false;
         }
@@ -127,6 +773,23 @@ impl Backend {
         false
     }
 
+    /*
+        BEGIN [UNLOGGED | COUNTER] BATCH [USING TIMESTAMP ...]
+    */
+    pub fn is_batch_begin(&self, line: &str) -> bool {
+        let lw = line.trim().to_lowercase();
+        let split: Vec<&str> = lw.split_whitespace().collect();
+
+        matches!(
+            split.as_slice(),
+            ["begin", "batch", ..] | ["begin", "unlogged" | "counter", "batch", ..]
+        )
+    }
+
+    pub fn is_batch_end(&self, line: &str) -> bool {
+        line.trim().to_lowercase().starts_with("apply batch")
+    }
+
     pub fn is_multi_line_comment_clause(&self, line: &str) -> bool {
         if line.contains("/*") || line.contains("*/") {
             return true;
@@ -197,12 +860,7 @@ impl Backend {
     }
 
     // Excluding /* && */
-    pub fn is_line_in_multiline_comment(
-        &self,
-        line: &str,
-        index: usize,
-        lines: &Vec<String>,
-    ) -> bool {
+    pub fn is_line_in_multiline_comment(&self, line: &str, index: usize, lines: &[String]) -> bool {
         if index == 0 || index == lines.len() - 1 || line.contains("/*") || line.contains("*/") {
             return false;
         }
@@ -259,14 +917,21 @@ impl Backend {
         false
     }
 
-    pub fn is_line_inside_init_args(&self, line: &str, index: usize, lines: &Vec<String>) -> bool {
-        if self.line_contains_cql_kw(line)
-            || line.contains(";")
-            || line.contains("{")
-            || line.contains("}")
-            || line.contains("(")
-            || line.contains(")")
-        {
+    /*
+        Line is an arg when it's inside of the parenthesis
+        opened by the statement it belongs to
+
+        CREATE TABLE t (
+            tags set<text>,
+            coords frozen<tuple<int, int>>,     -> depth 1
+            PRIMARY KEY ((id), name)            -> depth 1
+        );                                      -> closes the body
+
+        Depth is counted from the start of the statement, so nested
+        parenthesis on the line itself don't exclude it
+    */
+    pub fn is_line_inside_init_args(&self, line: &str, index: usize, lines: &[String]) -> bool {
+        if line.contains(";") || line.trim().is_empty() {
             return false;
         }
 
@@ -280,75 +945,96 @@ impl Backend {
             return false;
         }
 
-        let mut index_up = index - 1;
-        let mut index_down = index + 1;
-
-        let mut top_bracket = false;
-        let mut bottom_bracket = false;
-
-        while index_up > 0 {
-            let up_line = &lines[index_up];
-            if !top_bracket && (up_line.contains("{") || up_line.contains("(")) {
-                top_bracket = true;
-            }
-
-            if !top_bracket && (up_line.contains("}") || up_line.contains(")")) {
-                return false;
-            }
-
-            if !top_bracket && self.line_contains_cql_kw(up_line) {
-                return false;
-            }
-
-            if top_bracket {
-                break;
-            }
-
-            index_up -= 1;
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(")") || trimmed.starts_with("}") {
+            return false;
         }
 
-        let up_line = &lines[index_up];
-        if !top_bracket && (up_line.contains("{") || up_line.contains("(")) {
-            top_bracket = true;
+        let mut statement_start = index;
+        while statement_start > 0 && !lines[statement_start - 1].contains(";") {
+            statement_start -= 1;
         }
 
-        if !top_bracket && (up_line.contains("}") || up_line.contains(")")) {
-            return false;
+        let mut depth: isize = 0;
+        for up_line in &lines[statement_start..index] {
+            let (opened, closed) = self.count_parenthesis(up_line);
+            depth += opened as isize - closed as isize;
         }
 
-        if !top_bracket && self.line_contains_cql_kw(up_line) {
-            return false;
-        }
+        depth > 0
+    }
+}
 
-        while index_down < lines.len() {
-            let down_line = &lines[index_down];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::FormattingSettings;
 
-            if !bottom_bracket && (down_line.contains("}") || down_line.contains(")")) {
-                bottom_bracket = true;
-            }
+    fn service_with_timeout(query_timeout_ms: u64) -> tower_lsp::LspService<Backend> {
+        let mut settings = CqlSettings::new();
+        settings.query_timeout_ms = query_timeout_ms;
 
-            if !bottom_bracket && (down_line.contains("{") || down_line.contains("(")) {
-                return false;
-            }
+        Backend::test_service_with(
+            settings,
+            FormattingSettings::from_env("7", "false", "preserve", "false"),
+        )
+    }
 
-            if !bottom_bracket && down_line.contains(";") {
-                return false;
-            }
+    async fn delayed(delay_ms: u64) -> Result<Vec<&'static str>, CqlError> {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        Ok(vec!["users"])
+    }
 
-            if !bottom_bracket && self.line_contains_cql_kw(down_line) {
-                return false;
-            }
+    #[tokio::test(start_paused = true)]
+    async fn slow_query_yields_no_items() {
+        let service = service_with_timeout(50);
+        let backend = service.inner();
+
+        assert!(backend.unwrap_query(delayed(51)).await.is_empty());
+        assert!(
+            backend
+                .unwrap_query(std::future::pending::<Result<Vec<&str>, CqlError>>())
+                .await
+                .is_empty()
+        );
+        assert_eq!(backend.unwrap_query(delayed(49)).await, ["users"]);
+    }
 
-            if bottom_bracket {
-                break;
-            }
-            index_down += 1;
-        }
+    #[tokio::test(start_paused = true)]
+    async fn zero_timeout_waits_for_the_query() {
+        let service = service_with_timeout(0);
+        let backend = service.inner();
 
-        if top_bracket && bottom_bracket {
-            return true;
+        let start = tokio::time::Instant::now();
+        assert_eq!(backend.unwrap_query(delayed(60_000)).await, ["users"]);
+        assert!(start.elapsed() >= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn identifiers_are_quoted_when_needed() {
+        for (name, quoted) in [
+            ("users", "users"),
+            ("user_2", "user_2"),
+            ("CaseSensitive", "\"CaseSensitive\""),
+            ("firstName", "\"firstName\""),
+            ("first name", "\"first name\""),
+            ("2fa", "\"2fa\""),
+            ("_id", "\"_id\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("だよ", "\"だよ\""),
+        ] {
+            assert_eq!(Backend::quote_identifier(name), quoted, "{}", name);
+            assert_eq!(Backend::parse_identifier(quoted), name, "{}", quoted);
         }
 
-        false
+        assert_eq!(Backend::parse_identifier("CaseSensitive"), "casesensitive");
+        assert_eq!(
+            Backend::quote_qualified_name("CaseSensitive", "users"),
+            "\"CaseSensitive\".users"
+        );
+        assert_eq!(
+            Backend::parse_qualified_name("\"CaseSensitive\".\"firstName\""),
+            (Some("CaseSensitive".to_string()), "firstName".to_string())
+        );
     }
 }
@@ -0,0 +1,136 @@
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{ChildStdin, ChildStdout, Command, Stdio};
+
+/*
+    Starts the server over stdio with logging enabled && checks that
+    stdout carries nothing but framed JSON-RPC messages
+
+    Nothing listens on 127.0.0.1:1, keyspace completion fails the
+    query_keyspaces connection && logs the error on the way
+*/
+
+fn send(stdin: &mut ChildStdin, message: Value) {
+    let body = message.to_string();
+    write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    stdin.flush().unwrap();
+}
+
+// Panics on anything that isn't a Content-Length framed JSON-RPC message
+fn receive(stdout: &mut impl BufRead) -> Value {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        assert_ne!(stdout.read_line(&mut header).unwrap(), 0, "stdout closed");
+        assert!(header.ends_with("\r\n"), "not a header: {:?}", header);
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        match header.split_once(": ") {
+            Some(("Content-Length", length)) => content_length = Some(length.parse().unwrap()),
+            Some(("Content-Type", _)) => {}
+            _ => panic!("not a header: {:?}", header),
+        }
+    }
+
+    let mut body = vec![0; content_length.expect("Content-Length is missing")];
+    stdout.read_exact(&mut body).unwrap();
+
+    let message: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|e| panic!("{}: {:?}", e, String::from_utf8_lossy(&body)));
+    assert_eq!(message["jsonrpc"], "2.0", "{}", message);
+
+    message
+}
+
+fn response(stdout: &mut BufReader<ChildStdout>, id: u64) -> Value {
+    loop {
+        let message = receive(stdout);
+        if message["id"] == id && message.get("method").is_none() {
+            return message;
+        }
+    }
+}
+
+#[test]
+fn stdout_only_carries_json_rpc() {
+    let dir = std::env::temp_dir().join(format!("cql_lsp_stdio_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log_path = dir.join("output.log");
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_cql_lsp"))
+        .env("XDG_DATA_HOME", &dir)
+        .env("CQL_LSP_ENABLE_LOGGING", "true")
+        .env("CQL_LSP_LOG_LEVEL", "trace")
+        .env("CQL_LSP_LOG_PATH", &log_path)
+        .env("CQL_LSP_DB_URL", "127.0.0.1:1")
+        .env("CQL_LSP_DB_RETRIES", "0")
+        .env("CQL_LSP_DB_TIMEOUT", "1")
+        .env("CQL_LSP_OFFLINE", "false")
+        .env("CQL_LSP_SCHEMA_SNAPSHOT", "false")
+        .env_remove("CQL_LSP_LISTEN")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = server.stdin.take().unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+    );
+    assert!(response(&mut stdout, 1)["result"]["capabilities"].is_object());
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+    );
+
+    let uri = "file:///tmp/stdio.cql";
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": uri, "languageId": "cql", "version": 1, "text": "USE \""}
+        }}),
+    );
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/completion", "params": {
+            "textDocument": {"uri": uri},
+            "position": {"line": 0, "character": 5}
+        }}),
+    );
+    assert!(response(&mut stdout, 2).get("error").is_none());
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+    );
+    response(&mut stdout, 3);
+    send(&mut stdin, json!({"jsonrpc": "2.0", "method": "exit"}));
+    drop(stdin);
+
+    // Whatever is written until the server exits must be framed too
+    let mut rest = Vec::new();
+    stdout.read_to_end(&mut rest).unwrap();
+    let mut rest = &rest[..];
+    while !rest.is_empty() {
+        receive(&mut rest);
+    }
+
+    server.wait().unwrap();
+
+    // Logs went to the file instead
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert!(log.contains("Failed to connect to 127.0.0.1:1"), "{}", log);
+    assert!(log.contains("Query failed"), "{}", log);
+
+    std::fs::remove_dir_all(&dir).ok();
+}